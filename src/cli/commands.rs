@@ -1,123 +1,1445 @@
 // Command handler implementations
 
-use crate::cli::{Cli, Commands, ConfigCommands, InstallCommands, UpgradeRepo};
-use crate::config::CrashConfig;
+#[cfg(windows)]
+use crate::cli::service;
+use crate::cli::{
+    Cli, Commands, ConfigCommands, DaemonCommands, DnsCommands, GeoCommands, InstallCommands,
+    InstallComponent, InstanceCommands, LogsCommands, NotifyCommands, ProxyCommands,
+    SecretCommands, TaskComponent, UpgradeRepo,
+};
+use crate::cli::{editor, selector, setup};
+use crate::config::core::{Core, GeoSource, parse_core_version};
+use crate::config::dns::{EnhancedMode, parse_dns_servers};
+use crate::config::events::{EventKind, events_path, read_events};
+use crate::config::format::ConfigFormat;
+use crate::config::instance::{DEFAULT_INSTANCE, InstanceConfig};
+use crate::config::notify::TelegramConfig;
+use crate::config::web::UiType;
+use crate::config::{
+    CrashConfig, NO_PROXY_ENV, WebConfig, get_config_dir, get_config_path, get_daemon_pid_path,
+    get_log_dir, no_proxy_env, override_proxy, parse_repo_name,
+};
+use crate::convert::{convert_external, convert_subscription};
 use crate::error::{CrashError, Result};
-use crate::log_info;
+use crate::log::{LogFilter, archive_log, log_files};
 use crate::utils::command::execute;
-use crate::utils::monitor::format_status;
-use clap::{CommandFactory, Parser};
-use clap_complete::{Shell, generate};
+use crate::utils::decode::decode_subscription_body;
+use crate::utils::download::download_text;
+use crate::utils::fs::{MtimeWatcher, atomic_write, ensure_dir, is_writable, write_zip};
+use crate::utils::monitor::{format_core_status, format_status, render_lines};
+use crate::utils::pidfile::{PidFile, read_pid};
+use crate::utils::process::{self, is_running, kill_pid, pid_alive};
+#[cfg(unix)]
+use crate::utils::time::{cron_minutes_until, local_clock};
+use crate::utils::{
+    format_age, format_hours, format_size, format_uptime, get_dir_size, is_url,
+    normalize_subscription_url, ping_via_proxy, probe_url,
+};
+use crate::{log_error, log_info, log_warn};
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap_complete::{Shell, generate, generate_to};
+use github_proxy::{Proxy, Resource};
+use reqwest::StatusCode;
 use std::io;
-use std::time::Duration;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Exit code of `status --exit-code` when the core is not running (the LSB
+/// init-script code for "program is not running").
+const STATUS_STOPPED_EXIT_CODE: i32 = 3;
+
+/// Check interval of the daemon registered by `install task --service`,
+/// matching the `daemon --interval` default.
+#[cfg(windows)]
+pub(crate) const DEFAULT_DAEMON_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 pub async fn handle(command: Option<Commands>) -> Result<()> {
+    if let Some(
+        Commands::Install {
+            proxy: Some(proxy), ..
+        }
+        | Commands::Update {
+            proxy: Some(proxy), ..
+        }
+        | Commands::UpdateUrl {
+            proxy: Some(proxy), ..
+        }
+        | Commands::Upgrade {
+            proxy: Some(proxy), ..
+        },
+    ) = command
+    {
+        log_info!("Using proxy {} for this invocation", proxy);
+        override_proxy(proxy);
+    }
+
     match command {
-        Some(Commands::Install { force, command }) => handle_install(force, command).await,
-        Some(Commands::Start { force }) => handle_start(force).await,
-        Some(Commands::Stop { force }) => handle_stop(force).await,
-        Some(Commands::Status) => handle_status().await,
-        Some(Commands::RunTask) => handle_run_task().await,
+        Some(Commands::Install {
+            force,
+            print_url,
+            reset_config,
+            components,
+            command,
+            ..
+        }) => handle_install(force, print_url, reset_config, components, command).await,
+        Some(Commands::Start {
+            instance: Some(name),
+            force,
+            ..
+        }) if name != DEFAULT_INSTANCE => handle_start_instance(&name, force),
+        Some(Commands::Start {
+            force,
+            if_stopped,
+            dump_config,
+            ignore_port_conflict,
+            foreground,
+            env,
+            safe,
+            ..
+        }) => {
+            if safe {
+                handle_start_safe(!ignore_port_conflict, env).await
+            } else if dump_config {
+                handle_dump_config(env)
+            } else if foreground {
+                handle_start_foreground(!ignore_port_conflict, env).await
+            } else if if_stopped {
+                handle_start_if_stopped(!ignore_port_conflict, env)
+            } else {
+                handle_start(force, !ignore_port_conflict, env).await
+            }
+        }
+        Some(Commands::Stop {
+            instance: Some(name),
+            ..
+        }) if name != DEFAULT_INSTANCE => handle_stop_instance(&name),
+        Some(Commands::Stop {
+            force,
+            all,
+            wait,
+            timeout,
+            ..
+        }) => handle_stop(force, all, wait.then(|| Duration::from_secs(timeout))).await,
+        Some(Commands::TestConfig { path }) => handle_test_config(&path),
+        Some(Commands::Status {
+            instance,
+            core,
+            no_net,
+            exit_code,
+            tasks,
+            history,
+            last,
+        }) => {
+            let running = match instance {
+                Some(name) if name != DEFAULT_INSTANCE => handle_instance_status(&name)?,
+                _ => handle_status(core, no_net, tasks).await?,
+            };
+            if history {
+                print_history(last)?;
+            }
+            if exit_code && !running {
+                std::process::exit(STATUS_STOPPED_EXIT_CODE);
+            }
+            Ok(())
+        }
+        Some(Commands::Ping { url }) => handle_ping(&url).await,
+        Some(Commands::Select { group, node }) => handle_select(group, node).await,
+        Some(Commands::Logs {
+            command: Some(LogsCommands::Rotate),
+            ..
+        }) => handle_logs_rotate(),
+        Some(Commands::Logs {
+            export: Some(path), ..
+        }) => handle_logs_export(&path),
+        Some(Commands::Logs { level, since, .. }) => handle_logs(LogFilter { level, since }),
+        Some(Commands::Core { list: true, .. }) => handle_core_list(),
+        Some(Commands::Geo { command }) => handle_geo(command),
+        Some(Commands::Core { value, install, .. }) => handle_core(value, install).await,
+        Some(Commands::Daemon {
+            command: Some(DaemonCommands::Stop),
+            ..
+        }) => handle_daemon_stop(),
+        Some(Commands::Daemon {
+            interval,
+            service: true,
+            ..
+        }) => handle_daemon_service(interval),
+        Some(Commands::Daemon {
+            interval,
+            background,
+            watch_config,
+            ..
+        }) => handle_daemon(interval, background, watch_config).await,
+        Some(Commands::RunTask { components }) => handle_run_task(components).await,
         Some(Commands::RemoveTask) => handle_remove_task(),
-        Some(Commands::UpdateUrl { force }) => handle_update_url(force).await,
-        Some(Commands::Config { command }) => handle_config(command),
-        Some(Commands::Upgrade { repo }) => handle_upgrade(repo).await,
+        Some(Commands::Uninstall {
+            service,
+            keep_config,
+            yes,
+        }) => handle_uninstall(service, keep_config, yes).await,
+        Some(Commands::Prune {
+            keep,
+            older_than,
+            dry_run,
+        }) => handle_prune(keep, older_than, dry_run),
+        Some(Commands::Update { force, .. }) => handle_update_all(force).await,
+        Some(Commands::UpdateUrl {
+            force,
+            config_format,
+            no_validate,
+            prefetch_providers,
+            ..
+        }) => handle_update_url(force, config_format, !no_validate, prefetch_providers).await,
+        Some(Commands::Convert {
+            source,
+            native,
+            converter,
+            output,
+        }) => handle_convert(source, native, &converter, output.as_deref()).await,
+        Some(Commands::Config { command }) => handle_config(command).await,
+        Some(Commands::Upgrade { repo, .. }) => handle_upgrade(repo).await,
         Some(Commands::Ei { args }) => handle_ei(args).await,
-        Some(Commands::Completions { shell }) => handle_completions(shell),
-        None => handle_status().await,
+        Some(Commands::Completions {
+            shell, output_dir, ..
+        }) => handle_completions(shell, output_dir),
+        Some(Commands::Setup) => setup::run().await,
+        None => handle_status(false, false, false).await.map(|_| ()),
     }
 }
 
 /// Handle install command
-async fn handle_install(force: bool, command: Option<InstallCommands>) -> Result<()> {
+async fn handle_install(
+    force: bool,
+    print_url: bool,
+    reset_config: bool,
+    components: Vec<InstallComponent>,
+    command: Option<InstallCommands>,
+) -> Result<()> {
     log_info!(
-        "Executing install command (force: {}, subcommand: {:?})",
+        "Executing install command (force: {}, print url: {}, reset config: {}, components: {:?}, subcommand: {:?})",
         force,
+        print_url,
+        reset_config,
+        components,
         command
     );
 
+    if command.is_some() && !components.is_empty() {
+        return Err(CrashError::Config(
+            "--components cannot be combined with an install subcommand".to_string(),
+        ));
+    }
+
+    if let Some(InstallCommands::Task { service: true }) = command {
+        return handle_service_install();
+    }
+
+    let mut config = CrashConfig::load()?;
+    if let Some(InstallCommands::Geo { ipv6: true }) = command
+        && !config.geo_ipv6
+    {
+        config.geo_ipv6 = true;
+        config.save()?;
+        log_info!("IPv6 geo lists enabled");
+    }
+    if reset_config && config.ensure_default_config(true)? {
+        println!(
+            "Core config reset to the default: {}",
+            config.core_config_path().display()
+        );
+    }
+
+    let components = match command {
+        Some(InstallCommands::Core) => vec![InstallComponent::Core],
+        Some(InstallCommands::Ui) => vec![InstallComponent::Ui],
+        Some(InstallCommands::Geo { .. }) => vec![InstallComponent::Geo],
+        Some(InstallCommands::Task { .. }) => vec![InstallComponent::Task],
+        None if !components.is_empty() => components,
+        None if print_url => vec![
+            InstallComponent::Core,
+            InstallComponent::Ui,
+            InstallComponent::Geo,
+        ],
+        None => {
+            // Install all components
+            config.cancellable(config.install(force)).await?;
+            handle_task()?;
+            println!("Installation completed successfully!");
+            return Ok(());
+        }
+    };
+
+    if print_url {
+        return print_install_urls(&config, components);
+    }
+
+    config
+        .cancellable(install_components(&config, components, force))
+        .await
+}
+
+/// Install `components` in order, each at most once
+async fn install_components(
+    config: &CrashConfig,
+    components: Vec<InstallComponent>,
+    force: bool,
+) -> Result<()> {
+    let mut done = Vec::new();
+    for component in components {
+        if done.contains(&component) {
+            continue;
+        }
+        match component {
+            InstallComponent::Core => {
+                config.install_core(force).await?;
+                println!("Core installation completed successfully!");
+            }
+            InstallComponent::Ui => {
+                config.install_ui(force).await?;
+                println!("UI installation completed successfully!");
+            }
+            InstallComponent::Geo => {
+                config.install_geo(force).await?;
+                println!("Geo installation completed successfully!");
+            }
+            InstallComponent::Task => {
+                handle_task()?;
+                println!("Task installation completed successfully!");
+            }
+        }
+        done.push(component);
+    }
+
+    Ok(())
+}
+
+/// Shown by a bare `crash ei`.
+const EI_USAGE: &str = "\
+Install a binary from a GitHub release or a download URL with easy-install.
+
+Usage: crash ei [OPTIONS] <URL|OWNER/REPO>
+
+Examples:
+  crash ei ahaoboy/coreutils-build --name mktemp
+  crash ei ilai-deutel/kibi --proxy gh-proxy
+
+Run 'crash ei --help' for all options.";
+
+/// Handle ei command: run the embedded easy-install with `args`
+async fn handle_ei(args: Vec<String>) -> Result<()> {
+    log_info!("Executing ei command (args: {:?})", args);
+
+    if args.is_empty() {
+        println!("{}", EI_USAGE);
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg.trim().is_empty()) {
+        return Err(CrashError::Config(format!(
+            "Empty argument passed to ei: {:?}",
+            args
+        )));
+    }
+
+    let parsed = match easy_install::Args::try_parse_from(
+        std::iter::once("crash ei".to_string()).chain(args.iter().cloned()),
+    ) {
+        Ok(parsed) => parsed,
+        Err(e) if !e.use_stderr() => {
+            // --help / --version
+            e.print()?;
+            return Ok(());
+        }
+        Err(e) => {
+            // clap's message points at the offending argument.
+            let _ = e.print();
+            return Err(CrashError::Config(format!(
+                "Invalid ei arguments: {}",
+                e.kind()
+            )));
+        }
+    };
+
+    match easy_install::run_main(parsed).await {
+        Ok(()) => {
+            log_info!("ei {} finished", args.join(" "));
+            Ok(())
+        }
+        Err(e) => Err(CrashError::External(format!(
+            "ei {} failed: {}",
+            args.join(" "),
+            e
+        ))),
+    }
+}
+
+/// Handle `install --print-url`: show where each component would be downloaded from
+fn print_install_urls(config: &CrashConfig, components: Vec<InstallComponent>) -> Result<()> {
+    let mut done = Vec::new();
+    for component in components {
+        if done.contains(&component) {
+            continue;
+        }
+        match component {
+            InstallComponent::Core => println!("core\t{}", config.core_url()?),
+            InstallComponent::Ui => println!("ui\t{}", config.ui_url()?),
+            InstallComponent::Geo => {
+                for (name, url) in config.geo_urls() {
+                    println!("geo\t{}\t{}", name, url);
+                }
+            }
+            InstallComponent::Task => {}
+        }
+        done.push(component);
+    }
+    Ok(())
+}
+
+/// Handle start command
+pub(crate) async fn handle_start(
+    force: bool,
+    check_ports: bool,
+    env: Vec<(String, String)>,
+) -> Result<()> {
+    log_info!(
+        "Executing start command (force: {}, check ports: {})",
+        force,
+        check_ports
+    );
+
+    let mut config = CrashConfig::load()?;
+    config.start_env = env.into_iter().collect();
+    // Only someone at the terminal leaves safe mode: the scheduled `start`
+    // must not bring back the config safe mode is escaping.
+    let force = if setup::is_interactive() && config.leave_safe_mode() {
+        println!("Leaving safe mode, restarting with the normal config");
+        true
+    } else {
+        if config.safe_mode {
+            log_info!("Core is in safe mode, keeping the safe config");
+        }
+        force
+    };
+    config.start_with(force, check_ports).await?;
+    println!("{} proxy service started successfully!", config.core);
+    print_warnings(&config.privilege_warnings());
+
+    tokio::time::sleep(Duration::from_secs_f32(0.1)).await;
+    handle_status(false, false, false).await?;
+
+    Ok(())
+}
+
+/// Handle `start --safe`: restart the core from the minimal recovery config
+async fn handle_start_safe(check_ports: bool, env: Vec<(String, String)>) -> Result<()> {
+    log_info!("Executing start --safe command");
+
+    let mut config = CrashConfig::load()?;
+    config.start_env = env.into_iter().collect();
+    config.start_safe(check_ports).await?;
+    println!(
+        "{} started in safe mode from {} (no TUN, controller on localhost)",
+        config.core,
+        config.safe_config_path().display()
+    );
+    println!("Run 'crash start' to go back to the normal config");
+    Ok(())
+}
+
+/// Print warnings prominently, on stderr so they stand out from the status.
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("⚠️  Warning: {}", warning);
+    }
+}
+
+/// Handle `start --foreground`: run the core in this terminal until Ctrl-C
+async fn handle_start_foreground(check_ports: bool, env: Vec<(String, String)>) -> Result<()> {
+    log_info!("Executing start --foreground command");
+
+    let mut config = CrashConfig::load()?;
+    config.start_env = env.into_iter().collect();
+    print_warnings(&config.privilege_warnings());
+    println!(
+        "Running {} in the foreground, press Ctrl-C to stop",
+        config.core
+    );
+    config.run_foreground(check_ports).await?;
+    println!("{} stopped", config.core);
+    Ok(())
+}
+
+/// Handle `start --if-stopped`: start a stopped core, leave a running one alone
+fn handle_start_if_stopped(check_ports: bool, env: Vec<(String, String)>) -> Result<()> {
+    log_info!("Executing start --if-stopped command");
+
+    let mut config = CrashConfig::load()?;
+    config.start_env = env.into_iter().collect();
+    if config.stop_force {
+        println!(
+            "{} was stopped manually, not starting (use 'crash start -f')",
+            config.core
+        );
+    } else if config.ensure_started(check_ports)? {
+        println!("{} started", config.core);
+        print_warnings(&config.privilege_warnings());
+    } else {
+        println!("{} already running", config.core);
+    }
+    Ok(())
+}
+
+/// Handle `start --dump-config`: show how the core would be started
+fn handle_dump_config(env: Vec<(String, String)>) -> Result<()> {
+    log_info!("Executing start --dump-config command");
+
+    let mut config = CrashConfig::load()?;
+    config.start_env = env.into_iter().collect();
+    println!("{}", render_lines(&config.start_info()));
+    Ok(())
+}
+
+/// Handle stop command, optionally waiting up to `wait` for the core to exit
+async fn handle_stop(force: bool, all: bool, wait: Option<Duration>) -> Result<()> {
+    log_info!(
+        "Executing stop command force: {}, all: {}, wait: {:?}",
+        force,
+        all,
+        wait
+    );
+
+    let mut config = CrashConfig::load()?;
+    if all {
+        let stopped = config.stop_all(force)?;
+        if let Some(timeout) = wait {
+            config.wait_all_stopped(timeout).await?;
+        }
+        for core in Core::ALL {
+            match stopped.iter().find(|(c, _)| *c == core) {
+                Some((_, pid)) => println!("{}: stopped (PID {})", core, pid),
+                None => println!("{}: not running", core),
+            }
+        }
+    } else {
+        config.stop(force)?;
+        if let Some(timeout) = wait {
+            config.wait_stopped(timeout).await?;
+        }
+        println!("{} proxy service stopped successfully!", config.core);
+    }
+
+    tokio::time::sleep(Duration::from_secs_f32(0.1)).await;
+    handle_status(false, false, false).await?;
+
+    Ok(())
+}
+
+/// Handle `start <instance>`
+fn handle_start_instance(name: &str, force: bool) -> Result<()> {
+    log_info!(
+        "Executing start command (instance: {}, force: {})",
+        name,
+        force
+    );
+
+    let mut config = CrashConfig::load()?;
+    if config.start_instance(name, force)? {
+        println!("Instance {} started", name);
+    } else {
+        println!("Instance {} already running (use -f to restart)", name);
+    }
+    print_warnings(&config.instance(name)?.privilege_warnings(name));
+    handle_instance_status(name)?;
+    Ok(())
+}
+
+/// Handle `stop <instance>`
+fn handle_stop_instance(name: &str) -> Result<()> {
+    log_info!("Executing stop command (instance: {})", name);
+
+    let mut config = CrashConfig::load()?;
+    config.stop_instance(name)?;
+    println!("Instance {} stopped", name);
+    Ok(())
+}
+
+/// Handle `status <instance>`; returns whether the instance is running
+fn handle_instance_status(name: &str) -> Result<bool> {
     let config = CrashConfig::load()?;
+    let instance = config.instance(name)?;
+    let pid = instance.pid(name);
+
+    let mut lines = vec![
+        ("instance", name.to_string()),
+        ("core", instance.core.to_string()),
+        ("config", instance.config_path(name).display().to_string()),
+        ("controller", instance.web_host.clone()),
+        ("mixed port", instance.mixed_port.to_string()),
+    ];
+    match pid {
+        Some(pid) => {
+            lines.push(("pid", pid.to_string()));
+            let uptime = if instance.start_time > 0 {
+                format_uptime(instance.start_time)
+            } else {
+                "unknown".to_string()
+            };
+            lines.push(("status", format!("✅ {}", uptime)));
+        }
+        None => lines.push(("status", "❌ stopped".to_string())),
+    }
+    println!("{}", render_lines(&lines));
+    Ok(pid.is_some())
+}
 
+/// Handle `config instance` and its subcommands
+fn handle_instances(command: Option<InstanceCommands>) -> Result<()> {
     match command {
-        Some(InstallCommands::Core) => {
-            config.install_core(force).await?;
-            println!("Core installation completed successfully!");
+        None => {
+            let config = CrashConfig::load()?;
+            println!(
+                "{:16} {:8} {:12} {:6} status",
+                "name", "core", "controller", "mixed"
+            );
+            println!(
+                "{:16} {:8} {:12} {:6} {}",
+                DEFAULT_INSTANCE,
+                config.core,
+                config.web.host,
+                config.mixed_port(),
+                if is_running(&config.core.exe_name()) {
+                    "running"
+                } else {
+                    "stopped"
+                }
+            );
+            for (name, instance) in &config.instances {
+                println!(
+                    "{:16} {:8} {:12} {:6} {}",
+                    name,
+                    instance.core,
+                    instance.web_host,
+                    instance.mixed_port,
+                    if instance.pid(name).is_some() {
+                        "running"
+                    } else {
+                        "stopped"
+                    }
+                );
+            }
         }
-        Some(InstallCommands::Ui) => {
-            config.install_ui(force).await?;
-            println!("UI installation completed successfully!");
+        Some(InstanceCommands::Add {
+            name,
+            core,
+            host,
+            mixed_port,
+        }) => {
+            let mut config = CrashConfig::load()?;
+            config.add_instance(
+                &name,
+                InstanceConfig {
+                    core,
+                    web_host: host,
+                    mixed_port,
+                    start_time: 0,
+                },
+            )?;
+            config.save()?;
+            println!(
+                "Instance {} added, start it with 'crash start {}'",
+                name, name
+            );
         }
-        Some(InstallCommands::Geo) => {
-            config.install_geo(force).await?;
-            println!("Geo installation completed successfully!");
+        Some(InstanceCommands::Remove { name }) => {
+            let mut config = CrashConfig::load()?;
+            config.stop_instance(&name)?;
+            config.instances.remove(&name);
+            config.save()?;
+
+            let dir = InstanceConfig::dir(&name);
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir)?;
+            }
+            println!("Instance {} removed", name);
         }
-        Some(InstallCommands::Task) => {
-            handle_task()?;
-            println!("Task installation completed successfully!");
+    }
+    Ok(())
+}
+
+/// Handle test-config command
+fn handle_test_config(path: &Path) -> Result<()> {
+    log_info!("Executing test-config command (path: {})", path.display());
+
+    let config = CrashConfig::load()?;
+    let output = config.test_config(path)?;
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    println!("{} accepts {}", config.core, path.display());
+    Ok(())
+}
+
+/// Handle status command; returns whether the core process is alive
+async fn handle_status(core_only: bool, offline: bool, tasks: bool) -> Result<bool> {
+    log_info!(
+        "Executing status command (core only: {}, offline: {}, tasks: {})",
+        core_only,
+        offline,
+        tasks
+    );
+    let mut config = CrashConfig::load()?;
+    let running = is_running(&config.core.exe_name());
+    // Status must keep working on a read-only filesystem.
+    if config.reconcile_start_time(running)
+        && let Err(e) = config.save()
+    {
+        log_warn!("Failed to save reconciled start time: {}", e);
+    }
+
+    let status = if core_only {
+        format_core_status(&config, offline)
+    } else {
+        format_status(&config, offline, tasks.then(scheduled_tasks)).await
+    };
+    println!("{}", status);
+    Ok(running)
+}
+
+/// Print the newest `last` core events of `status --history`, and how
+/// often the core restarted in the last day
+fn print_history(last: usize) -> Result<()> {
+    let events = read_events()?;
+    if events.is_empty() {
+        println!(
+            "\nNo core events recorded yet ({})",
+            events_path().display()
+        );
+        return Ok(());
+    }
+
+    let shown = &events[events.len().saturating_sub(last)..];
+    println!("\nHistory (last {} of {}):", shown.len(), events.len());
+    for event in shown {
+        println!(
+            "  {}  {:7} {:7} {:12} {} ago",
+            humantime::format_rfc3339_seconds(event.time),
+            event.kind.to_string(),
+            event.core.to_string(),
+            event.reason.to_string(),
+            format_age(event.time)
+        );
+    }
+
+    let day_ago = SystemTime::now() - Duration::from_secs(86400);
+    let restarts = events
+        .iter()
+        .filter(|e| e.kind == EventKind::Restart && e.time >= day_ago)
+        .count();
+    println!("Restarts in the last 24h: {}", restarts);
+    Ok(())
+}
+
+/// Handle `config proxy test`: fetch a small file through `proxy` without
+/// touching the stored config
+async fn handle_proxy_test(proxy: Proxy) -> Result<()> {
+    log_info!("Executing config proxy test command (proxy: {})", proxy);
+
+    let (url, status, latency) = probe_proxy(proxy).await?;
+    println!(
+        "{} is reachable: {} in {} ms ({})",
+        proxy,
+        status,
+        latency.as_millis(),
+        url
+    );
+    Ok(())
+}
+
+/// Show or set the proxies tried after the configured one. `auto` probes
+/// every mirror and keeps the reachable ones, fastest first.
+async fn handle_proxy_fallbacks(proxies: Vec<Proxy>, auto: bool, clear: bool) -> Result<()> {
+    log_info!(
+        "Executing config proxy fallbacks command (auto: {}, clear: {})",
+        auto,
+        clear
+    );
+
+    let fallbacks = if clear {
+        Vec::new()
+    } else if auto {
+        let mut reachable = Vec::new();
+        for &proxy in Proxy::value_variants() {
+            match probe_proxy(proxy).await {
+                Ok((_, _, latency)) => {
+                    println!("  {:12} {} ms", proxy.to_string(), latency.as_millis());
+                    reachable.push((latency, proxy));
+                }
+                Err(e) => println!("  {:12} ❌ {}", proxy.to_string(), e),
+            }
         }
-        None => {
-            // Install all components
-            config.install(force).await?;
-            handle_task()?;
-            println!("Installation completed successfully!");
+        reachable.sort_by_key(|&(latency, _)| latency);
+        reachable.into_iter().map(|(_, proxy)| proxy).collect()
+    } else if !proxies.is_empty() {
+        proxies
+    } else {
+        let config = CrashConfig::load()?;
+        let chain = config.proxy_chain();
+        println!(
+            "{}",
+            chain
+                .iter()
+                .map(Proxy::to_string)
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        );
+        return Ok(());
+    };
+
+    mutate_config(|c| {
+        c.proxy_fallbacks = fallbacks;
+        c.proxy_fallbacks.retain(|&proxy| proxy != c.proxy);
+        if c.proxy_fallbacks.is_empty() {
+            "Proxy fallbacks cleared".to_string()
+        } else {
+            format!(
+                "Proxy fallbacks set to: {}",
+                c.proxy_fallbacks
+                    .iter()
+                    .map(Proxy::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    })
+}
+
+/// Download a small repository file through `proxy`, returning the URL,
+/// status and latency. Fails unless the status is a success.
+pub(crate) async fn probe_proxy(proxy: Proxy) -> Result<(String, StatusCode, Duration)> {
+    let url = Resource::File {
+        owner: "ahaoboy".to_string(),
+        repo: "crash".to_string(),
+        reference: "main".to_string(),
+        path: "README.md".to_string(),
+    }
+    .url(&proxy)
+    .ok_or_else(|| {
+        CrashError::Download(format!("Proxy {} cannot serve repository files", proxy))
+    })?;
+
+    let (status, latency) = probe_url(&url, Duration::from_secs(10)).await?;
+    if !status.is_success() {
+        return Err(CrashError::Download(format!(
+            "{} returned {} via {}",
+            url, status, proxy
+        )));
+    }
+    Ok((url, status, latency))
+}
+
+/// Handle ping command
+async fn handle_ping(url: &str) -> Result<()> {
+    log_info!("Executing ping command (url: {})", url);
+
+    let config = CrashConfig::load()?;
+    let port = config.proxy_port();
+
+    let (status, latency) = ping_via_proxy(url, port, Duration::from_secs(10)).await?;
+    if !status.is_success() {
+        return Err(CrashError::Download(format!(
+            "{} returned {} via 127.0.0.1:{}",
+            url, status, port
+        )));
+    }
+
+    println!(
+        "{} via 127.0.0.1:{}: {} in {}ms",
+        url,
+        port,
+        status,
+        latency.as_millis()
+    );
+    Ok(())
+}
+
+/// Handle select command: switch a group's node, show a group's nodes, or
+/// pick both interactively
+async fn handle_select(group: Option<String>, node: Option<String>) -> Result<()> {
+    log_info!(
+        "Executing select command (group: {:?}, node: {:?})",
+        group,
+        node
+    );
+
+    let config = CrashConfig::load()?;
+    match (group, node) {
+        (Some(group), Some(node)) => {
+            config.select_proxy(&group, &node).await?;
+            println!("{}: {}", group, node);
+        }
+        (Some(group), None) => {
+            let groups = config.proxy_groups().await?;
+            let found = groups
+                .iter()
+                .find(|g| g.name == group)
+                .ok_or_else(|| CrashError::Config(format!("No selectable group {:?}", group)))?;
+            for node in &found.nodes {
+                let marker = if node.name == found.now { "*" } else { " " };
+                match node.delay {
+                    Some(ms) => println!("{} {} ({}ms)", marker, node.name, ms),
+                    None => println!("{} {}", marker, node.name),
+                }
+            }
+        }
+        (None, _) if setup::is_interactive() => selector::run(&config).await?,
+        (None, _) => {
+            for group in config.proxy_groups().await? {
+                println!("{}: {}", group.name, group.now);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle logs command
+fn handle_logs(filter: LogFilter) -> Result<()> {
+    log_info!("Executing logs command (filter: {:?})", filter);
+
+    let now = SystemTime::now();
+    for path in log_files(&CrashConfig::load()?.log_dir()) {
+        let content = std::fs::read_to_string(&path)?;
+        for line in filter.apply(&content, now) {
+            println!("{}", line);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle `logs rotate`: archive the current log now
+fn handle_logs_rotate() -> Result<()> {
+    log_info!("Executing logs rotate command");
+
+    let config = CrashConfig::load()?;
+    match archive_log(&config.log_dir(), config.log_archives)? {
+        Some(archive) => println!("Log archived to {}", archive.display()),
+        None => println!("Log is empty, nothing to rotate"),
+    }
+    Ok(())
+}
+
+/// Handle `logs --export`: bundle everything needed for a bug report into a zip
+fn handle_logs_export(path: &Path) -> Result<()> {
+    log_info!("Executing logs --export command (path: {})", path.display());
+
+    let config = CrashConfig::load()?;
+    let core_version = config
+        .get_version()
+        .unwrap_or_else(|e| format!("unknown ({})", e));
+
+    let mut entries = vec![
+        (
+            "version.txt".to_string(),
+            format!(
+                "crash {}\n{} {}\n",
+                crate::cli::VERSION,
+                config.core,
+                core_version
+            ),
+        ),
+        (
+            "crash_config.json".to_string(),
+            serde_json::to_string_pretty(&config.redacted())?,
+        ),
+        ("status.txt".to_string(), format_core_status(&config, false)),
+        ("start.txt".to_string(), render_lines(&config.start_info())),
+    ];
+    for log in log_files(&config.log_dir()) {
+        let Some(name) = log.file_name() else {
+            continue;
+        };
+        let content = std::fs::read_to_string(&log)?;
+        entries.push((format!("logs/{}", name.to_string_lossy()), content));
+    }
+
+    let entries: Vec<_> = entries
+        .into_iter()
+        .map(|(name, content)| (name, config.redact(&content)))
+        .collect();
+    write_zip(path, &entries)?;
+
+    println!("Wrote {} files to {}", entries.len(), path.display());
+    Ok(())
+}
+
+/// Handle core command: show or switch the active core
+async fn handle_core(value: Option<Core>, install: bool) -> Result<()> {
+    log_info!(
+        "Executing core command (value: {:?}, install: {})",
+        value,
+        install
+    );
+
+    let mut config = CrashConfig::load()?;
+    let Some(core) = value else {
+        println!("{}", config.core);
+        return Ok(());
+    };
+
+    let old = config.core;
+    // `stop` only knows the active core, so stop the old one before switching.
+    if old != core && process::is_running(&old.exe_name()) {
+        config.stop(false)?;
+        println!("Stopped {}, run 'crash start' to start {}", old, core);
+    }
+
+    config.core = core;
+    let mut applied = if old != core {
+        config.apply_core_baseline(old)
+    } else {
+        Vec::new()
+    };
+    config.save()?;
+    println!("Core set to: {}", core);
+
+    if (old != core || install) && config.ensure_default_config(false)? {
+        applied.push(format!(
+            "default config {}",
+            config.core_config_path().display()
+        ));
+    }
+    if !applied.is_empty() {
+        println!("Applied {} defaults:", core);
+        for line in &applied {
+            println!("  {}", line);
+        }
+    }
+
+    if install {
+        config.install_core(false).await?;
+        println!("{} installed successfully!", core);
+    } else if !core.exe_path(&get_config_dir()).exists() {
+        println!(
+            "{} is not installed yet, run 'crash core {} --install'",
+            core,
+            core.name().to_lowercase()
+        );
+    }
+    Ok(())
+}
+
+/// Handle `core --list`: each core with its release asset for the target
+fn handle_core_list() -> Result<()> {
+    let config = CrashConfig::load()?;
+    println!("Target: {}", config.target);
+    if let Some(url) = &config.core_url_override {
+        println!("Custom core URL: {} (used instead of crash-assets)", url);
+    } else if let Some(version) = &config.core_version {
+        println!(
+            "Pinned core version: {} (from the upstream release)",
+            version
+        );
+    }
+
+    for core in Core::ALL {
+        let selected = if core == config.core { "*" } else { " " };
+        let asset = match core.release_file_name(&config.target) {
+            Ok(name) => format!("✅ {}", name),
+            Err(_) => "❌ no build for this target".to_string(),
+        };
+        println!(
+            "{} {:8} {:36} {}",
+            selected,
+            core.name(),
+            asset,
+            core.github()
+        );
+    }
+    Ok(())
+}
+
+/// Handle `geo list` / `geo clean`
+fn handle_geo(command: GeoCommands) -> Result<()> {
+    log_info!("Executing geo command: {:?}", command);
+
+    let config = CrashConfig::load()?;
+    let files = config.geo_files()?;
+    match command {
+        GeoCommands::List => {
+            if files.is_empty() {
+                println!("No geo files installed");
+            }
+            for (path, size, used) in files {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let state = if used {
+                    format!("used by {}", config.core)
+                } else {
+                    "unused".to_string()
+                };
+                println!("{:24} {:>10}  {}", name, format_size(size), state);
+            }
+        }
+        GeoCommands::Clean { dry_run } => {
+            let mut reclaimed = 0;
+            for (path, size, _) in files.into_iter().filter(|(_, _, used)| !used) {
+                if dry_run {
+                    println!("Would remove {} ({})", path.display(), format_size(size));
+                } else {
+                    std::fs::remove_file(&path)?;
+                    println!("Removed {} ({})", path.display(), format_size(size));
+                }
+                reclaimed += size;
+            }
+            match (reclaimed, dry_run) {
+                (0, _) => println!("No unused geo files for {}", config.core),
+                (n, true) => println!("Would reclaim {}", format_size(n)),
+                (n, false) => println!("Reclaimed {}", format_size(n)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle daemon command: run `start` every `interval` until killed
+async fn handle_daemon(interval: Duration, background: bool, watch_config: bool) -> Result<()> {
+    log_info!(
+        "Executing daemon command (interval: {:?}, background: {}, watch config: {})",
+        interval,
+        background,
+        watch_config
+    );
+
+    if background {
+        if cfg!(windows) {
+            return Err(CrashError::Platform(
+                "daemon --background is not supported on Windows, use 'crash install task --service' instead"
+                    .to_string(),
+            ));
+        }
+        let exe = std::env::current_exe()?;
+        let mut args = vec![
+            "daemon".to_string(),
+            "--interval".to_string(),
+            humantime::format_duration(interval).to_string(),
+        ];
+        if watch_config {
+            args.push("--watch-config".to_string());
+        }
+        process::start(&exe, args, vec![])?;
+        println!(
+            "crash daemon started in the background, PID file: {}",
+            get_daemon_pid_path().display()
+        );
+        return Ok(());
+    }
+
+    run_daemon(interval, watch_config, std::future::pending()).await
+}
+
+/// How often `daemon --watch-config` looks at the core config.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Quiet time after the last write before the core is reloaded.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The daemon loop: check the core every `interval` until `shutdown`
+/// completes, and with `watch_config` reload it when its config file
+/// changes. Shared by `crash daemon` and the Windows service.
+pub(crate) async fn run_daemon(
+    interval: Duration,
+    watch_config: bool,
+    shutdown: impl std::future::Future<Output = ()>,
+) -> Result<()> {
+    let _pidfile = PidFile::acquire(&get_daemon_pid_path())?;
+    let mut shutdown = std::pin::pin!(shutdown);
+    let mut watcher: Option<MtimeWatcher> = None;
+    loop {
+        match CrashConfig::load() {
+            Ok(config) if config.stop_force => {
+                log_info!("Core was stopped manually, daemon leaves it stopped");
+            }
+            Ok(mut config) => {
+                if let Err(e) = config.start(false).await {
+                    log_error!("Daemon failed to start the core: {}", e);
+                }
+                // Follow a core switch to the new core's config file.
+                let path = config.core_config_path();
+                if watch_config && watcher.as_ref().is_none_or(|w| w.path() != path) {
+                    log_info!("Daemon watching {}", path.display());
+                    watcher = Some(MtimeWatcher::new(path, WATCH_DEBOUNCE));
+                }
+            }
+            Err(e) => log_error!("Daemon failed to load the configuration: {}", e),
+        }
+
+        let mut next_check = std::pin::pin!(tokio::time::sleep(interval));
+        loop {
+            tokio::select! {
+                _ = &mut next_check => break,
+                _ = &mut shutdown => {
+                    log_info!("Daemon shutting down");
+                    return Ok(());
+                }
+                _ = tokio::time::sleep(WATCH_POLL_INTERVAL), if watcher.is_some() => {
+                    if watcher.as_mut().is_some_and(MtimeWatcher::poll) {
+                        reload_changed_config().await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Apply an edited core config to the running core, for `daemon --watch-config`.
+async fn reload_changed_config() {
+    let mut config = match CrashConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            log_error!("Daemon failed to load the configuration: {}", e);
+            return;
+        }
+    };
+    if config.stop_force || !is_running(&config.core.exe_name()) {
+        log_info!("Core config changed, but the core is not running");
+        return;
+    }
+    log_info!(
+        "Core config {} changed, reloading {}",
+        config.core_config_path().display(),
+        config.core
+    );
+    match config.reload_core().await {
+        Ok(true) => log_info!("Restarted {} with the new config", config.core),
+        Ok(false) => {}
+        Err(e) => log_error!("Daemon failed to reload the core: {}", e),
+    }
+}
+
+/// Handle `daemon stop`: terminate the daemon named in the PID file
+fn handle_daemon_stop() -> Result<()> {
+    log_info!("Executing daemon stop command");
+
+    let path = get_daemon_pid_path();
+    let Some(pid) = read_pid(&path) else {
+        println!("crash daemon is not running");
+        return Ok(());
+    };
+
+    if pid_alive(pid) {
+        kill_pid(pid)?;
+        println!("crash daemon (PID {}) stopped", pid);
+    } else {
+        println!("crash daemon is not running (removed stale PID file)");
+    }
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+/// Handle `install task --service`: register the daemon as a Windows service
+#[cfg(windows)]
+fn handle_service_install() -> Result<()> {
+    log_info!("Executing service install command");
+
+    if service::install(DEFAULT_DAEMON_INTERVAL)? {
+        println!(
+            "Service '{}' installed and started, it restarts automatically on failure",
+            service::SERVICE_NAME
+        );
+    } else {
+        println!("Service '{}' already exists", service::SERVICE_NAME);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn handle_service_install() -> Result<()> {
+    Err(service_unsupported())
+}
+
+/// Handle the hidden `daemon --service`, which the service manager runs
+#[cfg(windows)]
+fn handle_daemon_service(interval: Duration) -> Result<()> {
+    log_info!("Executing daemon as a service (interval: {:?})", interval);
+    service::run(interval)
+}
+
+#[cfg(unix)]
+fn handle_daemon_service(_interval: Duration) -> Result<()> {
+    Err(service_unsupported())
+}
+
+/// Handle uninstall command: remove the scheduled tasks, stop the daemon,
+/// cores and instances, then delete the config directory (or all but the
+/// configs with `keep_config`)
+async fn handle_uninstall(service: bool, keep_config: bool, yes: bool) -> Result<()> {
+    log_info!(
+        "Executing uninstall command (service: {}, keep_config: {}, yes: {})",
+        service,
+        keep_config,
+        yes
+    );
+
+    if service {
+        return handle_service_uninstall();
+    }
+
+    // Asked before anything is touched, so declining changes nothing.
+    let config_dir = get_config_dir();
+    if !keep_config && !yes && config_dir.exists() {
+        if !setup::is_interactive() {
+            return Err(CrashError::Config(format!(
+                "Pass --yes to delete {} without a prompt, or --keep-config to keep the configs",
+                config_dir.display()
+            )));
+        }
+        let prompt = format!("Delete {} including your configs?", config_dir.display());
+        if !setup::confirm(&prompt)? {
+            println!("Nothing removed; 'crash uninstall --keep-config' keeps the configs");
+            return Ok(());
+        }
+    }
+
+    // Tasks and the service go first so nothing starts the core again.
+    if let Err(e) = handle_remove_task() {
+        log_warn!("Failed to remove scheduled tasks: {}", e);
+        println!("Could not remove scheduled tasks: {}", e);
+    }
+    #[cfg(windows)]
+    if let Err(e) = handle_service_uninstall() {
+        println!(
+            "Could not remove service '{}': {}",
+            service::SERVICE_NAME,
+            e
+        );
+    }
+    handle_daemon_stop()?;
+
+    let mut config = CrashConfig::load()?;
+    let instances: Vec<String> = config.instances.keys().cloned().collect();
+    for name in instances {
+        config.stop_instance(&name)?;
+    }
+    for (core, pid) in config.stop_all(true)? {
+        println!("{}: stopped (PID {})", core, pid);
+    }
+    // A running executable can't be deleted on Windows.
+    config.wait_all_stopped(Duration::from_secs(10)).await?;
+
+    let mut failed = 0;
+    for path in config.uninstall_paths(keep_config)? {
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => println!("Removed {}", path.display()),
+            Err(e) => {
+                failed += 1;
+                println!("Could not remove {}: {}", path.display(), e);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(CrashError::Config(format!(
+            "{} path(s) could not be removed",
+            failed
+        )));
+    }
+    if keep_config {
+        println!("Configs kept in {}", config_dir.display());
+    }
+    println!("crash uninstalled");
+    Ok(())
+}
+
+/// Handle `prune`: delete what `CrashConfig::prunable` lists
+fn handle_prune(keep: usize, older_than: Option<Duration>, dry_run: bool) -> Result<()> {
+    log_info!(
+        "Executing prune command (keep: {}, older than: {:?}, dry_run: {})",
+        keep,
+        older_than,
+        dry_run
+    );
+
+    let paths = CrashConfig::load()?.prunable(keep, older_than)?;
+    if paths.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+    let mut reclaimed = 0;
+    for (path, size, modified) in paths {
+        if dry_run {
+            println!(
+                "Would remove {} ({}, {} old)",
+                path.display(),
+                format_size(size),
+                format_age(modified)
+            );
+        } else {
+            if path.is_dir() {
+                std::fs::remove_dir_all(&path)?;
+            } else {
+                std::fs::remove_file(&path)?;
+            }
+            log_info!("Pruned {}", path.display());
+            println!("Removed {} ({})", path.display(), format_size(size));
         }
+        reclaimed += size;
+    }
+    if dry_run {
+        println!("Would reclaim {}", format_size(reclaimed));
+    } else {
+        println!("Reclaimed {}", format_size(reclaimed));
     }
-
     Ok(())
 }
 
-async fn handle_ei(args: Vec<String>) -> Result<()> {
-    log_info!("Executing ei command (args: {:?})", args);
-    let mut v = vec!["ei".to_string()];
-    v.extend(args);
-    easy_install::run_main(easy_install::Args::parse_from(v))
-        .await
-        .map_err(|e| CrashError::External(e.to_string()))
-}
-
-/// Handle start command
-async fn handle_start(force: bool) -> Result<()> {
-    log_info!("Executing start command");
-
-    let mut config = CrashConfig::load()?;
-    config.start(force).await?;
-    println!("{} proxy service started successfully!", config.core);
-
-    tokio::time::sleep(Duration::from_secs_f32(0.1)).await;
-    handle_status().await?;
-
+#[cfg(windows)]
+fn handle_service_uninstall() -> Result<()> {
+    if service::uninstall()? {
+        println!("Service '{}' removed", service::SERVICE_NAME);
+    } else {
+        println!("Service '{}' is not installed", service::SERVICE_NAME);
+    }
     Ok(())
 }
 
-/// Handle stop command
-async fn handle_stop(force: bool) -> Result<()> {
-    log_info!("Executing stop command force: {}", force);
-
-    let mut config = CrashConfig::load()?;
-    config.stop(force)?;
-    println!("{} proxy service stopped successfully!", config.core);
-
-    tokio::time::sleep(Duration::from_secs_f32(0.1)).await;
-    handle_status().await?;
-
-    Ok(())
+#[cfg(unix)]
+fn handle_service_uninstall() -> Result<()> {
+    Err(service_unsupported())
 }
 
-/// Handle status command
-async fn handle_status() -> Result<()> {
-    log_info!("Executing status command");
-    let config = CrashConfig::load()?;
-    let status = format_status(&config).await;
-    println!("{}", status);
-    Ok(())
+#[cfg(unix)]
+fn service_unsupported() -> CrashError {
+    CrashError::Platform(
+        "Services are only supported on Windows, use 'crash install task' or \
+         'crash daemon --background' instead"
+            .to_string(),
+    )
 }
 
 /// Cron schedule entries installed on Unix systems: (cron expression, crash subcommand).
 #[cfg(unix)]
 const UNIX_SCHEDULES: [(&str, &str); 2] = [("0 3 * * 3", "run-task"), ("*/10 * * * *", "start")];
 
+/// Extra arguments of the scheduled `subcmd`: the run-task components when
+/// they differ from the default, so existing entries stay unchanged.
+fn task_args(subcmd: &str, components: &[TaskComponent]) -> String {
+    if !subcmd.ends_with("run-task") || components == TaskComponent::DEFAULT {
+        return String::new();
+    }
+    format!(
+        " --components {}",
+        components
+            .iter()
+            .map(TaskComponent::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
 #[cfg(unix)]
-fn handle_task() -> Result<()> {
+pub(crate) fn handle_task() -> Result<()> {
     use which::which;
 
     use crate::utils::get_user;
@@ -149,15 +1471,26 @@ fn handle_task() -> Result<()> {
         }
     }
 
+    let components = CrashConfig::load()?.task_components;
     for (cron, subcmd) in UNIX_SCHEDULES {
         let cmd = format!("{} {}", exe_path, subcmd);
-        let entry = format!("{} {}", cron, cmd);
+        let entry = format!("{} {}{}", cron, cmd, task_args(subcmd, &components));
 
         if let Ok(list) = execute("crontab", &["-l"]) {
             if list.lines().any(|line| line == entry) {
                 println!("Scheduled task already exists");
                 continue;
             }
+            // Same task with other arguments, e.g. older components.
+            let base = format!("{} {}", cron, cmd);
+            if list.lines().any(|line| line.starts_with(&base)) {
+                println!(
+                    "Scheduled '{}' task already exists with other arguments; run \
+                     'crash remove-task' first to replace it",
+                    subcmd
+                );
+                continue;
+            }
 
             let sh = format!("(crontab -l 2>/dev/null; echo '{}') | crontab -", entry);
             execute("bash", &["-c", &sh])?;
@@ -169,8 +1502,21 @@ fn handle_task() -> Result<()> {
     Ok(())
 }
 
+/// Windows scheduled tasks: (name, crash arguments, schedule, days, start time).
+#[cfg(windows)]
+const WINDOWS_TASKS: [(&str, &str, &str, &str, &str); 2] = [
+    (
+        "CrashRunTask",
+        "--schedule run-task",
+        "WEEKLY",
+        "WED",
+        "03:00",
+    ),
+    ("CrashStart", "--schedule start", "MINUTE", "", "00:00"),
+];
+
 #[cfg(windows)]
-fn handle_task() -> Result<()> {
+pub(crate) fn handle_task() -> Result<()> {
     log_info!("Executing task command");
 
     let exe = std::env::current_exe().map_err(|e| {
@@ -179,18 +1525,8 @@ fn handle_task() -> Result<()> {
 
     let exe_path = exe.to_string_lossy();
 
-    let tasks = [
-        (
-            "CrashRunTask",
-            "--schedule run-task",
-            "WEEKLY",
-            "WED",
-            "03:00",
-        ),
-        ("CrashStart", "--schedule start", "MINUTE", "", "00:00"),
-    ];
-
-    for (name, subcmd, schedule, days, time) in tasks {
+    let components = CrashConfig::load()?.task_components;
+    for (name, subcmd, schedule, days, time) in WINDOWS_TASKS {
         if execute("schtasks", &["/query", "/tn", name])
             .unwrap_or_default()
             .contains(name)
@@ -198,7 +1534,12 @@ fn handle_task() -> Result<()> {
             continue;
         }
 
-        let full_cmd = format!("\"{}\" {}", exe_path, subcmd);
+        let full_cmd = format!(
+            "\"{}\" {}{}",
+            exe_path,
+            subcmd,
+            task_args(subcmd, &components)
+        );
 
         let mut args = vec!["/create", "/tn", name, "/tr", &full_cmd, "/sc", schedule];
 
@@ -227,7 +1568,7 @@ fn handle_task() -> Result<()> {
 #[cfg(windows)]
 fn handle_remove_task() -> Result<()> {
     println!("Removing Windows scheduled task");
-    for name in ["CrashRunTask", "CrashStart"] {
+    for (name, ..) in WINDOWS_TASKS {
         let status = execute("schtasks", &["/delete", "/tn", name, "/f"]);
         if status.is_ok() {
             println!("Task '{}' deleted successfully.", name);
@@ -243,33 +1584,27 @@ pub fn handle_remove_task() -> Result<()> {
     println!("Removing Unix scheduled task");
 
     let current = execute("crontab", &["-l"])?;
-    let mut new_lines = Vec::new();
 
     let exe = std::env::current_exe().map_err(|e| {
         CrashError::Platform(format!("Failed to get current executable path: {}", e))
     })?;
 
-    let exe_path = exe.to_string_lossy();
-
-    for (cron, subcmd) in UNIX_SCHEDULES {
-        let cmd = format!("{} {}", exe_path, subcmd);
-        let entry = format!("{} {}", cron, cmd);
-
-        for line in current.lines() {
-            if !line.contains(&entry) {
-                new_lines.push(line);
-            } else {
-                println!("Removed: {}", line);
-            }
-        }
+    let (kept, removed) = remove_cron_entries(&current, &exe.to_string_lossy());
+    if removed.is_empty() {
+        println!("No cron task to remove.");
+        return Ok(());
+    }
+    for line in removed {
+        println!("Removed: {}", line);
     }
 
     let mut child = std::process::Command::new("crontab")
+        .arg("-")
         .stdin(std::process::Stdio::piped())
         .spawn()?;
 
     if let Some(stdin) = child.stdin.as_mut() {
-        std::io::Write::write_all(stdin, new_lines.join("\n").as_bytes())?;
+        std::io::Write::write_all(stdin, kept.as_bytes())?;
     }
 
     let status = child.wait()?;
@@ -281,34 +1616,298 @@ pub fn handle_remove_task() -> Result<()> {
     Ok(())
 }
 
-/// Handle run-task command
-async fn handle_run_task() -> Result<()> {
-    log_info!("Executing run-task command");
+/// `crontab` without crash's entries for `exe_path` (whatever their
+/// arguments), and the lines that were dropped.
+#[cfg(unix)]
+fn remove_cron_entries<'a>(crontab: &'a str, exe_path: &str) -> (String, Vec<&'a str>) {
+    let bases: Vec<String> = UNIX_SCHEDULES
+        .iter()
+        .map(|(cron, subcmd)| format!("{} {} {}", cron, exe_path, subcmd))
+        .collect();
+    let is_crash_entry = |line: &str| {
+        bases.iter().any(|base| {
+            line.strip_prefix(base.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        })
+    };
+
+    let (removed, kept): (Vec<&str>, Vec<&str>) =
+        crontab.lines().partition(|line| is_crash_entry(line));
+    let mut kept = kept.join("\n");
+    if !kept.is_empty() {
+        kept.push('\n');
+    }
+    (kept, removed)
+}
+
+/// Whether each of crash's cron entries exists, with its next run, for
+/// `status --tasks`.
+#[cfg(unix)]
+fn scheduled_tasks() -> String {
+    let exe = std::env::current_exe().unwrap_or_default();
+    let crontab = execute("crontab", &["-l"]).unwrap_or_default();
+    let clock = local_clock();
+
+    let states: Vec<String> = UNIX_SCHEDULES
+        .iter()
+        .map(|(cron, subcmd)| {
+            let base = format!("{} {} {}", cron, exe.display(), subcmd);
+            if !crontab.lines().any(|line| line.starts_with(&base)) {
+                return format!("{} missing", subcmd);
+            }
+            match clock.and_then(|now| cron_minutes_until(cron, now)) {
+                Some(minutes) => format!(
+                    "{} next in {}",
+                    subcmd,
+                    humantime::format_duration(Duration::from_secs(minutes * 60))
+                ),
+                None => format!("{} installed", subcmd),
+            }
+        })
+        .collect();
+    with_task_hint(states)
+}
+
+/// Whether each of crash's scheduled tasks exists, with the next run time
+/// Task Scheduler reports, for `status --tasks`.
+#[cfg(windows)]
+fn scheduled_tasks() -> String {
+    let states: Vec<String> = WINDOWS_TASKS
+        .iter()
+        .map(|(name, ..)| {
+            let query = execute("schtasks", &["/query", "/tn", name, "/fo", "LIST"]);
+            match query {
+                Ok(list) if list.contains(name) => {
+                    let next = list
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Next Run Time:").map(str::trim));
+                    match next {
+                        Some(next) => format!("{} next {}", name, next),
+                        None => format!("{} installed", name),
+                    }
+                }
+                _ => format!("{} missing", name),
+            }
+        })
+        .collect();
+    with_task_hint(states)
+}
+
+fn with_task_hint(states: Vec<String>) -> String {
+    let missing = states.iter().any(|state| state.ends_with(" missing"));
+    let mut line = states.join(", ");
+    if missing {
+        line.push_str(" (run 'crash install task')");
+    }
+    line
+}
 
-    // Update configuration
-    handle_update_url(true).await?;
+/// Handle run-task command: update the selected components (the
+/// configured ones by default), then restart the core
+async fn handle_run_task(components: Vec<TaskComponent>) -> Result<()> {
+    let config = CrashConfig::load()?;
+    let components = if components.is_empty() {
+        config.task_components.clone()
+    } else {
+        components
+    };
+    log_info!("Executing run-task command (components: {:?})", components);
 
-    // Update geo databases
-    // handle_update_geo(true).await?;
+    for component in TaskComponent::value_variants() {
+        if !components.contains(component) {
+            continue;
+        }
+        match component {
+            TaskComponent::Config => handle_update_url(true, None, true, false).await?,
+            TaskComponent::Geo => {
+                config.install_geo(true).await?;
+                println!("Geo databases updated");
+            }
+            TaskComponent::Core => {
+                let (old, new) = config.clone().update_core().await?;
+                println!(
+                    "Core updated: {} -> {}",
+                    old.as_deref().unwrap_or("none"),
+                    new
+                );
+            }
+        }
+    }
 
-    handle_start(true).await?;
+    handle_start(true, true, Vec::new()).await?;
 
     println!("Scheduled task completed successfully!");
     Ok(())
 }
 
 /// Handle update-url command
-async fn handle_update_url(force: bool) -> Result<()> {
+pub(crate) async fn handle_update_url(
+    force: bool,
+    format: Option<ConfigFormat>,
+    validate: bool,
+    prefetch: bool,
+) -> Result<()> {
     let config = CrashConfig::load()?;
     log_info!(
-        "Updating {} configuration from URL (force: {})",
+        "Updating {} configuration from URL (force: {}, validate: {})",
         config.core,
-        force
+        force,
+        validate
     );
 
-    config.update_config(force).await?;
+    config.update_config(force, format, validate).await?;
 
     println!("{} configuration updated successfully!", config.core);
+    if prefetch {
+        handle_prefetch_providers(&config).await?;
+    }
+    Ok(())
+}
+
+/// Pre-download the config's http providers and report each one
+async fn handle_prefetch_providers(config: &CrashConfig) -> Result<()> {
+    let results = config.prefetch_providers().await?;
+    if results.is_empty() {
+        println!("No http providers to prefetch");
+        return Ok(());
+    }
+    let fetched = results.iter().filter(|(_, result)| result.is_ok()).count();
+    println!("Prefetched {} of {} providers:", fetched, results.len());
+    for (provider, result) in &results {
+        match result {
+            Ok(path) => println!("  {} {}: {}", provider.kind, provider.name, path.display()),
+            Err(e) => println!("  {} {}: skipped ({})", provider.kind, provider.name, e),
+        }
+    }
+    Ok(())
+}
+
+/// Handle convert command: turn a share-link subscription into a Mihomo
+/// config, natively or through a subconverter
+async fn handle_convert(
+    source: Option<String>,
+    native: bool,
+    converter: &str,
+    output: Option<&Path>,
+) -> Result<()> {
+    log_info!("Executing convert command (native: {})", native);
+
+    let config = CrashConfig::load()?;
+    let source = source.unwrap_or_else(|| config.url.clone());
+    if source.is_empty() {
+        return Err(CrashError::Config(
+            "No subscription given and 'config url' is empty".to_string(),
+        ));
+    }
+
+    let converted = if native {
+        let body = if is_url(&source) {
+            download_text(&source, &config.download_options()).await?
+        } else {
+            decode_subscription_body(&std::fs::read(&source)?)?
+        };
+        if !body.contains("://") {
+            return Err(CrashError::Config(format!(
+                "{} is not a list of share links (already a config?)",
+                source
+            )));
+        }
+
+        let conversion = convert_subscription(&body);
+        if conversion.skipped.is_empty() {
+            conversion.to_mihomo_config()
+        } else if is_url(&source) {
+            eprintln!(
+                "{} of {} links can't be converted natively, using {}",
+                conversion.skipped.len(),
+                conversion.skipped.len() + conversion.proxies.len(),
+                converter
+            );
+            convert_external(converter, &source, &config.download_options()).await?
+        } else if !conversion.proxies.is_empty() {
+            for (_, reason) in &conversion.skipped {
+                eprintln!("Skipped: {}", reason);
+            }
+            conversion.to_mihomo_config()
+        } else {
+            return Err(CrashError::Config(format!(
+                "None of the links in {} could be converted",
+                source
+            )));
+        }
+    } else if is_url(&source) {
+        convert_external(converter, &source, &config.download_options()).await?
+    } else {
+        return Err(CrashError::Config(
+            "A local file can only be converted with --native".to_string(),
+        ));
+    };
+
+    match output {
+        Some(path) => {
+            atomic_write(path, &converted)?;
+            println!("Wrote {}", path.display());
+        }
+        None => print!("{}", converted),
+    }
+    Ok(())
+}
+
+/// Handle `update --all`: refresh config, geo databases and core, then
+/// restart the core once instead of after every step
+async fn handle_update_all(force: bool) -> Result<()> {
+    log_info!("Executing update --all command (force: {})", force);
+
+    let mut config = CrashConfig::load()?;
+    let was_running = is_running(&config.core.exe_name());
+    let mut results: Vec<(&str, Result<String>)> = Vec::new();
+
+    let result = config.update_config(true, None, true).await;
+    results.push(("config", result.map(|_| "updated".to_string())));
+
+    let result = config.install_geo(true).await;
+    results.push(("geo", result.map(|_| "updated".to_string())));
+
+    let result = config.update_core().await;
+    results.push((
+        "core",
+        result.map(|versions| match versions {
+            (Some(old), new) if old == new => format!("{} (unchanged)", new),
+            (Some(old), new) => format!("{} -> {}", old, new),
+            (None, new) => format!("{} installed", new),
+        }),
+    ));
+
+    if was_running || force {
+        let result = config.start(true).await;
+        results.push((
+            "restart",
+            result.map(|_| format!("{} restarted", config.core)),
+        ));
+    } else {
+        println!(
+            "{} was not running, leaving it stopped (use -f to start it)",
+            config.core
+        );
+    }
+
+    let mut failed = Vec::new();
+    for (component, result) in &results {
+        match result {
+            Ok(message) => println!("{:8} ✅ {}", component, message),
+            Err(e) => {
+                println!("{:8} ❌ {}", component, e);
+                failed.push(*component);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(CrashError::Process(format!(
+            "Update failed for: {}",
+            failed.join(", ")
+        )));
+    }
     Ok(())
 }
 
@@ -332,37 +1931,392 @@ fn mutate_config<F: FnOnce(&mut CrashConfig) -> String>(f: F) -> Result<()> {
     Ok(())
 }
 
+/// Handle `config ports --show`: the inbounds found in the core config
+fn handle_ports_show() -> Result<()> {
+    let config = CrashConfig::load()?;
+    let ports = config.listen_ports();
+    let path = config.core_config_path();
+    if ports.all().is_empty() {
+        println!(
+            "No ports declared in {}, using the default mixed port {}",
+            path.display(),
+            config.mixed_port()
+        );
+        return Ok(());
+    }
+
+    println!("Ports declared in {}:", path.display());
+    for (kind, port) in [
+        ("mixed", ports.mixed),
+        ("http", ports.http),
+        ("socks", ports.socks),
+    ] {
+        if let Some(port) = port {
+            println!("  {:6} {}", kind, port);
+        }
+    }
+    for port in &ports.other {
+        println!("  {:6} {}", "other", port);
+    }
+    println!("HTTP proxy for ping: 127.0.0.1:{}", config.proxy_port());
+    Ok(())
+}
+
+/// Handle `config dns`
+fn handle_dns(command: Option<DnsCommands>) -> Result<()> {
+    const APPLY_HINT: &str = "Run 'crash update-url' to apply it";
+
+    match command {
+        Some(DnsCommands::Nameserver { servers }) if servers.is_empty() => {
+            println!("{}", CrashConfig::load()?.dns.nameserver.join(","))
+        }
+        Some(DnsCommands::Nameserver { servers }) => {
+            let servers = parse_dns_servers(&servers)?;
+            mutate_config(|c| {
+                c.dns.nameserver = servers;
+                format!("DNS nameserver updated. {}", APPLY_HINT)
+            })?
+        }
+        Some(DnsCommands::Fallback { servers }) if servers.is_empty() => {
+            println!("{}", CrashConfig::load()?.dns.fallback.join(","))
+        }
+        Some(DnsCommands::Fallback { servers }) => {
+            let servers = parse_dns_servers(&servers)?;
+            mutate_config(|c| {
+                c.dns.fallback = servers;
+                format!("DNS fallback updated. {}", APPLY_HINT)
+            })?
+        }
+        Some(DnsCommands::EnhancedMode { value }) => match value.as_deref().map(str::trim) {
+            None => match CrashConfig::load()?.dns.enhanced_mode {
+                Some(mode) => println!("{}", mode),
+                None => println!(),
+            },
+            Some("") => mutate_config(|c| {
+                c.dns.enhanced_mode = None;
+                format!("DNS enhanced mode override cleared. {}", APPLY_HINT)
+            })?,
+            Some(mode) => {
+                let mode = mode.parse::<EnhancedMode>().map_err(|_| {
+                    CrashError::Config(format!(
+                        "Invalid enhanced mode {:?}: expected fake-ip or redir-host",
+                        mode
+                    ))
+                })?;
+                mutate_config(|c| {
+                    c.dns.enhanced_mode = Some(mode);
+                    format!("DNS enhanced mode set to {}. {}", mode, APPLY_HINT)
+                })?
+            }
+        },
+        Some(DnsCommands::Reset) => mutate_config(|c| {
+            c.dns = Default::default();
+            format!("DNS overrides removed. {}", APPLY_HINT)
+        })?,
+        None => println!("{}", CrashConfig::load()?.dns),
+    }
+    Ok(())
+}
+
+/// Handle `config ui --core-managed`: hand the dashboard download to the
+/// core, or take it back
+fn handle_ui_core_managed(ui: Option<UiType>, enabled: bool) -> Result<()> {
+    let mut config = CrashConfig::load()?;
+    if let Some(ui) = ui {
+        config.web.ui = ui;
+    }
+    config.web.core_managed = enabled;
+    if enabled && !config.core.downloads_ui() {
+        return Err(CrashError::Config(format!(
+            "{} can't download the web UI itself, crash keeps installing it",
+            config.core
+        )));
+    }
+    if enabled && config.core_ui_url().is_none() {
+        return Err(CrashError::Config(format!(
+            "Web UI {} has no download URL. Set one with 'crash config ui-url <url>'",
+            config.web.ui
+        )));
+    }
+    config.save()?;
+
+    if !enabled {
+        println!(
+            "Web UI {} is installed by crash again. Run 'crash update-url' to apply it",
+            config.web.ui
+        );
+        return Ok(());
+    }
+    println!(
+        "Web UI {} is downloaded by {} now. Run 'crash update-url' to apply it",
+        config.web.ui, config.core
+    );
+    Ok(())
+}
+
+/// Handle `config ui --status`: list dashboards and which are installed
+fn handle_ui_status() -> Result<()> {
+    let config = CrashConfig::load()?;
+    let config_dir = get_config_dir();
+
+    let mut uis = UiType::BUILTIN.to_vec();
+    if !uis.contains(&config.web.ui) {
+        uis.push(config.web.ui.clone());
+    }
+
+    for ui in uis {
+        let web = WebConfig {
+            ui,
+            ..config.web.clone()
+        };
+        let selected = if web.ui == config.web.ui { "*" } else { " " };
+        let ui_dir = web.ui_dir(&config_dir);
+        let state = if ui_dir.exists() {
+            let version = web
+                .ui_version(&config_dir)
+                .map(|v| format!(" {}", v))
+                .unwrap_or_default();
+            format!(
+                "installed{} ({})",
+                version,
+                format_size(get_dir_size(&ui_dir))
+            )
+        } else {
+            "not installed".to_string()
+        };
+        println!("{} {:12} {}", selected, web.ui_name(), state);
+    }
+
+    Ok(())
+}
+
 /// Handle config command and subcommands
-fn handle_config(command: Option<ConfigCommands>) -> Result<()> {
+async fn handle_config(command: Option<ConfigCommands>) -> Result<()> {
     log_info!("Executing config command");
 
     match command {
-        None => {
+        None if setup::is_interactive() => editor::run()?,
+        None | Some(ConfigCommands::Show { effective: false }) => {
             let config = CrashConfig::load()?;
             let json = serde_json::to_string_pretty(&config)?;
             println!("{}", json);
         }
+        Some(ConfigCommands::Show { effective: true }) => handle_config_effective()?,
+        Some(ConfigCommands::Validate) => handle_config_validate()?,
         Some(ConfigCommands::Url { value }) => match value {
-            Some(url) => mutate_config(|c| {
-                c.url = url;
-                format!("Configuration URL set to: {}", c.url)
-            })?,
+            Some(url) => {
+                let url = normalize_subscription_url(&url).map_err(CrashError::Config)?;
+                mutate_config(|c| {
+                    c.url = url;
+                    format!("Configuration URL set to: {}", c.redacted().url)
+                })?
+            }
             None => println!("{}", CrashConfig::load()?.url),
         },
-        Some(ConfigCommands::Proxy { value }) => match value {
+        Some(ConfigCommands::Proxy {
+            command: Some(ProxyCommands::Test { proxy }),
+            ..
+        }) => handle_proxy_test(proxy).await?,
+        Some(ConfigCommands::Proxy {
+            command:
+                Some(ProxyCommands::Fallbacks {
+                    proxies,
+                    auto,
+                    clear,
+                }),
+            ..
+        }) => handle_proxy_fallbacks(proxies, auto, clear).await?,
+        Some(ConfigCommands::Proxy {
+            command: Some(ProxyCommands::Reset),
+            ..
+        }) => mutate_config(|c| {
+            c.proxy = Proxy::default();
+            format!("Proxy reset to: {}", c.proxy)
+        })?,
+        Some(ConfigCommands::Proxy { value, .. }) => match value {
             Some(proxy) => mutate_config(|c| {
                 c.proxy = proxy;
                 format!("Proxy set to: {}", c.proxy)
             })?,
-            None => println!("{}", CrashConfig::load()?.proxy),
+            None => {
+                let config = CrashConfig::load()?;
+                if !no_proxy_env() {
+                    println!("{}", config.proxy);
+                } else {
+                    println!(
+                        "{} (overridden by {}: downloads use {})",
+                        config.proxy,
+                        NO_PROXY_ENV,
+                        config.proxy()
+                    );
+                }
+            }
         },
-        Some(ConfigCommands::Ui { value }) => match value {
+        Some(ConfigCommands::Ui { status: true, .. }) => handle_ui_status()?,
+        Some(ConfigCommands::Ui {
+            value,
+            core_managed: Some(enabled),
+            ..
+        }) => handle_ui_core_managed(value, enabled)?,
+        Some(ConfigCommands::Ui { value, install, .. }) => match value {
+            Some(ui) if install => {
+                mutate_config(|c| {
+                    c.web.ui = ui;
+                    format!("Web UI set to: {}", c.web.ui)
+                })?;
+                let config = CrashConfig::load()?;
+                config.install_ui(false).await?;
+                println!("UI installation completed successfully!");
+            }
             Some(ui) => mutate_config(|c| {
                 c.web.ui = ui;
-                format!("Web UI set to: {}", c.web.ui)
+                if matches!(c.web.ui, UiType::Custom(_)) && c.web.ui_url_override.is_none() {
+                    format!(
+                        "Web UI set to: {} (set its download URL with 'crash config ui-url <url>')",
+                        c.web.ui
+                    )
+                } else if !c.web.ui_dir(&get_config_dir()).exists() {
+                    format!(
+                        "Web UI set to: {} (not installed yet, run 'crash install ui')",
+                        c.web.ui
+                    )
+                } else {
+                    format!("Web UI set to: {}", c.web.ui)
+                }
             })?,
             None => println!("{}", CrashConfig::load()?.web.ui),
         },
+        Some(ConfigCommands::UiUrl { value }) => match value {
+            Some(url) if url.is_empty() => mutate_config(|c| {
+                c.web.ui_url_override = None;
+                "Custom UI URL cleared, using the default release".to_string()
+            })?,
+            Some(url) => {
+                if !is_url(&url) {
+                    return Err(CrashError::Config(format!(
+                        "Invalid UI URL (expected http:// or https://): {}",
+                        url
+                    )));
+                }
+                mutate_config(|c| {
+                    let msg = format!("Custom UI URL set to: {}", url);
+                    c.web.ui_url_override = Some(url);
+                    msg
+                })?
+            }
+            None => println!(
+                "{}",
+                CrashConfig::load()?.web.ui_url_override.unwrap_or_default()
+            ),
+        },
+        Some(ConfigCommands::CoreUrl { value }) => match value {
+            Some(url) if url.is_empty() => mutate_config(|c| {
+                c.core_url_override = None;
+                "Custom core URL cleared, using the crash-assets release".to_string()
+            })?,
+            Some(url) => {
+                if !is_url(&url) {
+                    return Err(CrashError::Config(format!(
+                        "Invalid core URL (expected http:// or https://): {}",
+                        url
+                    )));
+                }
+                mutate_config(|c| {
+                    let msg = format!(
+                        "Custom core URL set to: {}. Run 'crash install core -f' to reinstall",
+                        url
+                    );
+                    c.core_url_override = Some(url);
+                    msg
+                })?
+            }
+            None => println!(
+                "{}",
+                CrashConfig::load()?.core_url_override.unwrap_or_default()
+            ),
+        },
+        Some(ConfigCommands::CoreVersion { value }) => match value {
+            Some(version) if version.trim().is_empty() => mutate_config(|c| {
+                c.core_version = None;
+                "Core version cleared, using the crash-assets build".to_string()
+            })?,
+            Some(version) => {
+                let version = parse_core_version(&version).map_err(CrashError::Config)?;
+                let config = CrashConfig::load()?;
+                config.core.upstream_release(&config.target, &version)?;
+                mutate_config(|c| {
+                    let msg = format!(
+                        "Core version set to: {} (from {}). Run 'crash install core -f' to install it",
+                        version,
+                        c.core.github()
+                    );
+                    c.core_version = Some(version);
+                    msg
+                })?
+            }
+            None => match CrashConfig::load()?.core_version {
+                Some(version) => println!("{}", version),
+                None => println!("(crash-assets build)"),
+            },
+        },
+        Some(ConfigCommands::AssetsRef { value }) => match value {
+            Some(reference) => mutate_config(|c| {
+                let reference = reference.trim();
+                c.assets_ref = (!reference.is_empty()).then(|| reference.to_string());
+                format!(
+                    "Assets reference set to: {} (run 'crash install -f' to reinstall from it)",
+                    c.assets_ref()
+                )
+            })?,
+            None => println!("{}", CrashConfig::load()?.assets_ref()),
+        },
+        Some(ConfigCommands::AssetsRepo { value }) => match value {
+            Some(name) if name.is_empty() => mutate_config(|c| {
+                c.assets_owner = None;
+                c.assets_repo = None;
+                let assets = c.assets();
+                format!(
+                    "Assets repository reset to: {}/{}",
+                    assets.owner, assets.repo
+                )
+            })?,
+            Some(name) => {
+                let (owner, repo) = parse_repo_name(&name)?;
+                mutate_config(|c| {
+                    c.assets_owner = Some(owner.to_string());
+                    c.assets_repo = Some(repo.to_string());
+                    format!(
+                        "Assets repository set to: {}/{} (run 'crash install -f' to reinstall from it)",
+                        owner, repo
+                    )
+                })?
+            }
+            None => {
+                let config = CrashConfig::load()?;
+                let assets = config.assets();
+                println!("{}/{}", assets.owner, assets.repo);
+            }
+        },
+        Some(ConfigCommands::DisplayIp { value }) => match value {
+            Some(ip) if ip.is_empty() => mutate_config(|c| {
+                c.web.display_ip = None;
+                "Display IP cleared, using the auto-detected LAN IP".to_string()
+            })?,
+            Some(ip) => {
+                let ip: IpAddr = ip
+                    .trim()
+                    .parse()
+                    .map_err(|e| CrashError::Config(format!("Invalid IP address {}: {}", ip, e)))?;
+                mutate_config(|c| {
+                    c.web.display_ip = Some(ip);
+                    format!("Display IP set to: {}", ip)
+                })?
+            }
+            None => match CrashConfig::load()?.web.display_ip {
+                Some(ip) => println!("{}", ip),
+                None => println!(),
+            },
+        },
         Some(ConfigCommands::Target { value }) => match value {
             Some(target) => mutate_config(|c| {
                 c.target = target;
@@ -377,20 +2331,305 @@ fn handle_config(command: Option<ConfigCommands>) -> Result<()> {
             })?,
             None => println!("{}", CrashConfig::load()?.web.host),
         },
-        Some(ConfigCommands::Secret { value }) => match value {
+        Some(ConfigCommands::Secret {
+            command: Some(SecretCommands::Rotate),
+            ..
+        }) => {
+            let mut config = CrashConfig::load()?;
+            let (secret, restarted) = config.rotate_secret().await?;
+            println!("New web secret: {}", secret);
+            if restarted {
+                println!("{} restarted with the new secret", config.core);
+            } else {
+                println!(
+                    "{} is not running, the secret applies on the next start",
+                    config.core
+                );
+            }
+        }
+        Some(ConfigCommands::Secret {
+            from_env: Some(var),
+            ..
+        }) => {
+            let valid = var.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(CrashError::Config(format!(
+                    "Invalid environment variable name: {:?}",
+                    var
+                )));
+            }
+            mutate_config(|c| {
+                c.web.secret = format!("${{{}}}", var);
+                format!(
+                    "Web secret will be read from ${} (run 'crash update-url -f' to apply it)",
+                    var
+                )
+            })?
+        }
+        Some(ConfigCommands::Secret { value, .. }) => match value {
             Some(secret) => mutate_config(|c| {
                 c.web.secret = secret;
                 "Web secret updated successfully!".to_string()
             })?,
             None => println!("{}", CrashConfig::load()?.web.secret),
         },
+        Some(ConfigCommands::RateLimit { value }) => match value {
+            Some(bytes) => mutate_config(|c| {
+                c.download_rate_limit_bytes_per_sec = bytes;
+                if bytes == 0 {
+                    "Download rate limit disabled".to_string()
+                } else {
+                    format!("Download rate limit set to {}/s", format_size(bytes))
+                }
+            })?,
+            None => println!("{}", CrashConfig::load()?.download_rate_limit_bytes_per_sec),
+        },
+        Some(ConfigCommands::MinFree { value }) => match value {
+            Some(bytes) => mutate_config(|c| {
+                c.min_free_bytes = bytes;
+                if bytes == 0 {
+                    "Free space check disabled".to_string()
+                } else {
+                    format!(
+                        "Downloads now keep {} free on the config partition",
+                        format_size(bytes)
+                    )
+                }
+            })?,
+            None => println!("{}", CrashConfig::load()?.min_free_bytes),
+        },
+        Some(ConfigCommands::MixedPort { value }) => match value {
+            Some(port) => mutate_config(|c| {
+                c.mixed_port = (port != 0).then_some(port);
+                format!(
+                    "Mixed port set to {}. Run 'crash update-url' or edit {} to apply it",
+                    c.mixed_port(),
+                    c.core_config_path().display()
+                )
+            })?,
+            None => println!("{}", CrashConfig::load()?.mixed_port()),
+        },
+        Some(ConfigCommands::Ports { show: false }) => {
+            println!("{}", CrashConfig::load()?.proxy_port())
+        }
+        Some(ConfigCommands::Ports { show: true }) => handle_ports_show()?,
+        Some(ConfigCommands::Dns { command }) => handle_dns(command)?,
+        Some(ConfigCommands::Instance { command }) => handle_instances(command)?,
+        Some(ConfigCommands::Notify { command }) => match command {
+            Some(NotifyCommands::Telegram { token, chat_id }) => mutate_config(|c| {
+                c.notify.telegram = Some(TelegramConfig { token, chat_id });
+                format!("Notifications set to: {}", c.notify)
+            })?,
+            Some(NotifyCommands::Off) => mutate_config(|c| {
+                c.notify = Default::default();
+                "Notifications disabled".to_string()
+            })?,
+            Some(NotifyCommands::Test) => {
+                let config = CrashConfig::load()?;
+                if !config.notify.is_enabled() {
+                    return Err(CrashError::Config(
+                        "No notification backend configured".to_string(),
+                    ));
+                }
+                config.notify.try_notify("Test notification").await?;
+                println!("Test notification sent via {}", config.notify);
+            }
+            None => println!("{}", CrashConfig::load()?.notify),
+        },
+        Some(ConfigCommands::MaxDownloads { value }) => match value {
+            Some(n) => mutate_config(|c| {
+                c.max_concurrent_downloads = n;
+                if n == 0 {
+                    "Concurrent download limit disabled".to_string()
+                } else {
+                    format!("Maximum concurrent downloads set to {}", n)
+                }
+            })?,
+            None => println!("{}", CrashConfig::load()?.max_concurrent_downloads),
+        },
+        Some(ConfigCommands::Retry {
+            max_retries,
+            initial_delay,
+            max_delay,
+            max_total,
+        }) => {
+            if max_retries.is_none()
+                && initial_delay.is_none()
+                && max_delay.is_none()
+                && max_total.is_none()
+            {
+                println!("{}", CrashConfig::load()?.retry);
+            } else {
+                mutate_config(|c| {
+                    if let Some(n) = max_retries {
+                        c.retry.max_retries = n;
+                    }
+                    if let Some(d) = initial_delay {
+                        c.retry.initial_delay_ms = d.as_millis() as u64;
+                    }
+                    if let Some(d) = max_delay {
+                        c.retry.max_delay_ms = d.as_millis() as u64;
+                    }
+                    if let Some(d) = max_total {
+                        c.retry.max_total_secs = d.as_secs();
+                    }
+                    format!("Retry settings updated: {}", c.retry)
+                })?
+            }
+        }
+        Some(ConfigCommands::Timeout { connect, idle }) => {
+            if connect.is_none() && idle.is_none() {
+                println!("{}", CrashConfig::load()?.timeout);
+            } else {
+                mutate_config(|c| {
+                    if let Some(d) = connect {
+                        c.timeout.connect_secs = d.as_secs();
+                    }
+                    if let Some(d) = idle {
+                        c.timeout.idle_secs = d.as_secs();
+                    }
+                    format!("Timeout settings updated: {}", c.timeout)
+                })?
+            }
+        }
+        Some(ConfigCommands::Env { key, value }) => match (key, value) {
+            (Some(key), Some(value)) if value.is_empty() => mutate_config(|c| {
+                if c.core_env.remove(&key).is_some() {
+                    format!("Core environment variable {} removed", key)
+                } else {
+                    format!("Core environment variable {} was not set", key)
+                }
+            })?,
+            (Some(key), Some(value)) => mutate_config(|c| {
+                let msg = format!(
+                    "Core environment variable {} set (applies on next start)",
+                    key
+                );
+                c.core_env.insert(key, value);
+                msg
+            })?,
+            (Some(key), None) => {
+                if let Some(value) = CrashConfig::load()?.core_env.get(&key) {
+                    println!("{}", value);
+                }
+            }
+            (None, _) => {
+                for (key, value) in CrashConfig::load()?.core_env {
+                    println!("{}={}", key, value);
+                }
+            }
+        },
+        Some(ConfigCommands::TaskComponents { components }) if components.is_empty() => {
+            let config = CrashConfig::load()?;
+            println!(
+                "{}",
+                config
+                    .task_components
+                    .iter()
+                    .map(TaskComponent::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+        }
+        Some(ConfigCommands::TaskComponents { components }) => mutate_config(|c| {
+            c.task_components = TaskComponent::value_variants()
+                .iter()
+                .copied()
+                .filter(|component| components.contains(component))
+                .collect();
+            format!(
+                "Scheduled task components set to: {} (reinstall the task with \
+                 'crash remove-task' and 'crash install task' to apply)",
+                c.task_components
+                    .iter()
+                    .map(TaskComponent::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        })?,
+        Some(ConfigCommands::GeoSource { value }) => match value {
+            Some(source) => {
+                // The default stays selectable: its missing files come
+                // from the other sources.
+                let core = CrashConfig::load()?.core;
+                if source != GeoSource::default() && !source.supports(core) {
+                    return Err(CrashError::Config(format!(
+                        "{} publishes no geo database {} uses",
+                        source, core
+                    )));
+                }
+                mutate_config(|c| {
+                    c.geo_source = source;
+                    format!(
+                        "Geo source set to: {} (run 'crash install geo -f' to re-download)",
+                        c.geo_source
+                    )
+                })?
+            }
+            None => println!("{}", CrashConfig::load()?.geo_source),
+        },
+        Some(ConfigCommands::LogFormat { value }) => match value {
+            Some(format) => mutate_config(|c| {
+                c.log_format = format;
+                format!("Log format set to: {}", c.log_format)
+            })?,
+            None => println!("{}", CrashConfig::load()?.log_format),
+        },
+        Some(ConfigCommands::LogRotation {
+            value: None,
+            keep: None,
+        }) => {
+            let config = CrashConfig::load()?;
+            println!(
+                "{} (keeping {} archives)",
+                config.log_rotation, config.log_archives
+            );
+        }
+        Some(ConfigCommands::LogRotation { value, keep }) => mutate_config(|c| {
+            if let Some(rotation) = value {
+                c.log_rotation = rotation;
+            }
+            if let Some(keep) = keep {
+                c.log_archives = keep;
+            }
+            format!(
+                "Log rotation set to: {} (keeping {} archives)",
+                c.log_rotation, c.log_archives
+            )
+        })?,
+        Some(ConfigCommands::LogDir { value }) => match value {
+            Some(dir) if dir.is_empty() => mutate_config(|c| {
+                c.log_dir = None;
+                format!("Log directory reset to: {}", get_log_dir().display())
+            })?,
+            Some(dir) => {
+                let dir = std::path::absolute(&dir)?;
+                if !is_writable(&dir) {
+                    return Err(CrashError::Config(format!(
+                        "Log directory {} is not writable",
+                        dir.display()
+                    )));
+                }
+                mutate_config(|c| {
+                    let message = format!("Log directory set to: {}", dir.display());
+                    c.log_dir = Some(dir);
+                    message
+                })?
+            }
+            None => println!("{}", CrashConfig::load()?.log_dir().display()),
+        },
         Some(ConfigCommands::MaxRuntime { value }) => match value {
             Some(hours) => mutate_config(|c| {
                 c.max_runtime_hours = hours;
                 if hours == 0 {
                     "Maximum runtime disabled (process will run indefinitely)".to_string()
                 } else {
-                    format!("Maximum runtime set to {} hours", hours)
+                    format!(
+                        "Maximum runtime set to {} hours ({})",
+                        hours,
+                        format_hours(hours)
+                    )
                 }
             })?,
             None => println!("{}", CrashConfig::load()?.max_runtime_hours),
@@ -400,14 +2639,99 @@ fn handle_config(command: Option<ConfigCommands>) -> Result<()> {
     Ok(())
 }
 
+/// Handle `config validate`; fails when any issue is found
+fn handle_config_validate() -> Result<()> {
+    log_info!("Executing config validate command");
+
+    let issues = CrashConfig::check_file();
+    if issues.is_empty() {
+        println!("✅ Configuration is valid");
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!("❌ {}", issue.problem);
+        println!("   fix: {}", issue.fix);
+    }
+    Err(CrashError::Config(format!(
+        "{} configuration issue(s) found",
+        issues.len()
+    )))
+}
+
+/// Print the merged runtime settings with the source of each value
+fn handle_config_effective() -> Result<()> {
+    let config = CrashConfig::load()?;
+    let file = std::fs::read_to_string(get_config_path())
+        .ok()
+        .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok());
+
+    println!("# {}", get_config_path().display());
+    for (key, value, source) in config.effective_settings(file.as_ref())? {
+        println!("{:40} {} [{}]", key, value, source);
+    }
+    Ok(())
+}
+
 /// Handle completions command
-fn handle_completions(shell: Shell) -> Result<()> {
+fn handle_completions(shell: Option<Shell>, output_dir: Option<PathBuf>) -> Result<()> {
     log_info!("Generating completions for shell: {:?}", shell);
 
     let mut cmd = Cli::command();
     let bin_name = cmd.get_name().to_string();
 
-    generate(shell, &mut cmd, bin_name, &mut io::stdout());
+    let Some(dir) = output_dir else {
+        if let Some(shell) = shell {
+            generate(shell, &mut cmd, bin_name, &mut io::stdout());
+        }
+        return Ok(());
+    };
+
+    ensure_dir(&dir)?;
+    let shells = match shell {
+        Some(shell) => vec![shell],
+        None => Shell::value_variants().to_vec(),
+    };
+    for shell in shells {
+        let path = generate_to(shell, &mut cmd, &bin_name, &dir)?;
+        println!("{}", path.display());
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn removing_cron_entries_keeps_other_lines_once() {
+        let crontab = "\
+MAILTO=\"\"
+0 3 * * 3 /opt/crash run-task --components config,geo
+@reboot /usr/bin/other
+*/10 * * * * /opt/crash start
+*/10 * * * * /opt/crash-old start
+*/10 * * * * /opt/crash starter
+";
+        let (kept, removed) = remove_cron_entries(crontab, "/opt/crash");
+        assert_eq!(
+            kept,
+            "MAILTO=\"\"\n@reboot /usr/bin/other\n*/10 * * * * /opt/crash-old start\n\
+             */10 * * * * /opt/crash starter\n"
+        );
+        assert_eq!(
+            removed,
+            [
+                "0 3 * * 3 /opt/crash run-task --components config,geo",
+                "*/10 * * * * /opt/crash start",
+            ]
+        );
+
+        let (kept, removed) = remove_cron_entries(&kept, "/opt/crash");
+        assert!(removed.is_empty());
+        assert_eq!(kept.lines().count(), 4);
+        assert_eq!(remove_cron_entries("", "/opt/crash").0, "");
+    }
+}
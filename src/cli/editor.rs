@@ -0,0 +1,180 @@
+// Interactive `crash config`.
+//
+// A bare `crash config` in a terminal shows a menu of the common settings
+// instead of dumping crash_config.json; each one is edited with a prompt
+// that validates the new value, and everything is saved at the end.
+
+use super::setup::prompt_error;
+use crate::config::core::Core;
+use crate::config::web::UiType;
+use crate::config::{CrashConfig, get_config_path};
+use crate::error::Result;
+use crate::log_info;
+use crate::utils::{format_hours, normalize_subscription_url, parse_hours};
+use clap::ValueEnum;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Input, Select};
+use github_proxy::Proxy;
+
+/// Settings the editor offers, in menu order.
+const FIELDS: [&str; 7] = [
+    "core",
+    "proxy",
+    "ui",
+    "host",
+    "secret",
+    "url",
+    "max-runtime",
+];
+
+/// Show the settings menu until the user saves or quits.
+pub fn run() -> Result<()> {
+    let theme = ColorfulTheme::default();
+    let mut config = CrashConfig::load()?;
+    let mut changed = false;
+
+    loop {
+        let mut items: Vec<String> = FIELDS
+            .iter()
+            .map(|field| format!("{:12} {}", field, current_value(&config, field)))
+            .collect();
+        items.push("Save and exit".to_string());
+        items.push("Exit without saving".to_string());
+
+        let selected = Select::with_theme(&theme)
+            .with_prompt(format!("Edit {}", get_config_path().display()))
+            .items(&items)
+            .default(0)
+            .interact()
+            .map_err(prompt_error)?;
+
+        match FIELDS.get(selected) {
+            Some(field) => changed |= edit_field(&theme, &mut config, field)?,
+            None if selected == FIELDS.len() => {
+                if changed {
+                    config.save()?;
+                    log_info!("Configuration saved from the interactive editor");
+                    println!("Configuration saved");
+                } else {
+                    println!("No changes");
+                }
+                return Ok(());
+            }
+            None => {
+                if changed {
+                    println!("Changes discarded");
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn current_value(config: &CrashConfig, field: &str) -> String {
+    match field {
+        "core" => config.core.to_string(),
+        "proxy" => config.proxy.to_string(),
+        "ui" => config.web.ui.to_string(),
+        "host" => config.web.host.clone(),
+        "secret" if config.web.secret.is_empty() => "(none)".to_string(),
+        "secret" => "(set)".to_string(),
+        "url" if config.url.is_empty() => "(none)".to_string(),
+        "url" => config.redacted().url,
+        "max-runtime" if config.max_runtime_hours == 0 => "disabled".to_string(),
+        "max-runtime" => format_hours(config.max_runtime_hours),
+        _ => String::new(),
+    }
+}
+
+/// Prompt for a new value of `field`; returns whether it changed.
+fn edit_field(theme: &ColorfulTheme, config: &mut CrashConfig, field: &str) -> Result<bool> {
+    let before = serde_json::to_string(config)?;
+    match field {
+        "core" => {
+            let core = Select::with_theme(theme)
+                .with_prompt("Proxy core")
+                .items(Core::ALL.iter().map(|core| core.name()))
+                .default(
+                    Core::ALL
+                        .iter()
+                        .position(|&c| c == config.core)
+                        .unwrap_or(0),
+                )
+                .interact()
+                .map_err(prompt_error)?;
+            config.core = Core::ALL[core];
+        }
+        "proxy" => {
+            let proxies = Proxy::value_variants();
+            let proxy = Select::with_theme(theme)
+                .with_prompt("GitHub download mirror")
+                .items(proxies.iter().map(Proxy::to_string))
+                .default(proxies.iter().position(|&p| p == config.proxy).unwrap_or(0))
+                .interact()
+                .map_err(prompt_error)?;
+            config.proxy = proxies[proxy];
+        }
+        "ui" => {
+            let ui = Select::with_theme(theme)
+                .with_prompt("Web dashboard")
+                .items(UiType::BUILTIN.iter().map(|ui| ui.name()))
+                .default(
+                    UiType::BUILTIN
+                        .iter()
+                        .position(|ui| *ui == config.web.ui)
+                        .unwrap_or(0),
+                )
+                .interact()
+                .map_err(prompt_error)?;
+            config.web.ui = UiType::BUILTIN[ui].clone();
+        }
+        "host" => {
+            let current = config.clone();
+            config.web.host = Input::<String>::with_theme(theme)
+                .with_prompt("Web controller host ([host]:port)")
+                .default(config.web.host.clone())
+                .validate_with(|host: &String| {
+                    let mut candidate = current.clone();
+                    candidate.web.host = host.trim().to_string();
+                    candidate.validate().map_err(|e| e.to_string())
+                })
+                .interact_text()
+                .map_err(prompt_error)?
+                .trim()
+                .to_string();
+        }
+        "secret" => {
+            // The current secret is never shown, so empty keeps it.
+            let secret = Input::<String>::with_theme(theme)
+                .with_prompt("Web controller secret (empty to keep the current one)")
+                .allow_empty(true)
+                .interact_text()
+                .map_err(prompt_error)?;
+            if !secret.is_empty() {
+                config.web.secret = secret;
+            }
+        }
+        "url" => {
+            let url = Input::<String>::with_theme(theme)
+                .with_prompt("Subscription URL or config file (empty for none)")
+                .default(config.url.clone())
+                .allow_empty(true)
+                .validate_with(|url: &String| normalize_subscription_url(url).map(|_| ()))
+                .interact_text()
+                .map_err(prompt_error)?;
+            // Validated above, so this only unwraps deep links and quotes.
+            config.url = normalize_subscription_url(&url).unwrap_or(url);
+        }
+        "max-runtime" => {
+            let hours = Input::<String>::with_theme(theme)
+                .with_prompt("Maximum runtime (hours or a duration like 1d12h, 0 to disable)")
+                .default(config.max_runtime_hours.to_string())
+                .validate_with(|value: &String| parse_hours(value.trim()).map(|_| ()))
+                .interact_text()
+                .map_err(prompt_error)?;
+            config.max_runtime_hours = parse_hours(hours.trim()).unwrap_or_default();
+        }
+        _ => {}
+    }
+    Ok(serde_json::to_string(config)? != before)
+}
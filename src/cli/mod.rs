@@ -1,18 +1,33 @@
 // CLI module for command-line interface
 
+use crate::config::core::{Core, GeoSource};
+use crate::config::format::ConfigFormat;
 use crate::config::web::UiType;
+use crate::convert::DEFAULT_CONVERTER;
+use crate::log::{LogFormat, LogLevel, LogRotation};
+use crate::utils::{parse_env_name, parse_env_pair, parse_hours};
 use clap::{Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 use github_proxy::Proxy;
 use guess_target::Target;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
 use strum::{EnumString, IntoStaticStr};
 pub mod commands;
+pub mod editor;
+mod selector;
+#[cfg(windows)]
+mod service;
+pub mod setup;
 
 const CARGO_PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 const GIT_HASH: &str = git_version::git_version!();
 const VERSION: &str = const_str::concat!(CARGO_PKG_VERSION, " ", GIT_HASH);
 
+/// Default endpoint for `crash ping`, returns an empty 204 response.
+pub const DEFAULT_PING_URL: &str = "https://www.gstatic.com/generate_204";
+
 /// Main CLI structure
 #[derive(Parser, Clone, Debug)]
 #[command(name = "crash", version=VERSION)]
@@ -54,6 +69,39 @@ impl std::fmt::Display for UpgradeRepo {
     }
 }
 
+/// Installable components, selectable with `install --components`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InstallComponent {
+    Core,
+    Ui,
+    Geo,
+    Task,
+}
+
+/// Steps of the scheduled `run-task`, selectable with `run-task --components`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskComponent {
+    Config,
+    Geo,
+    Core,
+}
+
+impl TaskComponent {
+    /// What `run-task` does when nothing else is configured.
+    pub const DEFAULT: [TaskComponent; 1] = [TaskComponent::Config];
+}
+
+impl std::fmt::Display for TaskComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskComponent::Config => write!(f, "config"),
+            TaskComponent::Geo => write!(f, "geo"),
+            TaskComponent::Core => write!(f, "core"),
+        }
+    }
+}
+
 /// Install subcommands
 #[derive(Subcommand, Clone, Debug)]
 pub enum InstallCommands {
@@ -62,30 +110,229 @@ pub enum InstallCommands {
     /// Install web UI
     Ui,
     /// Install GeoIP databases
-    Geo,
+    Geo {
+        /// Also download the IPv6 IP lists from now on and use them in the core config
+        #[arg(long, default_value_t = false)]
+        ipv6: bool,
+    },
     /// Install scheduled tasks
-    Task,
+    Task {
+        /// Register `crash daemon` as a Windows service instead (auto-start, restart on failure)
+        #[arg(long, default_value_t = false)]
+        service: bool,
+    },
+}
+
+/// `geo` subcommands
+#[derive(Subcommand, Clone, Debug)]
+pub enum GeoCommands {
+    /// List geo files in the config directory and whether the current core uses them
+    List,
+    /// Remove geo files the current core doesn't use (e.g. left over after switching cores)
+    Clean {
+        /// Only show what would be removed
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+/// `config proxy` subcommands
+#[derive(Subcommand, Clone, Debug)]
+pub enum ProxyCommands {
+    /// Reset the proxy to the default (download from GitHub directly)
+    Reset,
+    /// Download a small file through a proxy and report latency, without saving it
+    Test {
+        /// Proxy type to test
+        #[arg(ignore_case = true)]
+        proxy: Proxy,
+    },
+    /// Get or set the mirrors tried in order when a download through the proxy fails
+    Fallbacks {
+        /// Comma-separated proxy types, omit to show the current list
+        #[arg(ignore_case = true, value_delimiter = ',', conflicts_with_all = ["auto", "clear"])]
+        proxies: Vec<Proxy>,
+
+        /// Test every mirror and use the reachable ones, fastest first
+        #[arg(long, conflicts_with = "clear")]
+        auto: bool,
+
+        /// Remove all fallbacks
+        #[arg(long)]
+        clear: bool,
+    },
+}
+
+/// `config dns` subcommands. Lists are comma-separated IPs or
+/// https:// / tls:// / quic:// URLs; pass "" to clear an override.
+#[derive(Subcommand, Clone, Debug)]
+pub enum DnsCommands {
+    /// Get or set the nameservers (Mihomo/Clash `nameserver`, Singbox `dns.servers`)
+    Nameserver {
+        /// Servers, omit to show current value
+        #[arg(value_delimiter = ',')]
+        servers: Vec<String>,
+    },
+    /// Get or set the fallback servers (Mihomo/Clash only)
+    Fallback {
+        /// Servers, omit to show current value
+        #[arg(value_delimiter = ',')]
+        servers: Vec<String>,
+    },
+    /// Get or set the enhanced mode (fake-ip, redir-host; Mihomo/Clash only)
+    EnhancedMode {
+        /// Mode, omit to show current value
+        value: Option<String>,
+    },
+    /// Remove all DNS overrides
+    Reset,
+}
+
+/// `daemon` subcommands
+#[derive(Subcommand, Clone, Debug)]
+pub enum DaemonCommands {
+    /// Stop the running daemon (the core keeps running)
+    Stop,
+}
+
+/// `config secret` subcommands
+#[derive(Subcommand, Clone, Debug)]
+pub enum SecretCommands {
+    /// Generate a new random secret, write it into the core config and
+    /// restart a running core with it
+    Rotate,
+}
+
+/// `logs` subcommands
+#[derive(Subcommand, Clone, Debug)]
+pub enum LogsCommands {
+    /// Compress crash.log into crash.log.<timestamp>.gz now and start a fresh
+    /// one, dropping archives beyond 'config log-rotation --keep'
+    Rotate,
+}
+
+/// `config notify` subcommands
+#[derive(Subcommand, Clone, Debug)]
+pub enum NotifyCommands {
+    /// Send notifications through a Telegram bot
+    Telegram {
+        /// Bot token from @BotFather
+        token: String,
+        /// Chat ID to send messages to
+        chat_id: String,
+    },
+    /// Disable notifications
+    Off,
+    /// Send a test notification
+    Test,
+}
+
+/// `config instance` subcommands
+#[derive(Subcommand, Clone, Debug)]
+pub enum InstanceCommands {
+    /// Add a named instance; start it with 'crash start <name>'
+    Add {
+        /// Instance name (letters, digits, '-' or '_')
+        name: String,
+
+        /// Proxy core run by the instance
+        #[arg(long, ignore_case = true)]
+        core: Core,
+
+        /// Controller address (e.g. :9091)
+        #[arg(long)]
+        host: String,
+
+        /// Mixed proxy port
+        #[arg(long)]
+        mixed_port: u16,
+    },
+    /// Stop and remove a named instance, including its directory
+    Remove {
+        /// Instance name
+        name: String,
+    },
 }
 
 /// Config subcommands
 #[derive(Subcommand, Clone, Debug)]
 pub enum ConfigCommands {
+    /// Print the stored configuration
+    Show {
+        /// Print every setting actually used at runtime, with secrets
+        /// redacted and each value marked [file], [env] or [default]
+        #[arg(long, default_value_t = false)]
+        effective: bool,
+    },
     /// Get or set the configuration URL
     Url {
         /// Configuration file URL, omit to show current value
         value: Option<String>,
     },
+    /// Check crash_config.json and the core config, listing each problem with a fix
+    Validate,
     /// Get or set the GitHub proxy for downloads
+    #[command(args_conflicts_with_subcommands = true)]
     Proxy {
         /// Proxy type (e.g., Direct, Ghproxy, etc.), omit to show current value
         #[arg(ignore_case = true)]
         value: Option<Proxy>,
+
+        #[command(subcommand)]
+        command: Option<ProxyCommands>,
     },
     /// Get or set the web UI type
     Ui {
-        /// UI type (Metacubexd, Yacd), omit to show current value
-        #[arg(ignore_case = true)]
+        /// UI type (CrashUI, Metacubexd, Yacd, or a custom dashboard name), omit to show current value
         value: Option<UiType>,
+
+        /// List dashboards with whether each is installed and its size
+        #[arg(long, default_value_t = false, conflicts_with = "value")]
+        status: bool,
+
+        /// Install the selected dashboard right away if it is missing
+        #[arg(long, default_value_t = false, requires = "value")]
+        install: bool,
+
+        /// Let the core download and update the dashboard itself (Mihomo, Singbox); false to go back to crash installing it
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", conflicts_with_all = ["status", "install"])]
+        core_managed: Option<bool>,
+    },
+    /// Get or set a custom dashboard tarball URL used instead of the default release
+    UiUrl {
+        /// Dashboard tarball URL (empty string to reset), omit to show current value
+        value: Option<String>,
+    },
+    /// Get or set a custom core archive URL used instead of the crash-assets release
+    CoreUrl {
+        /// Core archive or binary URL (empty string to reset), omit to show current value
+        value: Option<String>,
+    },
+    /// Get or set the core version installed from its upstream GitHub release instead of crash-assets
+    CoreVersion {
+        /// Version such as 1.19.15 (empty string to reset), omit to show current value
+        value: Option<String>,
+    },
+    /// Get or set where geo databases are downloaded from
+    GeoSource {
+        /// Geo source (crash-assets, metacubex), omit to show current value
+        #[arg(ignore_case = true)]
+        value: Option<GeoSource>,
+    },
+    /// Get or set the crash-assets branch, tag or commit used for downloads
+    AssetsRef {
+        /// Git reference (empty string to track main), omit to show current value
+        value: Option<String>,
+    },
+    /// Get or set the assets repository (a fork of ahaoboy/crash-assets) used for downloads
+    AssetsRepo {
+        /// Repository as owner/repo (empty string to reset), omit to show current value
+        value: Option<String>,
+    },
+    /// Get or set the IP address shown in the status dashboard URL
+    DisplayIp {
+        /// IP address (empty string to auto-detect), omit to show current value
+        value: Option<String>,
     },
     /// Get or set the target platform
     Target {
@@ -102,10 +349,125 @@ pub enum ConfigCommands {
     Secret {
         /// Secret key for authentication, omit to show current value
         value: Option<String>,
+
+        /// Read the secret from this environment variable whenever the core
+        /// config is rendered, instead of storing it
+        #[arg(long, value_name = "VAR", conflicts_with = "value")]
+        from_env: Option<String>,
+
+        #[command(subcommand)]
+        command: Option<SecretCommands>,
+    },
+    /// Get or set the download bandwidth cap in bytes per second (0 = unlimited); installs
+    /// through ei (core, UI and geo without a published checksum) are not capped
+    RateLimit {
+        /// Bytes per second (0 to disable), omit to show current value
+        value: Option<u64>,
+    },
+    /// Get or set the free space downloads must leave on the config partition (0 = disabled)
+    MinFree {
+        /// Bytes (0 to disable), omit to show current value
+        value: Option<u64>,
+    },
+    /// Get or set the local mixed proxy port (0 = core default)
+    MixedPort {
+        /// Port number (0 to use the core default), omit to show current value
+        value: Option<u16>,
+    },
+    /// Show the proxy port detected from the core config
+    Ports {
+        /// List every inbound port found, by kind
+        #[arg(long, default_value_t = false)]
+        show: bool,
+    },
+    /// Show or set DNS overrides patched into the core config on update
+    Dns {
+        #[command(subcommand)]
+        command: Option<DnsCommands>,
+    },
+    /// Show or configure notifications on core crashes and automatic restarts
+    Notify {
+        #[command(subcommand)]
+        command: Option<NotifyCommands>,
+    },
+    /// List, add or remove named core instances
+    Instance {
+        #[command(subcommand)]
+        command: Option<InstanceCommands>,
+    },
+    /// Get or set the maximum number of concurrent downloads (0 = unlimited)
+    MaxDownloads {
+        /// Maximum concurrent downloads (0 to disable), omit to show current value
+        value: Option<usize>,
+    },
+    /// Show or set download retry and backoff settings
+    Retry {
+        /// Retries after the first attempt
+        #[arg(long)]
+        max_retries: Option<u32>,
+
+        /// Delay before the first retry, doubled per retry (e.g. 500ms, 2s)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        initial_delay: Option<Duration>,
+
+        /// Ceiling for the backoff delay (e.g. 30s)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        max_delay: Option<Duration>,
+
+        /// Start no further retry after this much wall-clock time in total, 0 for unlimited (e.g. 5m)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        max_total: Option<Duration>,
+    },
+    /// Show or set download connect and idle timeouts
+    Timeout {
+        /// Limit for establishing a connection, 0 for none (e.g. 10s)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        connect: Option<Duration>,
+
+        /// Limit for waiting on data, restarted whenever some arrives, 0 for none (e.g. 30s)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        idle: Option<Duration>,
+    },
+    /// Get or set extra environment variables the core is started with
+    Env {
+        /// Variable name, omit to list all
+        #[arg(value_parser = parse_env_name)]
+        key: Option<String>,
+
+        /// Value (empty string to remove), omit to show the current value
+        value: Option<String>,
+    },
+    /// Get or set what the scheduled run-task updates (config, geo, core)
+    TaskComponents {
+        /// Comma-separated components, omit to show the current value
+        #[arg(value_delimiter = ',', ignore_case = true)]
+        components: Vec<TaskComponent>,
+    },
+    /// Get or set the log file format
+    LogFormat {
+        /// Log format (text, json), omit to show current value
+        #[arg(ignore_case = true)]
+        value: Option<LogFormat>,
+    },
+    /// Get or set how full log files are rotated
+    LogRotation {
+        /// Rotation (numbered, gzip), omit to show the current value
+        #[arg(ignore_case = true)]
+        value: Option<LogRotation>,
+
+        /// Number of compressed archives to keep (at least 1)
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        keep: Option<usize>,
+    },
+    /// Get or set the directory log files are written to
+    LogDir {
+        /// Directory (empty string for the default), omit to show the current value
+        value: Option<String>,
     },
     /// Get or set maximum runtime in hours (0 = disabled)
     MaxRuntime {
-        /// Maximum runtime in hours (0 to disable), omit to show current value
+        /// Maximum runtime as hours or a duration like 12h, 1d12h (0 to disable), omit to show current value
+        #[arg(value_parser = parse_hours)]
         value: Option<u64>,
     },
 }
@@ -113,42 +475,316 @@ pub enum ConfigCommands {
 /// Available CLI commands
 #[derive(Subcommand, Clone, Debug)]
 pub enum Commands {
+    /// Interactively choose core, download mirror, dashboard and subscription,
+    /// then install and start (runs on first use when in a terminal)
+    Setup,
+
     /// Install proxy core and UI components
     Install {
-        /// Force reinstallation even if already installed (applies to all when no subcommand)
+        /// Force reinstallation even if already installed (applies to all when no
+        /// subcommand). The core config is never replaced, see --reset-config
         #[arg(short, long, default_value_t = false)]
         force: bool,
 
+        /// Print the resolved download URLs instead of installing
+        #[arg(long, default_value_t = false, global = true)]
+        print_url: bool,
+
+        /// Replace the core config with the default; the current one is saved as <file>.bak
+        #[arg(
+            long,
+            default_value_t = false,
+            global = true,
+            conflicts_with = "print_url"
+        )]
+        reset_config: bool,
+
+        /// Comma-separated subset of components to install (e.g. core,geo),
+        /// not combinable with a subcommand
+        #[arg(long, value_delimiter = ',', ignore_case = true)]
+        components: Vec<InstallComponent>,
+
+        /// Download through this GitHub proxy instead of the configured one, without saving it
+        #[arg(long, ignore_case = true, global = true)]
+        proxy: Option<Proxy>,
+
         #[command(subcommand)]
         command: Option<InstallCommands>,
     },
 
     /// Start the proxy service
+    ///
+    /// Without flags, a running core is health-checked and restarted if the
+    /// check fails or its maximum runtime is exceeded. `-f` always restarts.
     Start {
+        /// Named instance to start instead of the default core
+        #[arg(conflicts_with_all = ["if_stopped", "dump_config"])]
+        instance: Option<String>,
+
         #[arg(short, long, default_value_t = false)]
         force: bool,
+
+        /// Start only if the core is not running; never restart it. Prints one
+        /// line ("started" or "already running") and exits 0 either way
+        #[arg(long, default_value_t = false, conflicts_with_all = ["force", "dump_config"])]
+        if_stopped: bool,
+
+        /// Print the resolved core executable, arguments, env and config, then exit without starting
+        #[arg(long, default_value_t = false)]
+        dump_config: bool,
+
+        /// Start even if the controller or a listen port is already in use
+        #[arg(long, default_value_t = false)]
+        ignore_port_conflict: bool,
+
+        /// Run the core attached to this terminal, showing its output, until Ctrl-C
+        #[arg(long, default_value_t = false, conflicts_with_all = ["instance", "if_stopped", "dump_config", "force"])]
+        foreground: bool,
+
+        /// Extra environment variable for the core, over 'config env' (repeatable)
+        #[arg(long, value_name = "KEY=VAL", value_parser = parse_env_pair, conflicts_with = "instance")]
+        env: Vec<(String, String)>,
+
+        /// Recovery: restart the core from a minimal config (proxy ports only, no TUN,
+        /// controller on localhost); the next 'crash start' goes back to the normal config
+        #[arg(long, default_value_t = false, conflicts_with_all = ["instance", "if_stopped", "dump_config", "foreground"])]
+        safe: bool,
     },
 
     /// Stop the proxy service
     Stop {
+        /// Named instance to stop instead of the default core
+        #[arg(conflicts_with_all = ["force", "wait", "all"])]
+        instance: Option<String>,
+
         #[arg(short, long, default_value_t = false)]
         force: bool,
+
+        /// Stop every core type (Mihomo, Clash, Singbox), e.g. one left running after a core switch
+        #[arg(long, default_value_t = false)]
+        all: bool,
+
+        /// Block until the core process has actually exited
+        #[arg(long, default_value_t = false)]
+        wait: bool,
+
+        /// Seconds to wait with --wait before failing
+        #[arg(long, default_value_t = 10, requires = "wait")]
+        timeout: u64,
+    },
+
+    /// Validate a config file with the core's own check, without touching the live config
+    TestConfig {
+        /// Path to the config file to test
+        path: PathBuf,
     },
 
     /// Show service status
-    Status,
+    Status {
+        /// Named instance to show instead of the default core
+        #[arg(conflicts_with = "core")]
+        instance: Option<String>,
+
+        /// Only show core version, pid, memory and uptime (no network or disk scans)
+        #[arg(long, default_value_t = false)]
+        core: bool,
+
+        /// Skip the public IP lookup and the core version check, showing local data only
+        #[arg(long, default_value_t = false)]
+        no_net: bool,
+
+        /// Exit with status 3 when the core is not running (0 when it is)
+        #[arg(long, default_value_t = false)]
+        exit_code: bool,
+
+        /// Also show whether the scheduled tasks are installed and when they run next
+        #[arg(long, default_value_t = false, conflicts_with_all = ["instance", "core"])]
+        tasks: bool,
+
+        /// Also show the recent starts, stops and restarts of the core, with their reasons
+        #[arg(long, default_value_t = false, conflicts_with = "instance")]
+        history: bool,
+
+        /// Number of events --history shows
+        #[arg(long, value_name = "N", default_value_t = 20, requires = "history")]
+        last: usize,
+    },
+
+    /// Check that requests actually go through the running proxy
+    Ping {
+        /// URL to request through the proxy
+        #[arg(short, long, default_value = DEFAULT_PING_URL)]
+        url: String,
+    },
+
+    /// Choose the node of a proxy group on the running core (interactive
+    /// without arguments when in a terminal)
+    Select {
+        /// Proxy group, e.g. "Proxy"
+        group: Option<String>,
+
+        /// Node to select in the group
+        #[arg(requires = "group")]
+        node: Option<String>,
+    },
+
+    /// Show crash's own log entries
+    Logs {
+        /// Only show entries at or above this level
+        #[arg(short, long, ignore_case = true)]
+        level: Option<LogLevel>,
+
+        /// Only show entries newer than this duration (e.g. 10m, 2h, 1d)
+        #[arg(short, long, value_parser = humantime::parse_duration)]
+        since: Option<Duration>,
+
+        /// Write logs, redacted config, version and status into a zip for bug reports
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["level", "since"])]
+        export: Option<PathBuf>,
+
+        #[command(subcommand)]
+        command: Option<LogsCommands>,
+    },
+
+    /// Show or switch the proxy core
+    Core {
+        /// Core to use (mihomo, clash, singbox), omit to show the current one
+        #[arg(ignore_case = true)]
+        value: Option<Core>,
+
+        /// Also install the core binary and its default config
+        #[arg(long, default_value_t = false, requires = "value")]
+        install: bool,
+
+        /// List the supported cores and whether crash-assets has a build for this target
+        #[arg(long, default_value_t = false, conflicts_with = "value")]
+        list: bool,
+    },
+
+    /// Inspect and clean up geo databases
+    Geo {
+        #[command(subcommand)]
+        command: GeoCommands,
+    },
+
+    /// Keep the core running, checking it every interval like the `start` cron task
+    #[command(args_conflicts_with_subcommands = true)]
+    Daemon {
+        /// How often to check the core (e.g. 30s, 10m)
+        #[arg(long, default_value = "10m", value_parser = humantime::parse_duration)]
+        interval: Duration,
+
+        /// Detach from the terminal and run in the background (unix only)
+        #[arg(long, default_value_t = false)]
+        background: bool,
+
+        /// Reload the core when its config file changes (PUT /configs, or a restart for Singbox)
+        #[arg(long, default_value_t = false)]
+        watch_config: bool,
+
+        /// Run under the Windows service manager (used by `install task --service`)
+        #[arg(long, default_value_t = false, hide = true)]
+        service: bool,
+
+        #[command(subcommand)]
+        command: Option<DaemonCommands>,
+    },
 
     /// Run scheduled update task
-    RunTask,
+    RunTask {
+        /// Comma-separated steps to run before restarting the core (config, geo, core),
+        /// defaults to 'config task-components'
+        #[arg(long, value_delimiter = ',', ignore_case = true)]
+        components: Vec<TaskComponent>,
+    },
 
     /// Remove scheduled update task
     RemoveTask,
 
+    /// Undo `install`: remove scheduled tasks, stop the daemon and cores and delete the installed files
+    Uninstall {
+        /// Only remove the Windows service installed by `install task --service`
+        #[arg(long, default_value_t = false, conflicts_with_all = ["keep_config", "yes"])]
+        service: bool,
+        /// Keep crash_config.json and the core configs (.yaml/.json), delete only cores, UI, geo files and logs
+        #[arg(long, default_value_t = false)]
+        keep_config: bool,
+        /// Delete the config directory without asking
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
+    },
+
+    /// Remove backups, download leftovers and cores/UIs no longer in use
+    Prune {
+        /// Backups to keep of each file, newest first
+        #[arg(long, default_value_t = 1)]
+        keep: usize,
+        /// Remove only what is older than this (e.g. 30d), including all backups and rotated logs
+        #[arg(long, value_parser = humantime::parse_duration, conflicts_with = "keep")]
+        older_than: Option<Duration>,
+        /// Only show what would be removed
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+
+    /// Refresh config, geo databases and core in one go, restarting the core once
+    Update {
+        /// Update every component (config, geo, core)
+        #[arg(long, required = true)]
+        all: bool,
+
+        /// Start the core afterwards even if it was not running
+        #[arg(short, long, default_value_t = false)]
+        force: bool,
+
+        /// Download through this GitHub proxy instead of the configured one, without saving it
+        #[arg(long, ignore_case = true)]
+        proxy: Option<Proxy>,
+    },
+
     /// Update configuration from URL
     UpdateUrl {
         /// Force update even if file exists
         #[arg(short, long, default_value_t = false)]
         force: bool,
+
+        /// Format of the downloaded config, detected from its content when omitted
+        #[arg(long, ignore_case = true)]
+        config_format: Option<ConfigFormat>,
+
+        /// Skip checking the new config with the installed core (`-t` / `check`)
+        #[arg(long, default_value_t = false)]
+        no_validate: bool,
+
+        /// Download the config's http proxy-providers/rule-providers into
+        /// their `path` now, so the core finds them locally
+        #[arg(long, default_value_t = false)]
+        prefetch_providers: bool,
+
+        /// Download through this GitHub proxy instead of the configured one, without saving it
+        #[arg(long, ignore_case = true)]
+        proxy: Option<Proxy>,
+    },
+
+    /// Convert a share-link subscription (vmess://, ss://, trojan://,
+    /// vless://) into a Mihomo config
+    Convert {
+        /// Subscription URL or file, defaults to 'config url'
+        source: Option<String>,
+
+        /// Convert locally instead of sending the subscription URL to the
+        /// converter; the converter is only used for links that can't be
+        /// converted here
+        #[arg(long, default_value_t = false)]
+        native: bool,
+
+        /// subconverter instance to use
+        #[arg(long, value_name = "URL", default_value = DEFAULT_CONVERTER)]
+        converter: String,
+
+        /// Write the config to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
     },
 
     /// View or modify configuration
@@ -161,16 +797,49 @@ pub enum Commands {
     Upgrade {
         #[arg(default_value_t = UpgradeRepo::CrashAssets, ignore_case = true)]
         repo: UpgradeRepo,
+
+        /// Download through this GitHub proxy instead of the configured one, without saving it
+        #[arg(long, ignore_case = true)]
+        proxy: Option<Proxy>,
     },
 
-    /// Usage: ei.exe [OPTIONS] [URL] [COMMAND]
-    #[command(trailing_var_arg = true, allow_hyphen_values = true)]
+    /// Install a binary with the embedded easy-install (see 'crash ei --help')
+    #[command(
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        disable_help_flag = true
+    )]
     Ei { args: Vec<String> },
 
     /// Generate shell completion scripts
     Completions {
         /// Shell type (bash, zsh, fish, powershell, elvish)
-        #[arg(ignore_case = true)]
-        shell: Shell,
+        #[arg(ignore_case = true, required_unless_present = "all")]
+        shell: Option<Shell>,
+
+        /// Generate completions for every supported shell (requires --output-dir)
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with = "shell",
+            requires = "output_dir"
+        )]
+        all: bool,
+
+        /// Write completion files into this directory, named per shell convention,
+        /// instead of printing to stdout
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+}
@@ -0,0 +1,89 @@
+// Interactive `crash select`.
+//
+// A bare `crash select` in a terminal lists the core's proxy groups, then
+// the nodes of the chosen group with their last measured delay, and
+// applies the pick through the controller API. Escape in the node list
+// goes back to the groups; escape in the group list quits.
+
+use super::setup::prompt_error;
+use crate::config::CrashConfig;
+use crate::config::proxies::{ProxyGroup, ProxyNode};
+use crate::error::{CrashError, Result};
+use dialoguer::Select;
+use dialoguer::theme::ColorfulTheme;
+
+/// Pick group → node until the user quits.
+pub async fn run(config: &CrashConfig) -> Result<()> {
+    let theme = ColorfulTheme::default();
+    loop {
+        let groups = config.proxy_groups().await?;
+        if groups.is_empty() {
+            return Err(CrashError::Config(
+                "The core reports no selectable proxy groups".to_string(),
+            ));
+        }
+
+        let width = groups
+            .iter()
+            .map(|g| g.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let items: Vec<String> = groups
+            .iter()
+            .map(|g| format!("{:width$}  {}", g.name, g.now, width = width))
+            .collect();
+        let Some(selected) = Select::with_theme(&theme)
+            .with_prompt("Proxy group (Esc to quit)")
+            .items(&items)
+            .default(0)
+            .interact_opt()
+            .map_err(prompt_error)?
+        else {
+            return Ok(());
+        };
+
+        let group = &groups[selected];
+        if let Some(node) = pick_node(&theme, group)? {
+            config.select_proxy(&group.name, &node).await?;
+            println!("{}: {}", group.name, node);
+        }
+    }
+}
+
+/// Show the nodes of `group`; `None` if the user backed out or kept the
+/// current one.
+fn pick_node(theme: &ColorfulTheme, group: &ProxyGroup) -> Result<Option<String>> {
+    if group.nodes.is_empty() {
+        println!("{} has no nodes", group.name);
+        return Ok(None);
+    }
+    let width = group
+        .nodes
+        .iter()
+        .map(|n| n.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<String> = group
+        .nodes
+        .iter()
+        .map(|n| format!("{:width$}  {}", n.name, format_delay(n), width = width))
+        .collect();
+    let current = group.nodes.iter().position(|n| n.name == group.now);
+
+    let picked = Select::with_theme(theme)
+        .with_prompt(format!("{} (Esc to go back)", group.name))
+        .items(&items)
+        .default(current.unwrap_or(0))
+        .interact_opt()
+        .map_err(prompt_error)?;
+    Ok(picked
+        .filter(|&i| Some(i) != current)
+        .map(|i| group.nodes[i].name.clone()))
+}
+
+fn format_delay(node: &ProxyNode) -> String {
+    match node.delay {
+        Some(ms) => format!("{}ms", ms),
+        None => "-".to_string(),
+    }
+}
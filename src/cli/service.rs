@@ -0,0 +1,210 @@
+// Windows service running `crash daemon`.
+//
+// `crash install task --service` registers `crash daemon --service` with the
+// service control manager (SCM), which starts it at boot and restarts it when
+// it fails. A service process has to hand its main thread to the SCM
+// dispatcher, so `daemon --service` does that and runs the regular daemon loop
+// from the service entry point until the SCM asks it to stop.
+
+use super::commands::{DEFAULT_DAEMON_INTERVAL, run_daemon};
+use crate::error::{CrashError, Result};
+use crate::{log_error, log_info};
+use std::ffi::{OsStr, OsString};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use windows_service::service::{
+    ServiceAccess, ServiceAction, ServiceActionType, ServiceControl, ServiceControlAccept,
+    ServiceErrorControl, ServiceExitCode, ServiceFailureActions, ServiceFailureResetPeriod,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+pub const SERVICE_NAME: &str = "CrashDaemon";
+const DISPLAY_NAME: &str = "Crash daemon";
+const DESCRIPTION: &str = "Keeps the crash proxy core running";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+/// Delay before the SCM restarts a failed daemon.
+const RESTART_DELAY: Duration = Duration::from_secs(60);
+/// Failure-free period after which the SCM resets its failure count.
+const RESET_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `ERROR_SERVICE_DOES_NOT_EXIST`
+const SERVICE_DOES_NOT_EXIST: i32 = 1060;
+
+/// Check interval handed from `daemon --service` to the service entry point.
+static INTERVAL: OnceLock<Duration> = OnceLock::new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_error(context: &str) -> impl FnOnce(windows_service::Error) -> CrashError + '_ {
+    move |e| CrashError::Platform(format!("{}: {}", context, e))
+}
+
+fn not_installed(e: &windows_service::Error) -> bool {
+    matches!(e, windows_service::Error::Winapi(e) if e.raw_os_error() == Some(SERVICE_DOES_NOT_EXIST))
+}
+
+/// Register and start the service. Returns false if it already exists.
+pub fn install(interval: Duration) -> Result<bool> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )
+    .map_err(service_error(
+        "Failed to open the service manager (run as administrator)",
+    ))?;
+
+    match manager.open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS) {
+        Ok(_) => return Ok(false),
+        Err(e) if not_installed(&e) => {}
+        Err(e) => return Err(service_error("Failed to query the service")(e)),
+    }
+
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(DISPLAY_NAME),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: vec![
+            OsString::from("daemon"),
+            OsString::from("--service"),
+            OsString::from("--interval"),
+            OsString::from(humantime::format_duration(interval).to_string()),
+        ],
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager
+        .create_service(&info, ServiceAccess::CHANGE_CONFIG | ServiceAccess::START)
+        .map_err(service_error("Failed to create the service"))?;
+
+    service
+        .set_description(DESCRIPTION)
+        .map_err(service_error("Failed to set the service description"))?;
+    service
+        .update_failure_actions(ServiceFailureActions {
+            reset_period: ServiceFailureResetPeriod::After(RESET_PERIOD),
+            reboot_msg: None,
+            command: None,
+            actions: Some(vec![
+                ServiceAction {
+                    action_type: ServiceActionType::Restart,
+                    delay: RESTART_DELAY,
+                };
+                3
+            ]),
+        })
+        .map_err(service_error("Failed to set the service recovery actions"))?;
+    // Also restart when the daemon exits with an error instead of crashing.
+    service
+        .set_failure_actions_on_non_crash_failures(true)
+        .map_err(service_error("Failed to set the service recovery actions"))?;
+
+    service
+        .start::<&OsStr>(&[])
+        .map_err(service_error("Failed to start the service"))?;
+    Ok(true)
+}
+
+/// Stop and remove the service. Returns false if it is not installed.
+pub fn uninstall() -> Result<bool> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(service_error(
+            "Failed to open the service manager (run as administrator)",
+        ))?;
+
+    let service = match manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    ) {
+        Ok(service) => service,
+        Err(e) if not_installed(&e) => return Ok(false),
+        Err(e) => return Err(service_error("Failed to open the service")(e)),
+    };
+
+    // Deletion completes once the service has stopped and all handles are closed.
+    service
+        .delete()
+        .map_err(service_error("Failed to delete the service"))?;
+    let status = service
+        .query_status()
+        .map_err(service_error("Failed to query the service"))?;
+    if status.current_state != ServiceState::Stopped {
+        service
+            .stop()
+            .map_err(service_error("Failed to stop the service"))?;
+    }
+    Ok(true)
+}
+
+/// Hand the process to the SCM dispatcher; blocks until the service stops.
+pub fn run(interval: Duration) -> Result<()> {
+    let _ = INTERVAL.set(interval);
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(service_error(
+        "Failed to connect to the service manager (is this running as a service?)",
+    ))
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        log_error!("Service failed: {}", e);
+    }
+}
+
+fn run_service() -> Result<()> {
+    let shutdown = Arc::new(Notify::new());
+    let handler_shutdown = shutdown.clone();
+    let status_handle =
+        service_control_handler::register(SERVICE_NAME, move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                handler_shutdown.notify_one();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })
+        .map_err(service_error(
+            "Failed to register the service control handler",
+        ))?;
+
+    let set_state = |state: ServiceState, exit_code: u32| {
+        status_handle.set_service_status(ServiceStatus {
+            service_type: SERVICE_TYPE,
+            current_state: state,
+            controls_accepted: if state == ServiceState::Running {
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+            } else {
+                ServiceControlAccept::empty()
+            },
+            exit_code: ServiceExitCode::Win32(exit_code),
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        })
+    };
+
+    set_state(ServiceState::Running, 0)
+        .map_err(service_error("Failed to report the service status"))?;
+    log_info!("Service started");
+
+    // `run` sets the interval before starting the dispatcher.
+    let interval = INTERVAL.get().copied().unwrap_or(DEFAULT_DAEMON_INTERVAL);
+    let result = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(CrashError::from)
+        .and_then(|runtime| runtime.block_on(run_daemon(interval, false, shutdown.notified())));
+
+    // A non-zero exit code counts as a failure, so the SCM restarts the daemon.
+    let exit_code = if result.is_ok() { 0 } else { 1 };
+    set_state(ServiceState::Stopped, exit_code)
+        .map_err(service_error("Failed to report the service status"))?;
+    result
+}
@@ -0,0 +1,172 @@
+// Interactive first-run setup.
+//
+// `crash setup` (and a bare `crash` on a machine without crash_config.json,
+// when run from a terminal) asks for the few settings a new install needs —
+// core, download mirror, dashboard, subscription URL and scheduled tasks —
+// then installs everything and starts the core.
+
+use super::commands::{handle_start, handle_task, handle_update_url, probe_proxy};
+use crate::config::core::Core;
+use crate::config::web::UiType;
+use crate::config::{CrashConfig, get_config_path};
+use crate::error::{CrashError, Result};
+use crate::log_info;
+use crate::utils::normalize_subscription_url;
+use clap::ValueEnum;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input, Select};
+use github_proxy::Proxy;
+use std::io::IsTerminal;
+
+/// Whether prompts can be shown: both stdin and stdout are terminals.
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Whether a bare `crash` should run the wizard instead of printing status:
+/// nothing has been configured yet and someone is there to answer.
+pub fn is_first_run() -> bool {
+    !get_config_path().exists() && is_interactive()
+}
+
+pub(crate) fn prompt_error(e: dialoguer::Error) -> CrashError {
+    CrashError::External(format!("Prompt failed: {}", e))
+}
+
+/// Ask a yes/no question that defaults to no.
+pub(crate) fn confirm(prompt: &str) -> Result<bool> {
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .map_err(prompt_error)
+}
+
+/// Walk through the setup questions, then install and start.
+pub async fn run() -> Result<()> {
+    if !is_interactive() {
+        return Err(CrashError::Config(
+            "Setup needs an interactive terminal; use 'crash core', 'crash config' and \
+             'crash install' instead"
+                .to_string(),
+        ));
+    }
+
+    let theme = ColorfulTheme::default();
+    let mut config = CrashConfig::load()?;
+    println!("Welcome to crash! Answer a few questions to get a proxy core running.\n");
+
+    let core = Select::with_theme(&theme)
+        .with_prompt("Proxy core")
+        .items(Core::ALL.iter().map(|core| core.name()))
+        .default(
+            Core::ALL
+                .iter()
+                .position(|&c| c == config.core)
+                .unwrap_or(0),
+        )
+        .interact()
+        .map_err(prompt_error)?;
+    config.core = Core::ALL[core];
+
+    config.proxy = select_proxy(&theme, config.proxy).await?;
+
+    let ui = Select::with_theme(&theme)
+        .with_prompt("Web dashboard")
+        .items(UiType::BUILTIN.iter().map(|ui| ui.name()))
+        .default(
+            UiType::BUILTIN
+                .iter()
+                .position(|ui| *ui == config.web.ui)
+                .unwrap_or(0),
+        )
+        .interact()
+        .map_err(prompt_error)?;
+    config.web.ui = UiType::BUILTIN[ui].clone();
+
+    let url = Input::<String>::with_theme(&theme)
+        .with_prompt("Subscription URL or config file (empty to use the default config)")
+        .default(config.url.clone())
+        .allow_empty(true)
+        .validate_with(|url: &String| normalize_subscription_url(url).map(|_| ()))
+        .interact_text()
+        .map_err(prompt_error)?;
+    // Validated above, so this only unwraps deep links and quotes.
+    config.url = normalize_subscription_url(&url).unwrap_or(url);
+
+    let task = Confirm::with_theme(&theme)
+        .with_prompt("Install scheduled tasks (start on boot, keep the core running)?")
+        .default(true)
+        .interact()
+        .map_err(prompt_error)?;
+
+    config.save()?;
+    println!("\nSettings saved to {}", get_config_path().display());
+    log_info!(
+        "Setup saved (core: {}, proxy: {}, ui: {}, url: {})",
+        config.core,
+        config.proxy,
+        config.web.ui,
+        !config.url.is_empty()
+    );
+
+    let install = Confirm::with_theme(&theme)
+        .with_prompt("Install and start now?")
+        .default(true)
+        .interact()
+        .map_err(prompt_error)?;
+    if !install {
+        println!("Run 'crash install' and then 'crash start' when ready.");
+        return Ok(());
+    }
+
+    config.cancellable(config.install(false)).await?;
+    if !config.url.is_empty() {
+        handle_update_url(true, None, true, false).await?;
+    }
+    if task {
+        handle_task()?;
+    }
+    handle_start(false, true, Vec::new()).await
+}
+
+/// Ask for the download mirror; "auto" tries each one and keeps the fastest.
+async fn select_proxy(theme: &ColorfulTheme, current: Proxy) -> Result<Proxy> {
+    let proxies = Proxy::value_variants();
+    let mut items = vec!["auto (test each mirror and pick the fastest)".to_string()];
+    items.extend(proxies.iter().map(|proxy| proxy.to_string()));
+
+    let selected = Select::with_theme(theme)
+        .with_prompt("GitHub download mirror")
+        .items(&items)
+        .default(0)
+        .interact()
+        .map_err(prompt_error)?;
+    if selected > 0 {
+        return Ok(proxies[selected - 1]);
+    }
+
+    let mut fastest = None;
+    for &proxy in proxies {
+        match probe_proxy(proxy).await {
+            Ok((_, _, latency)) => {
+                println!("  {:12} {} ms", proxy.to_string(), latency.as_millis());
+                if fastest.is_none_or(|(_, best)| latency < best) {
+                    fastest = Some((proxy, latency));
+                }
+            }
+            Err(e) => println!("  {:12} ❌ {}", proxy.to_string(), e),
+        }
+    }
+
+    Ok(match fastest {
+        Some((proxy, _)) => {
+            println!("Using {}", proxy);
+            proxy
+        }
+        None => {
+            println!("No mirror is reachable, keeping {}", current);
+            current
+        }
+    })
+}
@@ -1,13 +1,125 @@
 // Core-specific configuration
 
+use super::AssetsRepo;
+use super::ports::DefaultPorts;
 use crate::error::{CrashError, Result};
 use crate::utils::path::exe_extension;
-use github_proxy::Resource;
+use crate::utils::strip_suffix;
+use github_proxy::{Proxy, Resource};
 use guess_target::Target;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use strum::{Display, EnumString, IntoStaticStr};
 
+/// A geo database or IP list published in crash-assets.
+pub struct GeoFile {
+    /// Asset name; compression suffixes are stripped on install.
+    pub name: &'static str,
+    /// Only needed for IPv6 (dual-stack) routing.
+    pub ipv6: bool,
+}
+
+/// IPv6 CIDR list of mainland China, exposed to Mihomo as a rule provider by
+/// the patcher.
+pub const CHINA_IPV6_LIST: &str = "china_ipv6_list.txt";
+
+/// Covers IPv4 and IPv6.
+const GEOIP_METADB: GeoFile = GeoFile {
+    name: "geoip.metadb.tar.gz",
+    ipv6: false,
+};
+
+/// Geo files per core. Candidates from crash-assets not used yet: geoip.dat,
+/// geosite.dat, china_ip_list.txt, cn_mini.mmdb, Country.mmdb.
+const MIHOMO_GEO_FILES: &[GeoFile] = &[
+    GEOIP_METADB,
+    GeoFile {
+        name: CHINA_IPV6_LIST,
+        ipv6: true,
+    },
+];
+/// Rule providers are unsupported by Clash (its `RULE-SET` rules are
+/// commented out), so the IP lists would go unused.
+const CLASH_GEO_FILES: &[GeoFile] = &[GEOIP_METADB];
+/// sing-box's own geoip/geosite formats. crash-assets doesn't publish them,
+/// so they come from meta-rules-dat whatever the geo source.
+const SINGBOX_GEO_FILES: &[GeoFile] = &[
+    GeoFile {
+        name: "geoip.db",
+        ipv6: false,
+    },
+    GeoFile {
+        name: "geosite.db",
+        ipv6: false,
+    },
+];
+
+/// Where geo databases are downloaded from.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Display, Serialize, Deserialize, clap::ValueEnum,
+)]
+pub enum GeoSource {
+    /// The assets repository (ahaoboy/crash-assets or its configured fork)
+    #[default]
+    CrashAssets,
+    /// The `latest` release of MetaCubeX/meta-rules-dat
+    #[value(name = "metacubex")]
+    MetaCubeX,
+}
+
+/// Release of MetaCubeX/meta-rules-dat, refreshed daily.
+const METACUBEX_RELEASE: &str =
+    "https://github.com/MetaCubeX/meta-rules-dat/releases/download/latest";
+
+impl GeoSource {
+    /// Download URL of the crash-assets geo file `name` from this source,
+    /// `None` when the source doesn't publish it.
+    pub fn url(&self, name: &str, assets: &AssetsRepo) -> Option<String> {
+        self.url_via(name, assets, Proxy::Github)
+    }
+
+    /// Download URL of the geo file `name` from this source through `proxy`.
+    pub fn url_via(&self, name: &str, assets: &AssetsRepo, proxy: Proxy) -> Option<String> {
+        let singbox = SINGBOX_GEO_FILES.iter().any(|file| file.name == name);
+        match self {
+            GeoSource::CrashAssets => (!singbox).then(|| assets.file(name).url(&proxy))?,
+            // meta-rules-dat publishes the databases uncompressed.
+            GeoSource::MetaCubeX => {
+                if name != GEOIP_METADB.name && !singbox {
+                    return None;
+                }
+                let name = strip_suffix(name);
+                match proxy {
+                    Proxy::Github => Some(format!("{}/{}", METACUBEX_RELEASE, name)),
+                    proxy => Resource::Release {
+                        owner: "MetaCubeX".to_string(),
+                        repo: "meta-rules-dat".to_string(),
+                        tag: "latest".to_string(),
+                        name: name.to_string(),
+                    }
+                    .url(&proxy),
+                }
+            }
+        }
+    }
+
+    /// Whether this source publishes any of the geo files `core` downloads.
+    pub fn supports(&self, core: Core) -> bool {
+        let assets = AssetsRepo::default();
+        core.get_geo_files(true)
+            .iter()
+            .any(|name| self.url(name, &assets).is_some())
+    }
+}
+
+/// Older geo formats that no current core setup uses.
+const LEGACY_GEO_FILES: &[&str] = &[
+    "geoip.dat",
+    "geosite.dat",
+    "cn_mini.mmdb",
+    "china_ip_list.txt",
+];
+
 /// Proxy core type enumeration
 #[derive(
     Debug,
@@ -22,6 +134,7 @@ use strum::{Display, EnumString, IntoStaticStr};
     IntoStaticStr,
     Serialize,
     Deserialize,
+    clap::ValueEnum,
 )]
 pub enum Core {
     #[default]
@@ -31,6 +144,9 @@ pub enum Core {
 }
 
 impl Core {
+    /// All supported cores.
+    pub const ALL: [Core; 3] = [Core::Mihomo, Core::Clash, Core::Singbox];
+
     /// Get the core type name as a string
     pub fn name(&self) -> &'static str {
         self.into()
@@ -44,6 +160,12 @@ impl Core {
         }
     }
 
+    /// Whether the core can download the dashboard itself, from
+    /// `external-ui-url` (Mihomo) or `external_ui_download_url` (Singbox).
+    pub fn downloads_ui(&self) -> bool {
+        matches!(self, Core::Mihomo | Core::Singbox)
+    }
+
     /// Get the executable name with platform-specific extension
     pub fn exe_name(&self) -> String {
         format!("{}{}", self.name(), exe_extension())
@@ -82,7 +204,9 @@ impl Core {
             (Clash, Target::Aarch64UnknownLinuxMusl | Target::Aarch64UnknownLinuxGnu) => {
                 "clash-linux-arm64.tar.gz"
             }
-            (Clash, Target::X86_64UnknownLinuxGnu) => "clash-linux-amd64.tar.gz",
+            (Clash, Target::X86_64UnknownLinuxGnu | Target::X86_64UnknownLinuxMusl) => {
+                "clash-linux-amd64.tar.gz"
+            }
 
             (Singbox, Target::X86_64PcWindowsMsvc | Target::X86_64PcWindowsGnu) => {
                 "sing-box-windows-amd64.tar.gz"
@@ -102,38 +226,147 @@ impl Core {
         Ok(filename.to_string())
     }
 
-    /// Get the repository resource for downloading the core
-    pub fn repo(&self, target: &Target) -> Result<Resource> {
-        let filename = self.release_file_name(target)?;
+    /// Get the repository resource for downloading the core: the upstream
+    /// release at `version` when one is pinned, otherwise the build in
+    /// `assets`.
+    pub fn repo(
+        &self,
+        target: &Target,
+        assets: &AssetsRepo,
+        version: Option<&str>,
+    ) -> Result<Resource> {
+        match version {
+            Some(version) => self.upstream_release(target, version),
+            None => Ok(assets.file(self.release_file_name(target)?)),
+        }
+    }
 
-        Ok(Resource::File {
-            owner: "ahaoboy".to_string(),
-            repo: "crash-assets".to_string(),
-            reference: "main".to_string(),
-            path: filename.to_string(),
-        })
+    /// Asset of the upstream GitHub release `version` for `target`, named
+    /// after the platform of the crash-assets build (`<core>-linux-arm64`).
+    pub fn upstream_release(&self, target: &Target, version: &str) -> Result<Resource> {
+        let file = self.release_file_name(target)?;
+        let platform = file
+            .strip_suffix(".tar.gz")
+            .and_then(|name| name.split_once(self.asset_prefix()))
+            .map(|(_, platform)| platform)
+            .unwrap_or_default();
+        let windows = platform.starts_with("windows");
+        let release = |owner: &str, repo: &str, name: String| Resource::Release {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            tag: format!("v{}", version),
+            name,
+        };
+        match self {
+            Core::Mihomo => Ok(release(
+                "MetaCubeX",
+                "mihomo",
+                format!(
+                    "mihomo-{}-v{}.{}",
+                    platform,
+                    version,
+                    if windows { "zip" } else { "gz" }
+                ),
+            )),
+            Core::Singbox => Ok(release(
+                "SagerNet",
+                "sing-box",
+                format!(
+                    "sing-box-{}-{}.{}",
+                    version,
+                    platform,
+                    if windows { "zip" } else { "tar.gz" }
+                ),
+            )),
+            Core::Clash => Err(CrashError::Config(
+                "Clash has no upstream releases left to pin a version from".to_string(),
+            )),
+        }
+    }
+
+    /// Start of the crash-assets file names of this core, up to the platform.
+    fn asset_prefix(&self) -> &'static str {
+        match self {
+            Core::Mihomo => "mihomo-",
+            Core::Clash => "clash-",
+            Core::Singbox => "sing-box-",
+        }
+    }
+
+    /// Geo databases and IP lists downloaded for this core; the IPv6-only
+    /// lists are included with `ipv6`.
+    pub fn get_geo_files(&self, ipv6: bool) -> Vec<&'static str> {
+        let files: &[GeoFile] = match self {
+            Core::Mihomo => MIHOMO_GEO_FILES,
+            Core::Clash => CLASH_GEO_FILES,
+            Core::Singbox => SINGBOX_GEO_FILES,
+        };
+        files
+            .iter()
+            .filter(|file| ipv6 || !file.ipv6)
+            .map(|file| file.name)
+            .collect()
     }
 
-    pub fn get_geo_files(&self) -> Vec<&'static str> {
+    /// Databases the core downloads into its home directory by itself when
+    /// its rules need them.
+    fn self_managed_geo_files(&self) -> &'static [&'static str] {
         match self {
-            Core::Mihomo | Core::Clash => vec![
-                "geoip.metadb.tar.gz",
-                // "geoip.dat.tar.gz",
-                // "geosite.dat.tar.gz",
+            Core::Mihomo => &[
+                "geoip.metadb",
+                "GeoIP.dat",
+                "GeoSite.dat",
+                "Country.mmdb",
+                "GeoLite2-ASN.mmdb",
             ],
-            // Core::Clash => vec![
-            //     "china_ip_list.txt",
-            //     "china_ipv6_list.txt",
-            //     "cn_mini.mmdb",
-            //     "Country.mmdb",
-            //     "geoip_cn.db",
-            //     "geosite.dat",
-            //     "geosite_cn.db",
-            //     "mrs_geosite_cn.mrs",
-            //     "srs_geoip_cn.srs",
-            //     "srs_geosite_cn.srs",
-            // ],
-            _ => vec![],
+            Core::Clash => &["Country.mmdb"],
+            Core::Singbox => &["geoip.db", "geosite.db"],
+        }
+    }
+
+    /// File names of every geo file this core may use from the config
+    /// directory: those crash installs (`get_geo_files` without compression
+    /// suffixes) and those the core fetches itself.
+    pub fn installed_geo_files(&self, ipv6: bool) -> Vec<&'static str> {
+        let mut files = Vec::new();
+        for name in self
+            .get_geo_files(ipv6)
+            .into_iter()
+            .map(strip_suffix)
+            .chain(self.self_managed_geo_files().iter().copied())
+        {
+            if !files.contains(&name) {
+                files.push(name);
+            }
+        }
+        files
+    }
+
+    /// File names of the geo files any core may leave in the config
+    /// directory, including older formats.
+    pub fn known_geo_files() -> Vec<&'static str> {
+        let mut files: Vec<&'static str> = Core::ALL
+            .iter()
+            .flat_map(|core| core.installed_geo_files(true))
+            .chain(LEGACY_GEO_FILES.iter().copied())
+            .collect();
+        files.sort_by_key(|name| name.to_ascii_lowercase());
+        files.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        files
+    }
+
+    /// Default local ports for this core, used when rendering its default
+    /// config and when `CrashConfig::mixed_port` is unset.
+    pub fn default_ports(&self) -> DefaultPorts {
+        match self {
+            Core::Mihomo | Core::Clash => DefaultPorts {
+                mixed: 7890,
+                controller: 9090,
+            },
+            Core::Singbox => DefaultPorts {
+                mixed: 2080,
+                controller: 9090,
+            },
         }
     }
 
@@ -144,4 +377,242 @@ impl Core {
             Core::Singbox => vec![("ENABLE_DEPRECATED_SPECIAL_OUTBOUNDS", "true")],
         }
     }
+
+    /// Arguments that make the core print its version.
+    pub fn version_args(&self) -> &'static [&'static str] {
+        match self {
+            Core::Mihomo | Core::Clash => &["-v"],
+            Core::Singbox => &["version"],
+        }
+    }
+
+    /// Extract the version from the output of `version_args`, e.g. `1.19.15`
+    /// from "Mihomo Meta v1.19.15 linux amd64 with go1.25.1 ...". The whole
+    /// output is scanned for the first `\d+.\d+.\d+`, so the word order and
+    /// extra lines don't matter.
+    pub fn parse_version<'a>(&self, output: &'a str) -> Option<&'a str> {
+        match self {
+            Core::Mihomo | Core::Clash | Core::Singbox => find_semver(output),
+        }
+    }
+}
+
+/// Check a pinned core version: `1.19.15` or `v1.19.15`, optionally with a
+/// pre-release suffix such as `1.13.0-alpha.10`. Returns it without the `v`.
+pub fn parse_core_version(s: &str) -> std::result::Result<String, String> {
+    let version = s.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let valid = semver_len(version.as_bytes()).is_some_and(|len| {
+        let suffix = &version[len..];
+        suffix.is_empty()
+            || suffix.strip_prefix('-').is_some_and(|pre| {
+                !pre.is_empty()
+                    && pre
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+            })
+    });
+    if valid {
+        Ok(version.to_string())
+    } else {
+        Err(format!(
+            "{:?} is not a version like 1.19.15 or 1.13.0-alpha.10",
+            s
+        ))
+    }
+}
+
+/// First `\d+.\d+.\d+` in `text` that stands on its own or after a `v`, so
+/// `go1.25.1` in a build line isn't taken for the core's version.
+fn find_semver(text: &str) -> Option<&str> {
+    let bytes = text.as_bytes();
+    let separated = |i: usize| i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+    (0..bytes.len())
+        .filter(|&start| {
+            separated(start) || (matches!(bytes[start - 1], b'v' | b'V') && separated(start - 1))
+        })
+        .find_map(|start| semver_len(&bytes[start..]).map(|len| &text[start..start + len]))
+}
+
+/// Length of the `\d+.\d+.\d+` at the start of `bytes`, if there is one.
+fn semver_len(bytes: &[u8]) -> Option<usize> {
+    let mut len = 0;
+    for part in 0..3 {
+        if part > 0 {
+            if bytes.get(len) != Some(&b'.') {
+                return None;
+            }
+            len += 1;
+        }
+        let digits = bytes[len..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        len += digits;
+    }
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv6_geo_files_are_opt_in() {
+        assert_eq!(Core::Mihomo.get_geo_files(false), ["geoip.metadb.tar.gz"]);
+        assert_eq!(
+            Core::Mihomo.get_geo_files(true),
+            ["geoip.metadb.tar.gz", CHINA_IPV6_LIST]
+        );
+        assert_eq!(Core::Clash.get_geo_files(true), ["geoip.metadb.tar.gz"]);
+        assert_eq!(
+            Core::Singbox.get_geo_files(false),
+            ["geoip.db", "geosite.db"]
+        );
+
+        // Installed names, as loaded by the core.
+        let installed = Core::Mihomo.installed_geo_files(true);
+        assert_eq!(installed[..2], ["geoip.metadb", CHINA_IPV6_LIST]);
+        assert_eq!(
+            installed.iter().filter(|&&f| f == "geoip.metadb").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn geo_sources_publish_the_metadb() {
+        let assets = AssetsRepo::default();
+        assert_eq!(
+            GeoSource::MetaCubeX
+                .url(GEOIP_METADB.name, &assets)
+                .as_deref(),
+            Some(
+                "https://github.com/MetaCubeX/meta-rules-dat/releases/download/latest/geoip.metadb"
+            )
+        );
+        assert_eq!(GeoSource::MetaCubeX.url(CHINA_IPV6_LIST, &assets), None);
+        assert!(GeoSource::MetaCubeX.supports(Core::Mihomo));
+
+        assert_eq!(GeoSource::CrashAssets.url("geosite.db", &assets), None);
+        assert_eq!(
+            GeoSource::MetaCubeX.url("geosite.db", &assets).as_deref(),
+            Some("https://github.com/MetaCubeX/meta-rules-dat/releases/download/latest/geosite.db")
+        );
+        assert!(GeoSource::MetaCubeX.supports(Core::Singbox));
+        assert!(!GeoSource::CrashAssets.supports(Core::Singbox));
+    }
+
+    #[test]
+    fn known_geo_files_cover_every_core() {
+        let known = Core::known_geo_files();
+        for core in Core::ALL {
+            for file in core.installed_geo_files(true) {
+                assert!(
+                    known.iter().any(|k| k.eq_ignore_ascii_case(file)),
+                    "{}",
+                    file
+                );
+            }
+        }
+        // Legacy lowercase geosite.dat is matched by Mihomo's GeoSite.dat.
+        assert!(known.contains(&"GeoSite.dat"));
+        assert!(!known.contains(&"geosite.dat"));
+    }
+
+    #[test]
+    fn clash_installs_from_crash_assets() {
+        for target in [
+            Target::Aarch64UnknownLinuxGnu,
+            Target::Aarch64UnknownLinuxMusl,
+        ] {
+            assert_eq!(
+                Core::Clash.release_file_name(&target).unwrap(),
+                "clash-linux-arm64.tar.gz"
+            );
+        }
+        assert_eq!(
+            Core::Clash
+                .release_file_name(&Target::X86_64UnknownLinuxMusl)
+                .unwrap(),
+            "clash-linux-amd64.tar.gz"
+        );
+        match Core::Clash.repo(
+            &Target::Aarch64UnknownLinuxMusl,
+            &AssetsRepo::default(),
+            None,
+        ) {
+            Ok(Resource::File { repo, path, .. }) => {
+                assert_eq!(repo, "crash-assets");
+                assert!(path.ends_with("clash-linux-arm64.tar.gz"), "{}", path);
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        // crash-assets has no Clash build for Windows.
+        assert!(
+            Core::Clash
+                .release_file_name(&Target::X86_64PcWindowsMsvc)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn pinned_versions_use_upstream_asset_names() {
+        let name = |core: Core, target: Target| match core.upstream_release(&target, "1.2.3") {
+            Ok(Resource::Release { tag, name, .. }) => format!("{} {}", tag, name),
+            other => panic!("{:?}", other.map(|_| ())),
+        };
+        assert_eq!(
+            name(Core::Mihomo, Target::Aarch64UnknownLinuxMusl),
+            "v1.2.3 mihomo-linux-arm64-v1.2.3.gz"
+        );
+        assert_eq!(
+            name(Core::Mihomo, Target::X86_64PcWindowsMsvc),
+            "v1.2.3 mihomo-windows-amd64-v1.2.3.zip"
+        );
+        assert_eq!(
+            name(Core::Singbox, Target::X86_64UnknownLinuxGnu),
+            "v1.2.3 sing-box-1.2.3-linux-amd64.tar.gz"
+        );
+        assert!(
+            Core::Clash
+                .upstream_release(&Target::X86_64UnknownLinuxGnu, "1.2.3")
+                .is_err()
+        );
+
+        assert_eq!(parse_core_version(" v1.19.15 ").unwrap(), "1.19.15");
+        assert_eq!(
+            parse_core_version("1.13.0-alpha.10").unwrap(),
+            "1.13.0-alpha.10"
+        );
+        for bad in ["", "1.19", "latest", "1.19.15-", "1.19.15 beta"] {
+            assert!(parse_core_version(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parses_versions_from_core_output() {
+        let mihomo = "Mihomo Meta v1.19.15 linux amd64 with go1.25.1 Wed Oct 15 10:00:00 UTC 2025\n\
+                      Use tags: with_gvisor\n";
+        assert_eq!(Core::Mihomo.parse_version(mihomo), Some("1.19.15"));
+
+        let singbox = "sing-box version 1.12.12\n\n\
+                       Environment: go1.25.1 linux/amd64\n\
+                       Tags: with_gvisor,with_quic,with_utls\n\
+                       Revision: 2f9ea5ae3ff9ac1b9db1c2bd8bd4ba7c8c4c6b7e\n\
+                       CGO: disabled\n";
+        assert_eq!(Core::Singbox.parse_version(singbox), Some("1.12.12"));
+
+        let clash = "Clash 2023.08.17 linux amd64 with go1.21.0\n";
+        assert_eq!(Core::Clash.parse_version(clash), Some("2023.08.17"));
+        let clash = "Clash v1.18.0 linux arm64 with go1.20.4 Fri May 12 00:00:00 UTC 2023\n";
+        assert_eq!(Core::Clash.parse_version(clash), Some("1.18.0"));
+
+        // Alpha builds print a commit instead of a version.
+        let alpha =
+            "Mihomo Meta alpha-6fd6b11 linux amd64 with go1.25.1 Wed Oct 15 10:00:00 UTC 2025\n";
+        assert_eq!(Core::Mihomo.parse_version(alpha), None);
+    }
 }
@@ -0,0 +1,101 @@
+// DNS overrides rendered into the core config by `patch_config`.
+
+use crate::error::{CrashError, Result};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use strum::{Display, EnumString};
+
+/// URL schemes accepted for encrypted DNS servers (DoH, DoT, DoQ).
+const DNS_URL_SCHEMES: [&str; 3] = ["https://", "tls://", "quic://"];
+
+/// Mihomo/Clash `dns.enhanced-mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnhancedMode {
+    FakeIp,
+    RedirHost,
+}
+
+/// User DNS overrides. Empty fields leave the downloaded config untouched.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    pub nameserver: Vec<String>,
+    pub fallback: Vec<String>,
+    pub enhanced_mode: Option<EnhancedMode>,
+}
+
+impl DnsConfig {
+    /// Whether any override is set.
+    pub fn is_empty(&self) -> bool {
+        self.nameserver.is_empty() && self.fallback.is_empty() && self.enhanced_mode.is_none()
+    }
+}
+
+impl std::fmt::Display for DnsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |servers: &[String]| match servers {
+            [] => "(from config)".to_string(),
+            _ => servers.join(","),
+        };
+        writeln!(f, "nameserver: {}", list(&self.nameserver))?;
+        writeln!(f, "fallback: {}", list(&self.fallback))?;
+        match self.enhanced_mode {
+            Some(mode) => write!(f, "enhanced-mode: {}", mode),
+            None => write!(f, "enhanced-mode: (from config)"),
+        }
+    }
+}
+
+/// Parse a comma-separated server list; each entry must be an IP address or
+/// a DoH/DoT/DoQ URL. An empty string yields an empty list.
+pub fn parse_dns_servers(servers: &[String]) -> Result<Vec<String>> {
+    servers
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let valid = s.parse::<IpAddr>().is_ok()
+                || DNS_URL_SCHEMES
+                    .iter()
+                    .any(|scheme| s.strip_prefix(scheme).is_some_and(|rest| !rest.is_empty()));
+            if valid {
+                Ok(s.to_string())
+            } else {
+                Err(CrashError::Config(format!(
+                    "Invalid DNS server {:?}: expected an IP address or an https:// / tls:// / quic:// URL",
+                    s
+                )))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn accepts_ips_and_encrypted_urls() {
+        let servers = strings(&[
+            "223.5.5.5",
+            " 2606:4700::1111",
+            "https://dns.alidns.com/dns-query",
+            "tls://1.1.1.1",
+        ]);
+        assert_eq!(parse_dns_servers(&servers).unwrap().len(), 4);
+        assert!(parse_dns_servers(&strings(&[""])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_hostnames_and_bare_schemes() {
+        assert!(parse_dns_servers(&strings(&["dns.google"])).is_err());
+        assert!(parse_dns_servers(&strings(&["https://"])).is_err());
+        assert!(parse_dns_servers(&strings(&["udp://8.8.8.8"])).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+// Core lifecycle history shown by `crash status --history`.
+//
+// Every start, stop and restart of the default core is appended to
+// `events.log` in the config directory as one line:
+// `<rfc3339 time> <kind> <core> <reason>`. Only the newest entries are kept.
+
+use super::core::Core;
+use super::get_config_dir;
+use crate::error::Result;
+use crate::log_warn;
+use crate::utils::fs::atomic_write;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+use strum::{Display, EnumString};
+
+const EVENTS_FILE: &str = "events.log";
+/// Entries kept in `events.log`; about a week of restarts every 10 minutes.
+const MAX_EVENTS: usize = 1000;
+/// Size past which `events.log` is trimmed back to `MAX_EVENTS` entries,
+/// about half again as many, so not every event rewrites the file.
+const TRIM_SIZE: u64 = 64 * 1024;
+
+/// What happened to the core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum EventKind {
+    Start,
+    Stop,
+    Restart,
+}
+
+/// Why it happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum EventReason {
+    /// Asked for by a command (`start`, `stop`, `start -f`, a reload).
+    Manual,
+    /// The core had exited on its own and was started again.
+    Crashed,
+    /// The core ran longer than `max_runtime_hours`.
+    MaxRuntime,
+    /// Requests through the running core failed.
+    Connectivity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub time: SystemTime,
+    pub kind: EventKind,
+    pub core: Core,
+    pub reason: EventReason,
+}
+
+impl Event {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        Some(Event {
+            time: humantime::parse_rfc3339(fields.next()?).ok()?,
+            kind: EventKind::from_str(fields.next()?).ok()?,
+            core: Core::from_str(fields.next()?).ok()?,
+            reason: EventReason::from_str(fields.next()?).ok()?,
+        })
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            humantime::format_rfc3339_seconds(self.time),
+            self.kind,
+            self.core,
+            self.reason
+        )
+    }
+}
+
+pub fn events_path() -> PathBuf {
+    get_config_dir().join(EVENTS_FILE)
+}
+
+/// Append an event for `core` now. Failing to record it only logs a
+/// warning, so it never stops the core from starting or stopping.
+pub fn record_event(core: Core, kind: EventKind, reason: EventReason) {
+    let event = Event {
+        time: SystemTime::now(),
+        kind,
+        core,
+        reason,
+    };
+    if let Err(e) = append_event(&events_path(), &event) {
+        log_warn!("Failed to record core event '{}': {}", event, e);
+    }
+}
+
+fn append_event(path: &Path, event: &Event) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(format!("{}\n", event).as_bytes())?;
+    if file.metadata()?.len() > TRIM_SIZE {
+        let content = std::fs::read_to_string(path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let keep = lines.len().saturating_sub(MAX_EVENTS);
+        let mut out = lines[keep..].join("\n");
+        out.push('\n');
+        atomic_write(path, &out)?;
+    }
+    Ok(())
+}
+
+/// Every recorded event, oldest first. Unreadable lines are skipped.
+pub fn read_events() -> Result<Vec<Event>> {
+    let content = match std::fs::read_to_string(events_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content.lines().filter_map(Event::parse).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::override_config_dir;
+
+    #[test]
+    fn events_round_trip_and_are_capped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        assert!(read_events().unwrap().is_empty());
+
+        record_event(Core::Mihomo, EventKind::Start, EventReason::Manual);
+        record_event(Core::Mihomo, EventKind::Restart, EventReason::MaxRuntime);
+        std::fs::write(
+            events_path(),
+            std::fs::read_to_string(events_path()).unwrap() + "garbage\n",
+        )
+        .unwrap();
+        record_event(Core::Singbox, EventKind::Stop, EventReason::Manual);
+
+        let events = read_events().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].kind, EventKind::Restart);
+        assert_eq!(events[1].reason, EventReason::MaxRuntime);
+        assert_eq!(events[2].core, Core::Singbox);
+
+        for _ in 0..2 * MAX_EVENTS {
+            record_event(Core::Mihomo, EventKind::Restart, EventReason::Crashed);
+        }
+        let content = std::fs::read_to_string(events_path()).unwrap();
+        let count = content.lines().count();
+        assert!((MAX_EVENTS..2 * MAX_EVENTS).contains(&count), "{}", count);
+        assert!(
+            content
+                .lines()
+                .all(|line| line.ends_with("restart Mihomo crashed"))
+        );
+    }
+}
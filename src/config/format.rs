@@ -0,0 +1,235 @@
+// Format checks for imported core configs.
+//
+// Mihomo/Clash read YAML and Singbox reads JSON. A subscription in the
+// other format would be written as-is and the core would refuse to start,
+// so the content is checked (and converted where that is safe) before it
+// replaces the working config.
+
+use super::core::Core;
+use crate::error::{CrashError, Result};
+use crate::log_warn;
+use serde_json::Value;
+use strum::Display;
+
+/// Syntax of a core config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, clap::ValueEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+}
+
+impl Core {
+    /// Format of the config file this core reads.
+    pub fn config_format(&self) -> ConfigFormat {
+        match self {
+            Core::Mihomo | Core::Clash => ConfigFormat::Yaml,
+            Core::Singbox => ConfigFormat::Json,
+        }
+    }
+}
+
+/// Shortest body accepted as a config; anything smaller can't hold a
+/// working proxy setup and is most likely an error message.
+const MIN_CONFIG_LEN: usize = 50;
+
+/// Refuse a downloaded body that can't be a config: empty or whitespace,
+/// shorter than `MIN_CONFIG_LEN`, or an HTML page (a provider's error or
+/// login page served with status 200).
+pub fn check_config_body(content: &str) -> Result<()> {
+    let trimmed = content.trim();
+    let head = trimmed
+        .get(..trimmed.len().min(16))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let problem = if trimmed.is_empty() {
+        "is empty".to_string()
+    } else if head.starts_with("<!doctype") || head.starts_with("<html") {
+        "is an HTML page, not a config (expired subscription?)".to_string()
+    } else if trimmed.len() < MIN_CONFIG_LEN {
+        format!("is only {} bytes: {:?}", trimmed.len(), trimmed)
+    } else {
+        return Ok(());
+    };
+    Err(CrashError::Config(format!(
+        "The downloaded config {}. The current config was kept",
+        problem
+    )))
+}
+
+/// Guess the format of `content`: a JSON object is JSON, anything else is
+/// treated as YAML.
+pub fn detect_config_format(content: &str) -> ConfigFormat {
+    if content.trim_start().starts_with('{') && serde_json::from_str::<Value>(content).is_ok() {
+        ConfigFormat::Json
+    } else {
+        ConfigFormat::Yaml
+    }
+}
+
+/// Make `content` usable by `core`. `format` overrides detection. A
+/// Clash-style config in JSON is converted to YAML for the YAML cores;
+/// every other mismatch is an error naming the core that matches.
+pub fn ensure_config_format(
+    core: Core,
+    content: &str,
+    format: Option<ConfigFormat>,
+) -> Result<String> {
+    let format = format.unwrap_or_else(|| detect_config_format(content));
+    if format == core.config_format() {
+        return Ok(content.to_string());
+    }
+
+    let mismatch = |suggested: Core| {
+        CrashError::Config(format!(
+            "The config is {} but {} expects {}; switch cores with 'crash core {}' \
+             or pass --config-format if detection is wrong. The current config was kept",
+            format,
+            core,
+            core.config_format(),
+            suggested.name().to_lowercase()
+        ))
+    };
+
+    match (core.config_format(), format) {
+        (ConfigFormat::Yaml, ConfigFormat::Json) => {
+            let value: Value = serde_json::from_str(content)
+                .map_err(|e| CrashError::Config(format!("Invalid JSON config: {}", e)))?;
+            if value.get("outbounds").is_some() || !value.is_object() {
+                return Err(mismatch(Core::Singbox));
+            }
+            log_warn!("Converting JSON config to YAML for {}", core);
+            Ok(json_to_yaml(&value))
+        }
+        _ => Err(mismatch(Core::Mihomo)),
+    }
+}
+
+/// Render a JSON value as block-style YAML. Plain keys are written bare so
+/// the line-based patcher recognises them; strings keep their JSON quoting,
+/// which YAML accepts as double-quoted scalars.
+pub fn json_to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    write_yaml(value, 0, &mut out);
+    out
+}
+
+fn write_yaml(value: &Value, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                if is_collection(child) {
+                    out.push_str(&format!("{}{}:\n", pad, yaml_key(key)));
+                    write_yaml(child, indent + 2, out);
+                } else {
+                    out.push_str(&format!("{}{}: {}\n", pad, yaml_key(key), child));
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                if is_collection(item) {
+                    // Render one level deeper, then put the dash in front of
+                    // the first line: `- name: a` / `  type: ss`.
+                    let mut nested = String::new();
+                    write_yaml(item, indent + 2, &mut nested);
+                    out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                } else {
+                    out.push_str(&format!("{}- {}\n", pad, item));
+                }
+            }
+        }
+        scalar => out.push_str(&format!("{}{}\n", pad, scalar)),
+    }
+}
+
+/// Non-empty object or array, which needs its own lines.
+fn is_collection(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+/// `key` as a YAML mapping key, quoted unless it is a plain identifier.
+fn yaml_key(key: &str) -> String {
+    let plain = key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if plain {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASH_YAML: &str = "mixed-port: 7890\nproxies:\n  - name: a\n    type: ss\n";
+    const CLASH_JSON: &str =
+        r#"{"mixed-port": 7890, "proxies": [{"name": "a", "type": "ss"}], "rules": []}"#;
+    const SINGBOX_JSON: &str = r#"{"inbounds": [], "outbounds": [{"type": "direct"}]}"#;
+
+    #[test]
+    fn empty_tiny_and_html_bodies_are_refused() {
+        for body in [
+            "",
+            " \n\t\n",
+            "proxies: []",
+            "<!DOCTYPE html><html><body>Not found</body></html>",
+        ] {
+            assert!(check_config_body(body).is_err(), "{:?}", body);
+        }
+        let html = format!("\n<HTML>\n<body>{}</body></html>", "x".repeat(100));
+        assert!(check_config_body(&html).is_err());
+
+        let config = "mixed-port: 7890\nmode: rule\nproxies:\n  - {name: a, type: ss}\n";
+        assert!(check_config_body(config).is_ok());
+    }
+
+    #[test]
+    fn detects_json_and_yaml() {
+        assert_eq!(detect_config_format(SINGBOX_JSON), ConfigFormat::Json);
+        assert_eq!(detect_config_format(CLASH_YAML), ConfigFormat::Yaml);
+        // Flow-style YAML that is not valid JSON.
+        assert_eq!(detect_config_format("{a: 1}"), ConfigFormat::Yaml);
+    }
+
+    #[test]
+    fn matching_formats_pass_through() {
+        assert_eq!(
+            ensure_config_format(Core::Mihomo, CLASH_YAML, None).unwrap(),
+            CLASH_YAML
+        );
+        assert_eq!(
+            ensure_config_format(Core::Singbox, SINGBOX_JSON, None).unwrap(),
+            SINGBOX_JSON
+        );
+    }
+
+    #[test]
+    fn clash_json_is_converted_for_yaml_cores() {
+        let yaml = ensure_config_format(Core::Clash, CLASH_JSON, None).unwrap();
+        assert_eq!(
+            yaml,
+            "mixed-port: 7890\nproxies:\n  - name: \"a\"\n    type: \"ss\"\nrules: []\n"
+        );
+    }
+
+    #[test]
+    fn mismatches_are_refused() {
+        let err = ensure_config_format(Core::Mihomo, SINGBOX_JSON, None).unwrap_err();
+        assert!(err.to_string().contains("crash core singbox"));
+
+        let err = ensure_config_format(Core::Singbox, CLASH_YAML, None).unwrap_err();
+        assert!(err.to_string().contains("crash core mihomo"));
+
+        // An explicit format wins over detection.
+        assert!(ensure_config_format(Core::Singbox, CLASH_YAML, Some(ConfigFormat::Json)).is_ok());
+    }
+}
@@ -3,25 +3,62 @@
 // Split out of `config/mod.rs` to keep storage/validation logic small and
 // let this file focus on downloading / extracting / updating assets.
 
-use super::CrashConfig;
+use super::core::{Core, GeoSource};
+use super::format::{ConfigFormat, check_config_body, ensure_config_format};
 use super::get_config_dir;
-use super::patcher::patch_config;
+use super::patcher::{default_config, patch_config};
+use super::runtime::RESTART_STOP_TIMEOUT;
+use super::web::UiType;
+use super::{AssetsRepo, CrashConfig};
 use crate::cli::UpgradeRepo;
 use crate::error::{CrashError, Result};
-use crate::log_info;
-use crate::utils::download::download_text;
-use crate::utils::fs::{atomic_write, ensure_dir, file_exists};
-use crate::utils::{is_url, strip_suffix};
+use crate::log::{log_archives, log_files};
+use crate::utils::archive::{content_root, extract_archive, largest_file};
+use crate::utils::decode::decode_subscription_body;
+use crate::utils::download::{
+    DownloadOptions, FailureKind, RetryConfig, acquire_permit, content_length,
+    download_file_checked, download_text, parse_sha256_sidecar,
+};
+use crate::utils::format_size;
+use crate::utils::fs::{
+    Arch, atomic_write, atomic_write_bytes, detect_binary_arch, ensure_dir, file_exists,
+    is_executable_file,
+};
+use crate::utils::process::is_running;
+use crate::utils::{get_dir_size, is_url, strip_suffix};
+use crate::{log_debug, log_info, log_warn};
+use clap::ValueEnum;
 use easy_install::{InstallConfig, ei};
 use github_proxy::{Proxy, Resource};
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Staging directories of the built-in extraction fallback.
+const CORE_STAGING_DIR: &str = ".install-core";
+const UI_STAGING_DIR: &str = ".install-ui";
+/// Geo databases are downloaded here and moved into place once complete.
+const GEO_STAGING_DIR: &str = ".install-geo";
+const STAGING_DIRS: [&str; 3] = [CORE_STAGING_DIR, UI_STAGING_DIR, GEO_STAGING_DIR];
+/// Name of the downloaded archive inside a staging directory.
+const ARCHIVE_FILE_NAME: &str = ".download";
+/// Copies of a file kept when it is replaced; `prune` keeps the newest few.
+const BACKUP_SUFFIXES: [&str; 2] = [".bak", ".old"];
+/// Leftovers of failed downloads and rejected files, always pruned.
+const LEFTOVER_SUFFIXES: [&str; 2] = [".corrupt", ".part"];
+/// Limit of the controller's `/upgrade/ui`, which downloads the dashboard
+/// before answering and so may take longer than the idle timeout.
+const UI_UPGRADE_TIMEOUT: Duration = Duration::from_secs(300);
 
 impl CrashConfig {
     /// Install the proxy core, web UI and geo databases.
     pub async fn install(&self, force: bool) -> Result<()> {
         log_info!("Installing proxy core and UI (force: {})", force);
 
-        self.ensure_default_config()?;
+        // Never replaces an existing core config, even when `force`
+        // reinstalls everything else.
+        self.ensure_default_config(false)?;
 
         self.install_core(force).await?;
         self.install_ui(force).await?;
@@ -31,23 +68,139 @@ impl CrashConfig {
         Ok(())
     }
 
-    /// Ensure the default core configuration file exists on disk.
-    fn ensure_default_config(&self) -> Result<()> {
-        let config_path = self.core_config_path();
+    /// Run `install`, aborting it on Ctrl-C: in-flight downloads are dropped,
+    /// the fallback staging directories removed and components that were not
+    /// installed before deleted, as a partial file would pass for installed.
+    pub async fn cancellable<T>(&self, install: impl Future<Output = Result<T>>) -> Result<T> {
+        let config_dir = get_config_dir();
+        let new_paths: Vec<PathBuf> = self
+            .install_paths(&config_dir)
+            .into_iter()
+            .filter(|path| !path.exists())
+            .collect();
 
-        if config_path.exists() {
+        tokio::select! {
+            result = install => result,
+            _ = tokio::signal::ctrl_c() => {
+                log_warn!("Install interrupted, removing partial files");
+                for path in STAGING_DIRS.iter().map(|name| config_dir.join(name)).chain(new_paths) {
+                    let removed = if path.is_dir() {
+                        std::fs::remove_dir_all(&path)
+                    } else {
+                        std::fs::remove_file(&path)
+                    };
+                    if removed.is_ok() {
+                        log_info!("Removed {}", path.display());
+                    }
+                }
+                Err(CrashError::Download(
+                    "Install cancelled, cleaned up partial files".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Refuse to download when the config directory's partition has less
+    /// than `min_free_bytes` free, so a router never fills the partition it
+    /// keeps its own state on. Disabled at 0.
+    pub fn check_free_space(&self) -> Result<()> {
+        if self.min_free_bytes == 0 {
             return Ok(());
         }
+        let config_dir = get_config_dir();
+        let available = fs4::available_space(&config_dir)?;
+        if available < self.min_free_bytes {
+            return Err(CrashError::Download(format!(
+                "Only {} free on {}, below the {} kept free by 'config min-free'; \
+                 run 'crash prune' or lower the threshold",
+                format_size(available),
+                config_dir.display(),
+                format_size(self.min_free_bytes)
+            )));
+        }
+        Ok(())
+    }
 
-        log_info!(
-            "Creating default configuration file: {}",
-            config_path.display()
+    /// What `crash uninstall` deletes: the whole config directory, or with
+    /// `keep_config` everything in it but the `.yaml`/`.json` configs at its
+    /// top level (`crash_config.json` among them) and the instances' configs.
+    pub fn uninstall_paths(&self, keep_config: bool) -> Result<Vec<PathBuf>> {
+        let config_dir = get_config_dir();
+        if !config_dir.exists() {
+            return Ok(Vec::new());
+        }
+        if !keep_config {
+            return Ok(vec![config_dir]);
+        }
+
+        let mut keep: Vec<PathBuf> = self
+            .instances
+            .iter()
+            .map(|(name, instance)| instance.config_path(name))
+            .collect();
+        for entry in std::fs::read_dir(&config_dir)? {
+            let path = entry?.path();
+            let is_config = matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("yaml" | "yml" | "json")
+            );
+            if is_config && path.is_file() {
+                keep.push(path);
+            }
+        }
+
+        let mut paths = Vec::new();
+        collect_removable(&config_dir, &keep, &mut paths)?;
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Where the core, UI and geo databases are installed.
+    fn install_paths(&self, config_dir: &Path) -> Vec<PathBuf> {
+        let mut paths = vec![self.core.exe_path(config_dir), self.web.ui_dir(config_dir)];
+        paths.extend(
+            self.core
+                .get_geo_files(self.geo_ipv6)
+                .into_iter()
+                .map(|name| config_dir.join(strip_suffix(name))),
         );
+        paths
+    }
+
+    /// Write the default core configuration file if there is none. With
+    /// `reset` an existing file is replaced too, after backing it up to
+    /// `<file>.bak`. Returns whether the default was written.
+    pub fn ensure_default_config(&self, reset: bool) -> Result<bool> {
+        let config_path = self.core_config_path();
+
+        if config_path.exists() {
+            if !reset {
+                return Ok(false);
+            }
+            let backup = backup_path(&config_path);
+            std::fs::copy(&config_path, &backup)?;
+            log_info!(
+                "Resetting configuration file {}, previous one saved to {}",
+                config_path.display(),
+                backup.display()
+            );
+        } else {
+            log_info!(
+                "Creating default configuration file: {}",
+                config_path.display()
+            );
+        }
 
-        let default_config = include_str!("../assets/mihomo.yaml");
-        atomic_write(&config_path, default_config)?;
+        let default_config = default_config(
+            self.core,
+            &self.web.resolved()?,
+            self.mixed_port(),
+            &self.dns,
+            self.core_ui_url().as_deref(),
+        );
+        atomic_write(&config_path, &default_config)?;
 
-        Ok(())
+        Ok(true)
     }
 
     /// Install the proxy core binary.
@@ -62,29 +215,17 @@ impl CrashConfig {
         log_info!("Installing proxy core: {}", self.core.name());
 
         ensure_dir(&get_config_dir())?;
+        self.check_free_space()?;
 
-        let resource = self.core.repo(&self.target)?;
-        let url = self
-            .proxy
-            .url(resource)
-            .ok_or_else(|| CrashError::Download("Failed to get core download URL".to_string()))?;
-
-        log_info!("Downloading core from: {}", url);
-
-        let result = ei(
-            &url,
-            &self.ei_config(
-                &get_config_dir().to_string_lossy(),
-                Some(self.core.name().to_string()),
-            ),
-        )
-        .await;
-
-        if result.is_err() {
-            return Err(CrashError::Download(
-                "Failed to install core binary".to_string(),
-            ));
-        }
+        // A custom URL is used as-is, whatever the proxy.
+        let chain = if self.core_url_override.is_some() {
+            vec![self.proxy()]
+        } else {
+            self.proxy_chain()
+        };
+        with_proxy_fallback(chain, "core", |proxy| self.download_core(proxy, &exe_path))
+            .await
+            .map_err(|e| component_error("core", e))?;
 
         if !file_exists(&exe_path) {
             return Err(CrashError::Download(format!(
@@ -93,16 +234,144 @@ impl CrashConfig {
             )));
         }
 
+        if let Some((expected, found)) = self.core_arch_mismatch() {
+            let _ = std::fs::remove_file(&exe_path);
+            return Err(CrashError::Download(format!(
+                "Downloaded core is built for {} but target {:?} needs {}; fix the target \
+                 with 'crash config target <target>' and run 'crash install core -f'",
+                found, self.target, expected
+            )));
+        }
+
+        // A custom build may not be a runnable core for this platform.
+        if self.core_url_override.is_some() {
+            let version = self.get_version().map_err(|e| {
+                CrashError::Download(format!(
+                    "Core downloaded from the custom URL does not run: {}",
+                    e
+                ))
+            })?;
+            log_info!("Custom core version: {}", version);
+        }
+
         log_info!("Core installed successfully at {}", exe_path.display());
         Ok(())
     }
 
+    /// Architecture expected for the configured target and the one the
+    /// installed core binary is built for, when both are known and differ.
+    pub fn core_arch_mismatch(&self) -> Option<(Arch, Arch)> {
+        let expected = Arch::of_target(&self.target)?;
+        let found = detect_binary_arch(&self.core.exe_path(&get_config_dir()))?;
+        (expected != found).then_some((expected, found))
+    }
+
+    /// Download and install the core binary through `proxy`, falling back to
+    /// the built-in extraction when `ei` fails.
+    async fn download_core(&self, proxy: Proxy, exe_path: &Path) -> Result<()> {
+        let url = self.core_url_via(proxy)?;
+
+        log_info!("Downloading core from: {}", url);
+
+        // `ei` can't verify a checksum, so a published one means the
+        // built-in extraction; the sidecar just came from the same host.
+        if let Some(sha256) = self.published_sha256(&url).await {
+            return self
+                .install_core_fallback(&url, exe_path, Some(&sha256))
+                .await;
+        }
+
+        let result = self
+            .in_download_slot(ei(
+                &url,
+                &InstallConfig {
+                    proxy,
+                    ..self.ei_config(
+                        &get_config_dir().to_string_lossy(),
+                        Some(self.core.name().to_string()),
+                    )
+                },
+            ))
+            .await?;
+
+        if let Err(e) = result {
+            log_warn!(
+                "ei failed to install core: {}; trying built-in extraction",
+                e
+            );
+            self.install_core_fallback(&url, exe_path, None)
+                .await
+                .map_err(|fallback| {
+                    CrashError::Download(format!(
+                        "Failed to install core binary: {} (fallback: {})",
+                        e, fallback
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Re-download the core binary, stopping the core first when it is
+    /// running (a running executable cannot be replaced on Windows). The
+    /// caller restarts it. Returns the versions before and after.
+    pub async fn update_core(&mut self) -> Result<(Option<String>, String)> {
+        let old_version = self.get_version().ok();
+
+        if is_running(&self.core.exe_name()) {
+            self.stop(false)?;
+            self.wait_stopped(RESTART_STOP_TIMEOUT).await?;
+        }
+        self.install_core(true).await?;
+
+        Ok((old_version, self.get_version()?))
+    }
+
+    /// Resolved download URL of the core archive for the configured target,
+    /// version and proxy, or `core_url_override` when set.
+    pub fn core_url(&self) -> Result<String> {
+        self.core_url_via(self.proxy())
+    }
+
+    /// Download URL of the core archive through `proxy`.
+    fn core_url_via(&self, proxy: Proxy) -> Result<String> {
+        if let Some(url) = &self.core_url_override {
+            return Ok(url.clone());
+        }
+
+        let resource =
+            self.core
+                .repo(&self.target, &self.assets(), self.core_version.as_deref())?;
+        proxy
+            .url(resource)
+            .ok_or_else(|| CrashError::Download("Failed to get core download URL".to_string()))
+    }
+
+    /// Resolved download URL of the UI archive for the configured proxy, or
+    /// `ui_url_override` when set.
+    pub fn ui_url(&self) -> Result<String> {
+        self.web.ui_url_via(&self.assets(), self.proxy())
+    }
+
+    /// Resolved download URLs of the geo databases, as `(file name, url)`.
+    pub fn geo_urls(&self) -> Vec<(&'static str, String)> {
+        self.core
+            .get_geo_files(self.geo_ipv6)
+            .into_iter()
+            .filter_map(|name| {
+                Some((
+                    name,
+                    geo_url(name, self.geo_source, &self.assets(), self.proxy())?,
+                ))
+            })
+            .collect()
+    }
+
     /// Build an `easy_install` config derived from this crash config.
     pub fn ei_config(&self, dir: &str, alias: Option<String>) -> InstallConfig {
         easy_install::InstallConfig {
             dir: Some(dir.to_string()),
             install_only: true,
-            proxy: self.proxy,
+            proxy: self.proxy(),
             alias,
             target: Some(self.target),
             ..Default::default()
@@ -119,51 +388,240 @@ impl CrashConfig {
             return Ok(());
         }
 
+        if self.core_ui_url().is_some() {
+            log_info!("{} manages the web UI itself", self.core);
+            return self.request_core_ui_update().await;
+        }
+
         log_info!("Installing web UI: {}", self.web.ui_name());
+        self.check_free_space()?;
 
-        let url = self.web.ui_url()?;
+        // Fail early for a custom UI without a URL.
+        self.web.ui_url(&self.assets())?;
 
-        log_info!("Downloading UI from: {}", url);
+        let chain = if self.web.ui_url_override.is_some() {
+            vec![self.proxy()]
+        } else {
+            self.proxy_chain()
+        };
+        with_proxy_fallback(chain, "UI", |proxy| self.download_ui(proxy, &ui_dir))
+            .await
+            .map_err(|e| component_error("UI", e))?;
 
-        let result = ei(
-            &url,
-            &self.ei_config(
-                &config_dir.to_string_lossy(),
-                Some(self.web.ui_name().to_string()),
-            ),
-        )
-        .await;
+        check_ui_dir(&ui_dir)?;
+
+        log_info!("UI installed successfully at {}", ui_dir.display());
+        Ok(())
+    }
 
-        if result.is_err() {
-            return Err(CrashError::Download("Failed to install UI".to_string()));
+    /// URL the core downloads the dashboard from when it manages the UI
+    /// itself (`config ui --core-managed`), or `None` when crash installs it.
+    pub fn core_ui_url(&self) -> Option<String> {
+        if !self.web.core_managed || !self.core.downloads_ui() {
+            return None;
         }
+        self.web.ui_url(&self.assets()).ok()
+    }
 
-        if !ui_dir.exists() {
-            return Err(CrashError::Download(format!(
-                "UI directory not found after installation: {}",
-                ui_dir.display()
-            )));
+    /// Ask a running Mihomo to download the dashboard now through its
+    /// `/upgrade/ui` API. Singbox fetches a missing dashboard when it
+    /// starts, and a stopped core can't be asked.
+    async fn request_core_ui_update(&self) -> Result<()> {
+        if self.core != Core::Mihomo || !is_running(&self.core.exe_name()) {
+            log_info!("{} is not running, not requesting a UI update", self.core);
+            return Ok(());
         }
 
-        log_info!("UI installed successfully at {}", ui_dir.display());
+        let response = self
+            .controller_request(reqwest::Method::POST, "/upgrade/ui")?
+            // The core downloads the dashboard before it answers.
+            .timeout(UI_UPGRADE_TIMEOUT)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(component_error(
+                "UI",
+                CrashError::Download(format!(
+                    "{} answered the UI update with {}",
+                    self.core,
+                    response.status()
+                )),
+            ));
+        }
+        log_info!("{} updated the web UI", self.core);
         Ok(())
     }
 
+    /// Download and install the UI assets through `proxy`, falling back to
+    /// the built-in extraction when `ei` fails.
+    async fn download_ui(&self, proxy: Proxy, ui_dir: &Path) -> Result<()> {
+        let url = &self.web.ui_url_via(&self.assets(), proxy)?;
+
+        log_info!("Downloading UI from: {}", url);
+
+        if let Some(sha256) = self.published_sha256(url).await {
+            return self.install_ui_fallback(url, ui_dir, Some(&sha256)).await;
+        }
+
+        let result = self
+            .in_download_slot(ei(
+                url,
+                &InstallConfig {
+                    proxy,
+                    ..self.ei_config(
+                        &get_config_dir().to_string_lossy(),
+                        Some(self.web.ui_name().to_string()),
+                    )
+                },
+            ))
+            .await?;
+
+        if let Err(e) = result {
+            log_warn!("ei failed to install UI: {}; trying built-in extraction", e);
+            self.install_ui_fallback(url, ui_dir, None)
+                .await
+                .map_err(|fallback| {
+                    CrashError::Download(format!(
+                        "Failed to install UI: {} (fallback: {})",
+                        e, fallback
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Run `install`, an `ei` call, once a download slot is free. `ei`
+    /// downloads with its own client, so `max_concurrent_downloads` is the
+    /// only download limit that applies to it.
+    async fn in_download_slot<T>(&self, install: impl Future<Output = T>) -> Result<T> {
+        let _permit = acquire_permit(&self.download_options()).await?;
+        Ok(install.await)
+    }
+
+    /// SHA256 published next to `url` in a `.sha256` sidecar, as the
+    /// crash-assets releases can ship. `None` when there is none, so the
+    /// download stays unverified; a custom URL is never checked.
+    async fn published_sha256(&self, url: &str) -> Option<String> {
+        if self.core_url_override.as_deref() == Some(url)
+            || self.web.ui_url_override.as_deref() == Some(url)
+        {
+            return None;
+        }
+        let sidecar = format!("{}.sha256", url);
+        // Most assets have no sidecar; don't retry the 404.
+        let options = DownloadOptions {
+            retry: RetryConfig {
+                max_retries: 0,
+                ..self.retry
+            },
+            ..self.download_options()
+        };
+        let text = match download_text(&sidecar, &options).await {
+            Ok(text) => text,
+            Err(e) => {
+                log_debug!("No checksum for {}: {}", url, e);
+                return None;
+            }
+        };
+        let sha256 = parse_sha256_sidecar(&text);
+        if sha256.is_none() {
+            log_warn!("Ignoring malformed checksum file {}", sidecar);
+        }
+        sha256
+    }
+
+    /// Download `url` with crash's own client and extract it into a fresh
+    /// staging directory `name` under the config directory. With `sha256`
+    /// the download must match it before anything is extracted.
+    async fn fetch_and_extract(
+        &self,
+        url: &str,
+        name: &str,
+        sha256: Option<&str>,
+    ) -> Result<PathBuf> {
+        let staging = get_config_dir().join(name);
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+        ensure_dir(&staging)?;
+
+        // Downloaded inside the staging directory, so an interrupted install
+        // leaves nothing else behind.
+        let archive = staging.join(ARCHIVE_FILE_NAME);
+        download_file_checked(url, &archive, &self.download_options(), sha256).await?;
+        let bytes = std::fs::read(&archive)?;
+        std::fs::remove_file(&archive)?;
+        let file_name = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .unwrap_or_default();
+        extract_archive(&bytes, file_name, &staging)?;
+        Ok(staging)
+    }
+
+    /// Install the core binary without `ei`: the largest extracted file is
+    /// taken as the executable.
+    async fn install_core_fallback(
+        &self,
+        url: &str,
+        exe_path: &Path,
+        sha256: Option<&str>,
+    ) -> Result<()> {
+        let staging = self
+            .fetch_and_extract(url, CORE_STAGING_DIR, sha256)
+            .await?;
+        let result = largest_file(&staging).and_then(|binary| {
+            let binary = binary.ok_or_else(|| {
+                CrashError::Download("Downloaded archive contains no files".to_string())
+            })?;
+            if !is_executable_file(&binary) {
+                return Err(CrashError::Download(format!(
+                    "Download from {} is not an executable (an error page from the mirror?)",
+                    url
+                )));
+            }
+            atomic_write_bytes(exe_path, &std::fs::read(&binary)?)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(exe_path, std::fs::Permissions::from_mode(0o755))?;
+            }
+            Ok(())
+        });
+        let _ = std::fs::remove_dir_all(&staging);
+        result
+    }
+
+    /// Install the UI assets without `ei`, replacing any existing `ui_dir`.
+    async fn install_ui_fallback(
+        &self,
+        url: &str,
+        ui_dir: &Path,
+        sha256: Option<&str>,
+    ) -> Result<()> {
+        let staging = self.fetch_and_extract(url, UI_STAGING_DIR, sha256).await?;
+        let result = content_root(&staging).and_then(|root| {
+            if ui_dir.exists() {
+                std::fs::remove_dir_all(ui_dir)?;
+            }
+            std::fs::rename(root, ui_dir)?;
+            Ok(())
+        });
+        let _ = std::fs::remove_dir_all(&staging);
+        result
+    }
+
     /// Install GeoIP / geosite databases for the active core.
     pub async fn install_geo(&self, force: bool) -> Result<()> {
         log_info!("Installing GeoIP databases");
 
-        for name in self.core.get_geo_files() {
-            let Some(url) = Resource::File {
-                owner: "ahaoboy".to_string(),
-                repo: "crash-assets".to_string(),
-                reference: "main".to_string(),
-                path: name.to_string(),
-            }
-            .url(&Proxy::Github) else {
+        let mut failed = None;
+        for name in self.core.get_geo_files(self.geo_ipv6) {
+            if geo_url(name, self.geo_source, &self.assets(), Proxy::Github).is_none() {
                 log_info!("Database {} not found.", name);
                 continue;
-            };
+            }
 
             let db_path = get_config_dir().join(strip_suffix(name));
 
@@ -173,26 +631,176 @@ impl CrashConfig {
             }
 
             log_info!("Downloading GeoIP database: {}", name);
+            self.check_free_space()?;
 
-            if ei(
-                &url,
-                &self.ei_config(&get_config_dir().to_string_lossy(), None),
-            )
-            .await
-            .is_ok()
-            {
-                log_info!("Downloaded {} successfully", name);
-            } else {
-                log_info!("Downloaded {} error", name);
+            // The core must never load a half-written database, so it is
+            // downloaded aside and moved over the old one when complete.
+            let staging = get_config_dir().join(GEO_STAGING_DIR);
+            ensure_dir(&staging)?;
+            let staged = &staging.join(strip_suffix(name));
+            let db_path = &db_path;
+            let staging = &staging.to_string_lossy();
+            let download = with_proxy_fallback(self.proxy_chain(), name, |proxy| async move {
+                let url =
+                    &geo_url(name, self.geo_source, &self.assets(), proxy).ok_or_else(|| {
+                        CrashError::Download(format!("{} is not available through {}", name, proxy))
+                    })?;
+                log_debug!("Downloading {} from {}", name, url);
+                let expected = if let Some(sha256) = self.published_sha256(url).await {
+                    // Checked against the SHA256 before extraction.
+                    self.fetch_and_extract(url, GEO_STAGING_DIR, Some(&sha256))
+                        .await?;
+                    None
+                } else {
+                    // `ei` doesn't tell whether it got the whole body, so a
+                    // database served uncompressed must have the announced size.
+                    let expected = if url.rsplit('/').next() == Some(strip_suffix(name)) {
+                        content_length(url, &self.download_options()).await
+                    } else {
+                        None
+                    };
+                    self.in_download_slot(ei(
+                        url,
+                        &InstallConfig {
+                            proxy,
+                            ..self.ei_config(staging, None)
+                        },
+                    ))
+                    .await?
+                    .map_err(|e| CrashError::Download(e.to_string()))?;
+                    expected
+                };
+                promote_staged(staged, db_path, expected)
+            });
+            // Try the remaining databases before reporting the first failure.
+            match download.await {
+                Ok(_) => log_info!("Downloaded {} successfully", name),
+                Err(e) => {
+                    log_warn!("Failed to download {}: {}", name, e);
+                    failed.get_or_insert(component_error(&format!("geo database {}", name), e));
+                }
             }
         }
 
+        let _ = std::fs::remove_dir_all(get_config_dir().join(GEO_STAGING_DIR));
+        if let Some(e) = failed {
+            return Err(e);
+        }
         log_info!("GeoIP databases installed successfully");
         Ok(())
     }
 
+    /// Geo files in the config directory with their size and whether the
+    /// active core may use them. Instances keep theirs in their own
+    /// directory, so only the active core counts.
+    pub fn geo_files(&self) -> Result<Vec<(PathBuf, u64, bool)>> {
+        let known = Core::known_geo_files();
+        let used = self.core.installed_geo_files(true);
+        let matches =
+            |names: &[&str], name: &str| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(get_config_dir())? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let metadata = entry.metadata()?;
+            if metadata.is_file() && matches(&known, &name) {
+                files.push((entry.path(), metadata.len(), matches(&used, &name)));
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Files and directories `crash prune` removes, with their size and
+    /// modification time: backups beyond the `keep` newest of each file,
+    /// download leftovers, staging directories, and the binaries and UIs of
+    /// cores and dashboards that are no longer configured. With
+    /// `older_than`, only what was last modified before then is removed, all
+    /// backups regardless of `keep`, and rotated log files too. The active
+    /// core, UI and geo files are never included, nor a core that is running.
+    pub fn prunable(
+        &self,
+        keep: usize,
+        older_than: Option<Duration>,
+    ) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let config_dir = get_config_dir();
+        let keep = if older_than.is_some() { 0 } else { keep };
+        let mut paths = Vec::new();
+        let mut backups: BTreeMap<String, Vec<(SystemTime, PathBuf)>> = BTreeMap::new();
+        for entry in std::fs::read_dir(&config_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            if let Some(base) = BACKUP_SUFFIXES.iter().find_map(|s| name.strip_suffix(s)) {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                backups
+                    .entry(base.to_string())
+                    .or_default()
+                    .push((modified, entry.path()));
+            } else if LEFTOVER_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+                paths.push(entry.path());
+            }
+        }
+        for mut copies in backups.into_values() {
+            copies.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+            paths.extend(copies.into_iter().skip(keep).map(|(_, path)| path));
+        }
+
+        paths.extend(STAGING_DIRS.iter().map(|name| config_dir.join(name)));
+        // Instances copy their core from here, so theirs stay too.
+        let cores_in_use: Vec<Core> = std::iter::once(self.core)
+            .chain(self.instances.values().map(|instance| instance.core))
+            .collect();
+        paths.extend(
+            Core::ALL
+                .iter()
+                .filter(|core| !cores_in_use.contains(core) && !is_running(&core.exe_name()))
+                .map(|core| core.exe_path(&config_dir)),
+        );
+        paths.extend(
+            UiType::BUILTIN
+                .iter()
+                .filter(|ui| **ui != self.web.ui)
+                .map(|ui| config_dir.join(ui.name())),
+        );
+        if older_than.is_some() {
+            // Every log file but the last is a rotated one.
+            let mut logs = log_files(&self.log_dir());
+            logs.pop();
+            paths.extend(logs);
+            paths.extend(log_archives(&self.log_dir()));
+        }
+
+        let cutoff = older_than.map(|age| SystemTime::now() - age);
+        let active = self.install_paths(&config_dir);
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = paths
+            .into_iter()
+            .filter(|path| !active.contains(path))
+            .filter_map(|path| {
+                let metadata = std::fs::metadata(&path).ok()?;
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                if cutoff.is_some_and(|cutoff| modified > cutoff) {
+                    return None;
+                }
+                let size = if metadata.is_dir() {
+                    get_dir_size(&path)
+                } else {
+                    metadata.len()
+                };
+                Some((path, size, modified))
+            })
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
     /// Upgrade the `crash` (or `crash-assets`) binary in place.
     pub async fn upgrade(&self, repo: UpgradeRepo) -> Result<()> {
+        self.check_free_space()?;
         let exe = std::env::current_exe()?;
         let dir = exe
             .parent()
@@ -202,22 +810,33 @@ impl CrashConfig {
             UpgradeRepo::Crash => "ahaoboy/crash",
             UpgradeRepo::CrashAssets => "ahaoboy/crash-assets",
         };
-        ei(
-            url,
-            &InstallConfig {
-                name: vec!["crash".to_string()],
-                upx: repo == UpgradeRepo::Crash,
-                ..self.ei_config(dir, Some("crash".to_string()))
-            },
-        )
-        .await
-        .map_err(|e| CrashError::Download(e.to_string()))?;
+        with_proxy_fallback(self.proxy_chain(), url, |proxy| async move {
+            self.in_download_slot(ei(
+                url,
+                &InstallConfig {
+                    name: vec!["crash".to_string()],
+                    upx: repo == UpgradeRepo::Crash,
+                    proxy,
+                    ..self.ei_config(dir, Some("crash".to_string()))
+                },
+            ))
+            .await?
+            .map_err(|e| CrashError::Download(e.to_string()))
+        })
+        .await?;
 
         Ok(())
     }
 
-    /// Update the core configuration file from the configured URL or local path.
-    pub async fn update_config(&self, force: bool) -> Result<()> {
+    /// Update the core configuration file from the configured URL or local
+    /// path. With `validate`, the installed core checks the new file and the
+    /// previous one is put back if it is rejected.
+    pub async fn update_config(
+        &self,
+        force: bool,
+        format: Option<ConfigFormat>,
+        validate: bool,
+    ) -> Result<()> {
         let dest = &self.core_config_path();
         let source = &self.url;
 
@@ -234,12 +853,21 @@ impl CrashConfig {
         }
 
         log_info!("Updating configuration from: {}", source);
+        self.check_free_space()?;
 
         let content = if is_url(source) {
-            log_info!("Downloading configuration from URL: {}", source);
-            download_text(source).await.map_err(|e| {
-                CrashError::Config(format!("Failed to download configuration from URL: {}", e))
-            })?
+            // A config hosted on GitHub goes through the mirror too.
+            let url = &match self.proxy() {
+                Proxy::Github => None,
+                proxy => github_resource(source).and_then(|resource| resource.url(&proxy)),
+            }
+            .unwrap_or_else(|| source.to_string());
+            log_info!("Downloading configuration from URL: {}", url);
+            download_text(url, &self.download_options())
+                .await
+                .map_err(|e| {
+                    CrashError::Config(format!("Failed to download configuration from URL: {}", e))
+                })?
         } else {
             let source_path = Path::new(source);
             if !source_path.exists() {
@@ -250,12 +878,31 @@ impl CrashConfig {
             }
 
             log_info!("Reading configuration from local file: {}", source);
-            std::fs::read_to_string(source_path).map_err(|e| {
+            let bytes = std::fs::read(source_path).map_err(|e| {
                 CrashError::Config(format!("Failed to read local configuration file: {}", e))
-            })?
+            })?;
+            decode_subscription_body(&bytes)?
         };
 
-        let patched_content = patch_config(self.core, &self.web, &content);
+        // Checked before anything is written, so a dead subscription or a
+        // mismatch keeps the current working config.
+        check_config_body(&content)?;
+        let content = ensure_config_format(self.core, &content, format)?;
+        let patched_content = patch_config(
+            self.core,
+            &self.web.resolved()?,
+            self.mixed_port(),
+            &self.dns,
+            self.geo_ipv6,
+            self.core_ui_url().as_deref(),
+            &content,
+        );
+
+        let backup = backup_path(dest);
+        let has_backup = file_exists(dest);
+        if has_backup {
+            std::fs::copy(dest, &backup)?;
+        }
 
         std::fs::write(dest, patched_content).map_err(|e| {
             CrashError::Config(format!(
@@ -265,7 +912,449 @@ impl CrashConfig {
             ))
         })?;
 
+        if validate {
+            self.validate_updated_config(dest, has_backup.then_some(backup.as_path()))?;
+        }
+
         log_info!("Configuration updated successfully");
         Ok(())
     }
+
+    /// Check the config just written to `dest` with the core that will run
+    /// it. If the core rejects it, `backup` is restored (or the default
+    /// config written when there was none) and the core's output returned.
+    fn validate_updated_config(&self, dest: &Path, backup: Option<&Path>) -> Result<()> {
+        if !file_exists(&self.core.exe_path(&get_config_dir())) {
+            log_info!("{} is not installed, skipping config validation", self.core);
+            return Ok(());
+        }
+
+        let output = match self.check_config_file(dest) {
+            Ok((true, _)) => {
+                log_info!("{} accepted the new configuration", self.core);
+                return Ok(());
+            }
+            Ok((false, output)) => output,
+            Err(e) => {
+                log_warn!("Could not run the {} config check: {}", self.core, e);
+                return Ok(());
+            }
+        };
+
+        let restored = match backup {
+            Some(backup) => {
+                std::fs::copy(backup, dest)?;
+                "the previous config was restored"
+            }
+            None => {
+                let config = default_config(
+                    self.core,
+                    &self.web.resolved()?,
+                    self.mixed_port(),
+                    &self.dns,
+                    self.core_ui_url().as_deref(),
+                );
+                atomic_write(dest, &config)?;
+                "the default config was written instead"
+            }
+        };
+        Err(CrashError::Config(format!(
+            "{} rejected the downloaded configuration, {} (skip the check with --no-validate):\n{}",
+            self.core,
+            restored,
+            output.trim()
+        )))
+    }
+}
+
+/// Add what under `dir` is not in `keep` to `paths`; directories without
+/// anything to keep are added whole.
+fn collect_removable(dir: &Path, keep: &[PathBuf], paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if keep.contains(&path) {
+            continue;
+        }
+        if path.is_dir() && keep.iter().any(|kept| kept.starts_with(&path)) {
+            collect_removable(&path, keep, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Copy of the previous core config kept by `update_config`.
+fn backup_path(config_path: &Path) -> PathBuf {
+    let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    config_path.with_file_name(name)
+}
+
+/// Move the completely downloaded file `staged` over `dest`. An empty or
+/// missing file, or one shorter or longer than the `expected` size when it
+/// is known, means the download didn't finish, so `dest` is kept.
+fn promote_staged(staged: &Path, dest: &Path, expected: Option<u64>) -> Result<()> {
+    let size = std::fs::metadata(staged).map(|m| m.len()).unwrap_or(0);
+    if size == 0 || expected.is_some_and(|expected| size != expected) {
+        let _ = std::fs::remove_file(staged);
+        return Err(CrashError::Download(format!(
+            "Incomplete download of {} ({} of {} bytes), keeping the existing file",
+            dest.display(),
+            size,
+            expected.map_or("unknown".to_string(), |expected| expected.to_string())
+        )));
+    }
+    std::fs::rename(staged, dest)?;
+    Ok(())
+}
+
+/// Check that an install left a usable dashboard in `ui_dir`. One without
+/// `index.html` is removed, so the next install doesn't take it as done.
+fn check_ui_dir(ui_dir: &Path) -> Result<()> {
+    if !ui_dir.exists() {
+        return Err(CrashError::Download(format!(
+            "UI directory not found after installation: {}",
+            ui_dir.display()
+        )));
+    }
+
+    if !file_exists(&ui_dir.join("index.html")) {
+        let _ = std::fs::remove_dir_all(ui_dir);
+        return Err(CrashError::Download(format!(
+            "index.html not found in UI directory: {}",
+            ui_dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// The GitHub release asset or raw repository file `url` points to, so it
+/// can be fetched through a mirror; `None` for any other URL, or one with
+/// a query (e.g. a private repository's token) a mirror would drop.
+fn github_resource(url: &str) -> Option<Resource> {
+    if url.contains(['?', '#']) {
+        return None;
+    }
+    if let Some(rest) = url.strip_prefix("https://raw.githubusercontent.com/") {
+        let mut parts = rest.splitn(4, '/');
+        let (owner, repo, reference, path) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        return Some(Resource::File {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            reference: reference.to_string(),
+            path: path.to_string(),
+        });
+    }
+    let rest = url.strip_prefix("https://github.com/")?;
+    let parts: Vec<&str> = rest.split('/').collect();
+    match parts[..] {
+        [owner, repo, "releases", "download", tag, name] => Some(Resource::Release {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            tag: tag.to_string(),
+            name: name.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Name the failed install step and why it failed in `e`.
+fn component_error(component: &str, e: CrashError) -> CrashError {
+    let message = match e {
+        CrashError::Download(message) => message,
+        e => e.to_string(),
+    };
+    CrashError::Download(format!(
+        "Installing {}: {}: {}",
+        component,
+        FailureKind::of(&message),
+        message
+    ))
+}
+
+/// Run `attempt` through each proxy of `chain` in turn until one succeeds,
+/// returning the last error when all of them fail.
+async fn with_proxy_fallback<T, F, Fut>(chain: Vec<Proxy>, what: &str, mut attempt: F) -> Result<T>
+where
+    F: FnMut(Proxy) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut last_error = None;
+    for proxy in chain {
+        match attempt(proxy).await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                log_warn!("Downloading {} through {} failed: {}", what, proxy, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| CrashError::Download(format!("No proxy to download {} through", what))))
+}
+
+/// Download URL of the geo file `name` from `source` through `proxy`, or
+/// from the first other source publishing it (the assets repository, then
+/// meta-rules-dat).
+fn geo_url(name: &str, source: GeoSource, assets: &AssetsRepo, proxy: Proxy) -> Option<String> {
+    source.url_via(name, assets, proxy).or_else(|| {
+        GeoSource::value_variants()
+            .iter()
+            .find_map(|other| other.url_via(name, assets, proxy))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::instance::InstanceConfig;
+    use crate::config::{get_config_path, override_config_dir};
+
+    #[test]
+    fn interrupted_geo_download_keeps_the_old_database() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("geoip.metadb");
+        let staged = tmp.path().join(GEO_STAGING_DIR).join("geoip.metadb");
+        std::fs::create_dir_all(staged.parent().unwrap()).unwrap();
+        std::fs::write(&dest, b"old").unwrap();
+
+        // Cut off before any data arrived.
+        std::fs::write(&staged, b"").unwrap();
+        assert!(promote_staged(&staged, &dest, None).is_err());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"old");
+        assert!(promote_staged(&staged, &dest, None).is_err());
+
+        // Cut off half way through a body announced as 6 bytes.
+        std::fs::write(&staged, b"new").unwrap();
+        assert!(promote_staged(&staged, &dest, Some(6)).is_err());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"old");
+        assert!(!staged.exists());
+
+        std::fs::write(&staged, b"new").unwrap();
+        promote_staged(&staged, &dest, Some(3)).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"new");
+        assert!(!staged.exists());
+    }
+
+    #[test]
+    fn github_config_urls_are_mirrorable() {
+        match github_resource("https://raw.githubusercontent.com/me/subs/main/clash/config.yaml") {
+            Some(Resource::File {
+                owner,
+                reference,
+                path,
+                ..
+            }) => assert_eq!(
+                (&*owner, &*reference, &*path),
+                ("me", "main", "clash/config.yaml")
+            ),
+            _ => panic!("not a raw file"),
+        }
+        match github_resource("https://github.com/me/subs/releases/download/v1/config.yaml") {
+            Some(Resource::Release { tag, name, .. }) => {
+                assert_eq!((&*tag, &*name), ("v1", "config.yaml"))
+            }
+            _ => panic!("not a release asset"),
+        }
+        assert!(github_resource("https://sub.example.com/link?token=1").is_none());
+        assert!(
+            github_resource("https://raw.githubusercontent.com/me/subs/main/c.yaml?token=1")
+                .is_none()
+        );
+        assert!(github_resource("https://github.com/me/subs/blob/main/config.yaml").is_none());
+    }
+
+    #[test]
+    fn ui_dir_without_index_is_removed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ui_dir = tmp.path().join("metacubexd");
+        assert!(check_ui_dir(&ui_dir).is_err());
+
+        std::fs::create_dir_all(ui_dir.join("assets")).unwrap();
+        assert!(check_ui_dir(&ui_dir).is_err());
+        assert!(!ui_dir.exists());
+
+        std::fs::create_dir_all(&ui_dir).unwrap();
+        std::fs::write(ui_dir.join("index.html"), "<html>").unwrap();
+        check_ui_dir(&ui_dir).unwrap();
+        assert!(ui_dir.exists());
+    }
+
+    #[test]
+    fn uninstall_keep_config_only_keeps_config_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        let mut config = CrashConfig {
+            core: Core::Mihomo,
+            ..Default::default()
+        };
+        config.instances.insert(
+            "work".to_string(),
+            InstanceConfig {
+                core: Core::Singbox,
+                web_host: ":9091".to_string(),
+                mixed_port: 7891,
+                start_time: 0,
+            },
+        );
+        let instance = &config.instances["work"];
+        let ui_dir = config.web.ui_dir(tmp.path());
+        let kept = [
+            get_config_path(),
+            tmp.path().join("mihomo.yaml"),
+            instance.config_path("work"),
+        ];
+        let removed = [
+            config.core.exe_path(tmp.path()),
+            tmp.path().join("mihomo.yaml.bak"),
+            tmp.path().join("geoip.metadb"),
+            instance.exe_path("work"),
+        ];
+        std::fs::create_dir_all(ui_dir.join("assets")).unwrap();
+        std::fs::write(ui_dir.join("manifest.json"), "{}").unwrap();
+        std::fs::create_dir_all(InstanceConfig::dir("work")).unwrap();
+        for path in kept.iter().chain(&removed) {
+            std::fs::write(path, "x").unwrap();
+        }
+
+        let mut expected = removed.to_vec();
+        expected.push(ui_dir);
+        expected.sort();
+        assert_eq!(config.uninstall_paths(true).unwrap(), expected);
+        assert_eq!(
+            config.uninstall_paths(false).unwrap(),
+            [tmp.path().to_path_buf()]
+        );
+    }
+
+    #[test]
+    fn prune_keeps_the_active_core_ui_and_newest_backup() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        let config = CrashConfig {
+            core: Core::Mihomo,
+            ..Default::default()
+        };
+        let other_ui = UiType::BUILTIN
+            .into_iter()
+            .find(|ui| *ui != config.web.ui)
+            .unwrap();
+        for dir in [
+            config.web.ui_dir(tmp.path()),
+            tmp.path().join(other_ui.name()),
+        ] {
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("index.html"), b"ui").unwrap();
+        }
+        let core_config = config.core_config_path();
+        for (suffix, age) in [(".bak", 60), (".old", 0)] {
+            let backup = tmp.path().join(format!("mihomo.yaml{}", suffix));
+            std::fs::File::create(&backup)
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(age))
+                .unwrap();
+        }
+        for path in [
+            Core::Mihomo.exe_path(tmp.path()),
+            Core::Singbox.exe_path(tmp.path()),
+            core_config,
+            tmp.path().join("geoip.metadb.part"),
+        ] {
+            std::fs::write(path, b"data").unwrap();
+        }
+
+        let paths: Vec<PathBuf> = config
+            .prunable(1, None)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _, _)| path)
+            .collect();
+        let mut expected = vec![
+            tmp.path().join("geoip.metadb.part"),
+            tmp.path().join("mihomo.yaml.bak"),
+            tmp.path().join(other_ui.name()),
+            Core::Singbox.exe_path(tmp.path()),
+        ];
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        assert_eq!(config.prunable(2, None).unwrap().len(), 3);
+        // Only the backup made a minute ago is old enough, and by age
+        // even the newest backup goes.
+        let old: Vec<PathBuf> = config
+            .prunable(1, Some(Duration::from_secs(30)))
+            .unwrap()
+            .into_iter()
+            .map(|(path, _, _)| path)
+            .collect();
+        assert_eq!(old, [tmp.path().join("mihomo.yaml.bak")]);
+    }
+
+    #[tokio::test]
+    async fn dead_subscription_keeps_the_current_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        let source = tmp.path().join("subscription.yaml");
+        std::fs::write(&source, "<html><body>Subscription expired</body></html>").unwrap();
+        let config = CrashConfig {
+            url: source.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        std::fs::write(config.core_config_path(), "working: config\n").unwrap();
+
+        assert!(config.update_config(true, None, false).await.is_err());
+        assert_eq!(
+            std::fs::read_to_string(config.core_config_path()).unwrap(),
+            "working: config\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn low_disk_space_refuses_downloads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        let source = tmp.path().join("subscription.yaml");
+        std::fs::write(&source, "mixed-port: 7890\n".repeat(8)).unwrap();
+        let mut config = CrashConfig {
+            url: source.to_string_lossy().to_string(),
+            min_free_bytes: u64::MAX,
+            ..Default::default()
+        };
+
+        let err = config.update_config(true, None, false).await.unwrap_err();
+        assert!(err.to_string().contains("config min-free"), "{}", err);
+        assert!(!config.core_config_path().exists());
+
+        config.min_free_bytes = 0;
+        config.update_config(true, None, false).await.unwrap();
+        assert!(config.core_config_path().exists());
+    }
+
+    #[test]
+    fn default_config_only_replaces_an_edited_one_on_reset() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        let config = CrashConfig::default();
+        let path = config.core_config_path();
+
+        assert!(config.ensure_default_config(false).unwrap());
+        let default = std::fs::read_to_string(&path).unwrap();
+
+        // What `install --force` does: the edited config stays.
+        std::fs::write(&path, "mixed-port: 1234\n").unwrap();
+        assert!(!config.ensure_default_config(false).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "mixed-port: 1234\n"
+        );
+
+        assert!(config.ensure_default_config(true).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), default);
+        assert_eq!(
+            std::fs::read_to_string(backup_path(&path)).unwrap(),
+            "mixed-port: 1234\n"
+        );
+    }
 }
@@ -0,0 +1,290 @@
+// Named core instances running next to the default core.
+//
+// The top-level `CrashConfig` fields remain the `default` instance, so
+// single-core setups are unaffected. Every named instance lives in
+// `instances/<name>/` with its own config file and its own copy of the core
+// executable (`<core>-<name>`): processes are looked up by executable name,
+// so the copy keeps instances apart from the default core and each other.
+
+use super::core::Core;
+use super::patcher::{default_config, patch_config};
+use super::tun::{check_tun_device, privilege_warnings};
+use super::{CrashConfig, WebConfig, get_config_dir};
+use crate::error::{CrashError, Result};
+use crate::utils::current_timestamp;
+use crate::utils::fs::ensure_dir;
+use crate::utils::path::exe_extension;
+use crate::utils::process::{get_pid, kill_pid, start};
+use crate::{log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Name that refers to the top-level (single-core) configuration.
+pub const DEFAULT_INSTANCE: &str = "default";
+
+const INSTANCES_DIR: &str = "instances";
+
+/// Longest instance name; keeps `<core>-<name>` a usable process name.
+const MAX_NAME_LEN: usize = 16;
+
+/// A named core instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceConfig {
+    pub core: Core,
+    /// Controller address passed to the core (e.g. `:9091`).
+    pub web_host: String,
+    /// Mixed proxy port written into the instance's config.
+    pub mixed_port: u16,
+    #[serde(default)]
+    pub start_time: u64,
+}
+
+impl InstanceConfig {
+    /// Directory holding the instance's config file and executable copy.
+    pub fn dir(name: &str) -> PathBuf {
+        get_config_dir().join(INSTANCES_DIR).join(name)
+    }
+
+    /// Executable name of the instance's core copy.
+    pub fn exe_name(&self, name: &str) -> String {
+        format!("{}-{}{}", self.core.name(), name, exe_extension())
+    }
+
+    pub fn exe_path(&self, name: &str) -> PathBuf {
+        Self::dir(name).join(self.exe_name(name))
+    }
+
+    /// The instance's own core config file.
+    pub fn config_path(&self, name: &str) -> PathBuf {
+        Self::dir(name).join(self.core.config_file_name())
+    }
+
+    /// Features of the instance's config that won't work with the current
+    /// privileges, see `tun::privilege_warnings`.
+    pub fn privilege_warnings(&self, name: &str) -> Vec<String> {
+        let content = std::fs::read_to_string(self.config_path(name)).unwrap_or_default();
+        privilege_warnings(self.core, &self.exe_path(name), &content)
+    }
+
+    /// PID of the instance's core, if it is running.
+    pub fn pid(&self, name: &str) -> Option<u32> {
+        get_pid(&self.exe_name(name)).ok()
+    }
+
+    /// Command-line arguments the instance's core is spawned with. The
+    /// instance directory is the core's home, so caches are not shared.
+    fn args(&self, name: &str) -> Vec<String> {
+        let config = self.config_path(name).to_string_lossy().to_string();
+        let home = Self::dir(name).to_string_lossy().to_string();
+        match self.core {
+            Core::Mihomo | Core::Clash => vec![
+                "-f".to_string(),
+                config,
+                "-ext-ctl".to_string(),
+                self.web_host.clone(),
+                "-d".to_string(),
+                home,
+            ],
+            Core::Singbox => vec![
+                "run".to_string(),
+                "-c".to_string(),
+                config,
+                "-D".to_string(),
+                home,
+            ],
+        }
+    }
+}
+
+/// Check that `name` can be used for a new instance.
+pub fn validate_instance_name(name: &str) -> Result<()> {
+    if name == DEFAULT_INSTANCE {
+        return Err(CrashError::Config(format!(
+            "'{}' is the top-level configuration and cannot be added",
+            DEFAULT_INSTANCE
+        )));
+    }
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(CrashError::Config(format!(
+            "Invalid instance name {:?}: use up to {} letters, digits, '-' or '_'",
+            name, MAX_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+impl CrashConfig {
+    /// Look up a named instance.
+    pub fn instance(&self, name: &str) -> Result<&InstanceConfig> {
+        self.instances.get(name).ok_or_else(|| {
+            CrashError::Config(format!(
+                "Unknown instance '{}'. Add it with 'crash config instance add'",
+                name
+            ))
+        })
+    }
+
+    /// Register a new instance, rejecting ports already used by the default
+    /// core or another instance.
+    pub fn add_instance(&mut self, name: &str, instance: InstanceConfig) -> Result<()> {
+        validate_instance_name(name)?;
+        if self.instances.contains_key(name) {
+            return Err(CrashError::Config(format!(
+                "Instance '{}' already exists",
+                name
+            )));
+        }
+
+        let taken = std::iter::once((DEFAULT_INSTANCE, self.mixed_port(), self.web.host.as_str()))
+            .chain(
+                self.instances
+                    .iter()
+                    .map(|(n, i)| (n.as_str(), i.mixed_port, i.web_host.as_str())),
+            );
+        for (other, mixed_port, web_host) in taken {
+            if mixed_port == instance.mixed_port || web_host == instance.web_host {
+                return Err(CrashError::Config(format!(
+                    "Instance '{}' already uses mixed port {} or controller {}",
+                    other, mixed_port, web_host
+                )));
+            }
+        }
+
+        self.instances.insert(name.to_string(), instance);
+        Ok(())
+    }
+
+    /// Start a named instance unless it is already running (or restart it
+    /// with `force`). Returns whether the core was started.
+    pub fn start_instance(&mut self, name: &str, force: bool) -> Result<bool> {
+        let instance = self.instance(name)?.clone();
+        if instance.pid(name).is_some() {
+            if !force {
+                log_info!("Instance {} already running", name);
+                return Ok(false);
+            }
+            self.stop_instance(name)?;
+        }
+
+        let core_exe = instance.core.exe_path(&get_config_dir());
+        if !core_exe.exists() {
+            return Err(CrashError::Process(format!(
+                "Core executable not found: {}. Install the {} core first",
+                core_exe.display(),
+                instance.core
+            )));
+        }
+
+        ensure_dir(&InstanceConfig::dir(name))?;
+
+        // Refresh the copy when the shared core was upgraded.
+        let exe_path = instance.exe_path(name);
+        let stale = match (std::fs::metadata(&core_exe), std::fs::metadata(&exe_path)) {
+            (Ok(src), Ok(dst)) => src.len() != dst.len() || src.modified()? > dst.modified()?,
+            _ => true,
+        };
+        if stale {
+            std::fs::copy(&core_exe, &exe_path)?;
+        }
+
+        let config_path = instance.config_path(name);
+        if !config_path.exists() {
+            std::fs::write(&config_path, self.instance_config(&instance)?)?;
+            log_info!("Created {} from the default config", config_path.display());
+        }
+
+        check_tun_device(instance.core, &std::fs::read_to_string(&config_path)?)?;
+        for warning in instance.privilege_warnings(name) {
+            log_warn!("Instance {}: {}", name, warning);
+        }
+
+        start(&exe_path, instance.args(name), instance.core.envs())?;
+
+        if let Some(instance) = self.instances.get_mut(name) {
+            instance.start_time = current_timestamp();
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Stop a named instance.
+    pub fn stop_instance(&mut self, name: &str) -> Result<()> {
+        let instance = self.instance(name)?;
+        if let Some(pid) = instance.pid(name) {
+            kill_pid(pid)?;
+        }
+
+        if let Some(instance) = self.instances.get_mut(name) {
+            instance.start_time = 0;
+        }
+        self.save()
+    }
+
+    /// Initial config for a new instance: the default core's config when
+    /// it uses the same core, otherwise the built-in template, patched with
+    /// the instance's ports.
+    fn instance_config(&self, instance: &InstanceConfig) -> Result<String> {
+        let web = WebConfig {
+            host: instance.web_host.clone(),
+            ..self.web.resolved()?
+        };
+
+        Ok(match std::fs::read_to_string(self.core_config_path()) {
+            Ok(content) if instance.core == self.core => patch_config(
+                instance.core,
+                &web,
+                instance.mixed_port,
+                &self.dns,
+                self.geo_ipv6,
+                None,
+                &content,
+            ),
+            _ => default_config(instance.core, &web, instance.mixed_port, &self.dns, None),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(mixed_port: u16, web_host: &str) -> InstanceConfig {
+        InstanceConfig {
+            core: Core::Singbox,
+            web_host: web_host.to_string(),
+            mixed_port,
+            start_time: 0,
+        }
+    }
+
+    #[test]
+    fn instance_names_are_restricted() {
+        assert!(validate_instance_name("test_2").is_ok());
+        assert!(validate_instance_name(DEFAULT_INSTANCE).is_err());
+        assert!(validate_instance_name("../x").is_err());
+        assert!(validate_instance_name("a-very-long-instance-name").is_err());
+    }
+
+    #[test]
+    fn add_instance_rejects_port_clashes() {
+        let mut config = CrashConfig::default();
+        config.add_instance("sb", instance(2080, ":9091")).unwrap();
+
+        // Default core's mixed port and controller.
+        assert!(config.add_instance("a", instance(7890, ":9092")).is_err());
+        assert!(config.add_instance("b", instance(7892, ":9090")).is_err());
+        // Another instance's port, and a duplicate name.
+        assert!(config.add_instance("c", instance(2080, ":9093")).is_err());
+        assert!(config.add_instance("sb", instance(7893, ":9094")).is_err());
+
+        assert_eq!(
+            config.instance("sb").unwrap().exe_name("sb"),
+            format!("Singbox-sb{}", exe_extension())
+        );
+    }
+}
@@ -6,21 +6,47 @@
 //   - `runtime`  : start / stop / version probing
 //   - `installer`: download / install / upgrade / update-from-url
 //   - `patcher`  : core-specific config patching
+//   - `ports`    : proxy port discovery from the core config
+//   - `providers`: pre-download of the remote providers a config references
+//   - `notify`   : crash / restart notifications
+//   - `dns`      : DNS overrides applied by the patcher
+//   - `events`   : start / stop / restart history of the core
+//   - `format`   : YAML/JSON checks for imported configs
+//   - `instance` : named core instances next to the default core
 
-use crate::config::core::Core;
+use crate::cli::TaskComponent;
+use crate::config::core::{Core, GeoSource};
+use crate::config::dns::DnsConfig;
+use crate::config::format::ensure_config_format;
+use crate::config::instance::InstanceConfig;
+use crate::config::notify::NotifyConfig;
+use crate::config::ports::ListenPorts;
 use crate::error::{CrashError, Result};
-use crate::log_info;
-use crate::utils::fs::{atomic_write, ensure_dir};
-use crate::utils::get_dir_size;
-use github_proxy::Proxy;
+use crate::log::{DEFAULT_LOG_ARCHIVES, LogFormat, LogRotation};
+use crate::utils::download::{DownloadOptions, RetryConfig, TimeoutConfig};
+use crate::utils::fs::atomic_write;
+use crate::utils::{get_dir_size, is_url};
+use crate::{log_info, log_warn};
+use github_proxy::{Proxy, Resource};
 use guess_target::{Target, get_local_target};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 pub mod core;
+pub mod dns;
+pub mod events;
+pub mod format;
 pub mod installer;
+pub mod instance;
+pub mod notify;
 pub mod patcher;
+pub mod ports;
+pub mod providers;
+pub mod proxies;
 pub mod runtime;
+pub mod tun;
 pub mod web;
 
 pub use web::WebConfig;
@@ -28,6 +54,95 @@ pub use web::WebConfig;
 const APP_CONFIG_DIR: &str = "crash_config";
 const APP_CONFIG_NAME: &str = "crash_config.json";
 const APP_LOG_DIR: &str = "logs";
+const DAEMON_PID_NAME: &str = "daemon.pid";
+const DEFAULT_CHECK_URL: &str = "https://www.google.com";
+const DEFAULT_ASSETS_OWNER: &str = "ahaoboy";
+const DEFAULT_ASSETS_REPO: &str = "crash-assets";
+const DEFAULT_ASSETS_REF: &str = "main";
+
+/// The repository cores, dashboards and geo databases are downloaded from:
+/// crash-assets, or a fork of it, at a branch, tag or commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetsRepo<'a> {
+    pub owner: &'a str,
+    pub repo: &'a str,
+    pub reference: &'a str,
+}
+
+impl Default for AssetsRepo<'_> {
+    fn default() -> Self {
+        Self {
+            owner: DEFAULT_ASSETS_OWNER,
+            repo: DEFAULT_ASSETS_REPO,
+            reference: DEFAULT_ASSETS_REF,
+        }
+    }
+}
+
+impl AssetsRepo<'_> {
+    /// The file at `path` in the repository.
+    pub fn file(&self, path: impl Into<String>) -> Resource {
+        Resource::File {
+            owner: self.owner.to_string(),
+            repo: self.repo.to_string(),
+            reference: self.reference.to_string(),
+            path: path.into(),
+        }
+    }
+}
+
+/// Split an `owner/repo` GitHub repository name, rejecting anything else.
+pub fn parse_repo_name(name: &str) -> Result<(&str, &str)> {
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    match name.trim().split_once('/') {
+        Some((owner, repo)) if valid(owner) && valid(repo) => Ok((owner, repo)),
+        _ => Err(CrashError::Config(format!(
+            "Invalid repository {:?}: expected owner/repo",
+            name
+        ))),
+    }
+}
+
+/// Environment variable that, set to `1`, makes this invocation download
+/// from GitHub directly, ignoring the stored proxy without changing it.
+pub const NO_PROXY_ENV: &str = "CRASH_NO_PROXY";
+
+/// Environment variable naming a directory to write logs to, over the
+/// stored `log_dir`.
+pub const LOG_DIR_ENV: &str = "CRASH_LOG_DIR";
+
+/// Where an effective setting comes from, as shown by `config show --effective`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ConfigSource {
+    /// Set in the config file.
+    File,
+    /// Missing from the file (or unset) and filled in by crash.
+    Default,
+    /// Overridden by an environment variable for this invocation.
+    Env,
+}
+
+/// A problem found by `config validate`, with how to fix it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub problem: String,
+    pub fix: String,
+}
+
+impl ConfigIssue {
+    fn new(problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+}
 
 /// Main configuration structure for the Crash application.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,11 +159,125 @@ pub struct CrashConfig {
     #[serde(default)]
     pub stop_force: bool,
 
+    /// The core runs from the minimal config of `start --safe`; the next
+    /// plain `start` brings it back to the normal config.
+    #[serde(default)]
+    pub safe_mode: bool,
+
     /// Optional URL for the proxy health check performed on each `start`.
     /// Defaults to `https://www.google.com` when `None`. Set to a URL that
     /// is only reachable through the proxy for a meaningful check.
     #[serde(default)]
     pub check_url: Option<String>,
+
+    /// Format of crash's own log file.
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// How a full log file is rotated.
+    #[serde(default)]
+    pub log_rotation: LogRotation,
+
+    /// Compressed log archives to keep (`crash.log.<timestamp>.gz`).
+    #[serde(default = "default_log_archives")]
+    pub log_archives: usize,
+
+    /// Maximum number of concurrent downloads (0 = unlimited).
+    #[serde(default)]
+    pub max_concurrent_downloads: usize,
+
+    /// Download bandwidth cap in bytes per second (0 = unlimited).
+    #[serde(default)]
+    pub download_rate_limit_bytes_per_sec: u64,
+
+    /// Free space to leave on the config partition; downloads are refused
+    /// below it (0 = disabled).
+    #[serde(default)]
+    pub min_free_bytes: u64,
+
+    /// Local mixed (HTTP + SOCKS) proxy port injected into the core config.
+    /// `None` uses the core's default from `Core::default_ports`.
+    #[serde(default)]
+    pub mixed_port: Option<u16>,
+
+    /// Where to report core crashes and automatic restarts.
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    /// Retry and backoff settings for crash's own downloads.
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Connect and idle timeouts for crash's own downloads.
+    #[serde(default)]
+    pub timeout: TimeoutConfig,
+
+    /// DNS overrides patched into the core config.
+    #[serde(default)]
+    pub dns: DnsConfig,
+
+    /// Branch, tag or commit of crash-assets that cores, dashboards and geo
+    /// databases are downloaded from. `None` tracks `main`.
+    #[serde(default)]
+    pub assets_ref: Option<String>,
+
+    /// Owner and name of the assets repository, for forks of crash-assets.
+    /// `None` uses ahaoboy/crash-assets.
+    #[serde(default)]
+    pub assets_owner: Option<String>,
+    #[serde(default)]
+    pub assets_repo: Option<String>,
+
+    /// Core archive or binary URL used instead of the crash-assets release
+    /// for the configured target, for self-built cores.
+    #[serde(default)]
+    pub core_url_override: Option<String>,
+
+    /// Upstream release of the core to install (e.g. `1.19.15`) instead of
+    /// the crash-assets build. `None` uses crash-assets.
+    #[serde(default)]
+    pub core_version: Option<String>,
+
+    /// Named core instances running next to this (default) one.
+    #[serde(default)]
+    pub instances: BTreeMap<String, InstanceConfig>,
+
+    /// Also download the IPv6 IP lists and reference them from the core
+    /// config, for dual-stack routing. Set by `install geo --ipv6`.
+    #[serde(default)]
+    pub geo_ipv6: bool,
+
+    /// Where geo databases are downloaded from.
+    #[serde(default)]
+    pub geo_source: GeoSource,
+
+    /// Mirrors tried in order, after `proxy`, when a download through the
+    /// previous one fails.
+    #[serde(default)]
+    pub proxy_fallbacks: Vec<Proxy>,
+
+    /// Extra environment variables the core is started with, over the
+    /// core's own defaults.
+    #[serde(default)]
+    pub core_env: BTreeMap<String, String>,
+
+    /// Steps of the scheduled `run-task`, also written into the task
+    /// command by `install task`.
+    #[serde(default = "default_task_components")]
+    pub task_components: Vec<TaskComponent>,
+
+    /// Directory log files are written to, instead of the `logs` directory
+    /// next to the config, e.g. a tmpfs or a larger disk.
+    #[serde(default)]
+    pub log_dir: Option<PathBuf>,
+
+    /// Environment from `start --env`, over `core_env`, for this run only.
+    #[serde(skip)]
+    pub start_env: BTreeMap<String, String>,
+
+    /// Ports read from the core config by `listen_ports`, once per load.
+    #[serde(skip)]
+    detected_ports: OnceLock<ListenPorts>,
 }
 
 impl Default for CrashConfig {
@@ -62,8 +291,34 @@ impl Default for CrashConfig {
             web: WebConfig::default(),
             url: String::new(),
             stop_force: false,
+            safe_mode: false,
             max_runtime_hours: 0,
             check_url: None,
+            log_format: LogFormat::default(),
+            log_rotation: LogRotation::default(),
+            log_archives: DEFAULT_LOG_ARCHIVES,
+            max_concurrent_downloads: 0,
+            download_rate_limit_bytes_per_sec: 0,
+            min_free_bytes: 0,
+            mixed_port: None,
+            notify: NotifyConfig::default(),
+            retry: RetryConfig::default(),
+            timeout: TimeoutConfig::default(),
+            dns: DnsConfig::default(),
+            assets_ref: None,
+            assets_owner: None,
+            assets_repo: None,
+            core_url_override: None,
+            core_version: None,
+            instances: BTreeMap::new(),
+            geo_ipv6: false,
+            geo_source: GeoSource::default(),
+            proxy_fallbacks: Vec::new(),
+            core_env: BTreeMap::new(),
+            task_components: default_task_components(),
+            log_dir: None,
+            start_env: BTreeMap::new(),
+            detected_ports: OnceLock::new(),
         }
     }
 }
@@ -74,26 +329,39 @@ impl CrashConfig {
         let config_path = get_config_path();
         log_info!("Loading configuration from {}", config_path.display());
 
-        let config = if config_path.exists() {
-            let data = std::fs::read_to_string(&config_path).map_err(|e| {
-                CrashError::Config(format!(
-                    "Failed to read config file {}: {}",
-                    config_path.display(),
-                    e
-                ))
-            })?;
-
-            let config: CrashConfig = serde_json::from_str(&data)
-                .map_err(|e| CrashError::Config(format!("Failed to parse config file: {}", e)))?;
-            config.validate()?;
-            config
-        } else {
+        if !config_path.exists() {
             log_info!("Config file not found, creating default configuration");
             let config = Self::default();
-            config.save()?;
-            config
-        };
+            // Read-only commands (e.g. `status`) must keep working on a
+            // read-only filesystem, so failing to persist defaults is not fatal.
+            if let Err(e) = config.save() {
+                log_warn!("Failed to save default configuration: {}", e);
+            }
+            return Ok(config);
+        }
+
+        Self::read()
+    }
+
+    /// Read the configuration from disk like `load`, but never create the
+    /// file: the defaults are returned when it does not exist.
+    pub fn read() -> Result<Self> {
+        let config_path = get_config_path();
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
 
+        let data = std::fs::read_to_string(&config_path).map_err(|e| {
+            CrashError::Config(format!(
+                "Failed to read config file {}: {}",
+                config_path.display(),
+                e
+            ))
+        })?;
+
+        let config: CrashConfig = serde_json::from_str(&data)
+            .map_err(|e| CrashError::Config(format!("Failed to parse config file: {}", e)))?;
+        config.validate()?;
         Ok(config)
     }
 
@@ -102,14 +370,19 @@ impl CrashConfig {
         let config_path = get_config_path();
         log_info!("Saving configuration to {}", config_path.display());
 
-        ensure_dir(&get_config_dir())?;
-
         let json = serde_json::to_string_pretty(self)
             .map_err(|e| CrashError::Config(format!("Failed to serialize config: {}", e)))?;
 
-        atomic_write(&config_path, &json)?;
-
-        Ok(())
+        atomic_write(&config_path, &json).map_err(|e| {
+            CrashError::Config(format!(
+                "Failed to save configuration to {}: {} (read-only filesystem?)",
+                config_path.display(),
+                e
+            ))
+        })?;
+        // Some router overlays accept writes that never reach the disk;
+        // read the file back so the setting isn't silently lost.
+        verify_persisted(&config_path, &json)
     }
 
     /// Validate configuration values.
@@ -141,20 +414,453 @@ impl CrashConfig {
         Ok(())
     }
 
+    /// Check the stored config file without loading it the usual way, so a
+    /// file that `load` would reject is reported rather than fatal.
+    pub fn check_file() -> Vec<ConfigIssue> {
+        let config_path = get_config_path();
+        if !config_path.exists() {
+            return Self::default().issues();
+        }
+
+        let config = std::fs::read_to_string(&config_path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str::<CrashConfig>(&data).map_err(|e| e.to_string()));
+        match config {
+            Ok(config) => config.issues(),
+            Err(e) => vec![ConfigIssue::new(
+                format!("{} cannot be read: {}", config_path.display(), e),
+                "Fix the JSON by hand, or delete the file to start over with defaults",
+            )],
+        }
+    }
+
+    /// Everything wrong with this config and the core config it points at.
+    pub fn issues(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        if let Err(e) = self.validate() {
+            issues.push(ConfigIssue::new(
+                e.to_string(),
+                "Set a controller address with 'crash config host :9090'",
+            ));
+        }
+        if let Err(e) = self.web.resolved() {
+            issues.push(ConfigIssue::new(
+                e.to_string(),
+                "Export the variable or set a literal secret with 'crash config secret <value>'",
+            ));
+        }
+        // A custom core URL doesn't depend on the target.
+        if self.core_url_override.is_none()
+            && let Err(e) = self.core.release_file_name(&self.target)
+        {
+            issues.push(ConfigIssue::new(
+                e.to_string(),
+                "Pick another core with 'crash core <name>', fix the target with \
+                 'crash config target' or install a custom build with 'crash config core-url'",
+            ));
+        }
+        if let Some((expected, found)) = self.core_arch_mismatch() {
+            issues.push(ConfigIssue::new(
+                format!(
+                    "Installed {} core is built for {}, but target {:?} needs {}",
+                    self.core, found, self.target, expected
+                ),
+                "Fix the target with 'crash config target <target>', then reinstall with \
+                 'crash install core -f'",
+            ));
+        }
+        if !self.url.is_empty() && !is_url(&self.url) && !std::path::Path::new(&self.url).exists() {
+            issues.push(ConfigIssue::new(
+                format!(
+                    "Config source {} is neither a URL nor an existing file",
+                    self.url
+                ),
+                "Set it with 'crash config url <url-or-path>'",
+            ));
+        }
+
+        let core_config = self.core_config_path();
+        match std::fs::read_to_string(&core_config) {
+            Err(_) => issues.push(ConfigIssue::new(
+                format!("{} config {} is missing", self.core, core_config.display()),
+                if self.url.is_empty() {
+                    "Run 'crash install' to write the default config"
+                } else {
+                    "Run 'crash update-url -f' to download it"
+                },
+            )),
+            Ok(content) if content.trim().is_empty() => issues.push(ConfigIssue::new(
+                format!("{} config {} is empty", self.core, core_config.display()),
+                "Run 'crash update-url -f' or 'crash install' to rewrite it",
+            )),
+            Ok(content) => {
+                if let Err(e) = ensure_config_format(self.core, &content, None) {
+                    issues.push(ConfigIssue::new(
+                        e.to_string(),
+                        "Re-download a matching config with 'crash update-url -f'",
+                    ));
+                } else if self.core == Core::Singbox
+                    && let Err(e) = serde_json::from_str::<serde_json::Value>(&content)
+                {
+                    issues.push(ConfigIssue::new(
+                        format!("{} is not valid JSON: {}", core_config.display(), e),
+                        "Fix the file or re-download it with 'crash update-url -f'",
+                    ));
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// Copy of the config with the web secret, notification token and
+    /// subscription URL masked, safe to attach to a bug report.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        config.url = mask_url(&self.url);
+        // A `${NAME}` reference is not itself a secret.
+        if !config.web.secret.is_empty() && config.web.secret_env_var().is_none() {
+            config.web.secret = REDACTED.to_string();
+        }
+        if let Some(telegram) = &mut config.notify.telegram {
+            telegram.token = REDACTED.to_string();
+        }
+        // Core environment often carries proxy credentials.
+        for value in config.core_env.values_mut() {
+            *value = REDACTED.to_string();
+        }
+        config
+    }
+
+    /// Mask every secret of this config that occurs in `text`.
+    pub fn redact(&self, text: &str) -> String {
+        let mut secrets = vec![(self.url.as_str(), mask_url(&self.url))];
+        let web_secret = self
+            .web
+            .resolved()
+            .map(|web| web.secret)
+            .unwrap_or_default();
+        secrets.push((web_secret.as_str(), REDACTED.to_string()));
+        if let Some(telegram) = &self.notify.telegram {
+            secrets.push((telegram.token.as_str(), REDACTED.to_string()));
+        }
+
+        let mut text = text.to_string();
+        for (secret, mask) in secrets {
+            if !secret.is_empty() && secret != mask {
+                text = text.replace(secret, &mask);
+            }
+        }
+        text
+    }
+
     /// Path to the core's own configuration file.
     pub fn core_config_path(&self) -> PathBuf {
         get_config_dir().join(self.core.config_file_name())
     }
 
+    /// Cores whose executable is present in the config directory.
+    pub fn installed_cores(&self) -> Vec<Core> {
+        let config_dir = get_config_dir();
+        Core::ALL
+            .into_iter()
+            .filter(|core| core.exe_path(&config_dir).exists())
+            .collect()
+    }
+
+    /// Mixed proxy port written into the core config.
+    pub fn mixed_port(&self) -> u16 {
+        self.mixed_port
+            .unwrap_or_else(|| self.core.default_ports().mixed)
+    }
+
+    /// Bring the core-specific settings in line with `self.core` after a
+    /// switch from `old`, returning what was applied. Only values still at
+    /// `old`'s defaults are moved to the new core's; anything else was set
+    /// on purpose and is kept, with a note when it looks meant for `old`.
+    pub fn apply_core_baseline(&mut self, old: Core) -> Vec<String> {
+        let (old_ports, new_ports) = (old.default_ports(), self.core.default_ports());
+        let mut applied = Vec::new();
+
+        if self.mixed_port == Some(old_ports.mixed) && old_ports.mixed != new_ports.mixed {
+            self.mixed_port = None;
+        }
+        applied.push(format!("mixed port {}", self.mixed_port()));
+
+        if ports::host_port(&self.web.host) == Some(old_ports.controller)
+            && old_ports.controller != new_ports.controller
+        {
+            self.web.host = format!(":{}", new_ports.controller);
+            applied.push(format!("controller {}", self.web.host));
+        }
+
+        for (key, value) in self.core.envs() {
+            match self.core_env.get(key) {
+                Some(own) if own != value => {
+                    applied.push(format!("env {}={} kept (recommended: {})", key, own, value))
+                }
+                _ => applied.push(format!("env {}={}", key, value)),
+            }
+        }
+
+        if let Some(url) = &self.core_url_override {
+            applied.push(format!(
+                "custom core URL kept, check it is a {} build: {}",
+                self.core, url
+            ));
+        }
+        if let Some(version) = &self.core_version {
+            applied.push(format!(
+                "core version {} kept, check {} has that release",
+                version, self.core
+            ));
+        }
+        applied
+    }
+
+    /// GitHub proxy used for downloads: the `--proxy` of this invocation,
+    /// `Proxy::Github` (no mirror) when `CRASH_NO_PROXY=1`, otherwise the
+    /// stored one.
+    pub fn proxy(&self) -> Proxy {
+        if let Some(&proxy) = PROXY_OVERRIDE.get() {
+            proxy
+        } else if no_proxy_env() {
+            Proxy::Github
+        } else {
+            self.proxy
+        }
+    }
+
+    /// Proxies downloads are tried through, in order: `proxy`, then each of
+    /// `proxy_fallbacks` not tried yet. Only the one proxy when it comes
+    /// from `--proxy` or `CRASH_NO_PROXY` is set.
+    pub fn proxy_chain(&self) -> Vec<Proxy> {
+        let mut chain = vec![self.proxy()];
+        if PROXY_OVERRIDE.get().is_none() && !no_proxy_env() {
+            for &proxy in &self.proxy_fallbacks {
+                if !chain.contains(&proxy) {
+                    chain.push(proxy);
+                }
+            }
+        }
+        chain
+    }
+
+    /// crash-assets reference used for downloads.
+    pub fn assets_ref(&self) -> &str {
+        self.assets_ref.as_deref().unwrap_or(DEFAULT_ASSETS_REF)
+    }
+
+    /// Assets repository and reference used for downloads.
+    pub fn assets(&self) -> AssetsRepo<'_> {
+        AssetsRepo {
+            owner: self.assets_owner.as_deref().unwrap_or(DEFAULT_ASSETS_OWNER),
+            repo: self.assets_repo.as_deref().unwrap_or(DEFAULT_ASSETS_REPO),
+            reference: self.assets_ref(),
+        }
+    }
+
+    /// URL probed by the health check on each `start`.
+    pub fn check_url(&self) -> &str {
+        self.check_url.as_deref().unwrap_or(DEFAULT_CHECK_URL)
+    }
+
+    /// Every setting as `(dotted key, JSON value, source)`, with secrets
+    /// redacted and unset options resolved to the value used at runtime.
+    /// `file` is the raw config file, if any, used to tell values set there
+    /// apart from defaults.
+    pub fn effective_settings(
+        &self,
+        file: Option<&serde_json::Value>,
+    ) -> Result<Vec<(String, String, ConfigSource)>> {
+        let mut value = serde_json::to_value(self.redacted())?;
+        value["mixed_port"] = self.mixed_port().into();
+        value["check_url"] = self.check_url().into();
+        value["assets_ref"] = self.assets_ref().into();
+        value["assets_owner"] = self.assets().owner.into();
+        value["assets_repo"] = self.assets().repo.into();
+        value["log_dir"] = self.log_dir().to_string_lossy().into();
+
+        let mut settings = Vec::new();
+        flatten_settings(String::new(), &value, file, &mut settings);
+        if no_proxy_env()
+            && let Some(setting) = settings.iter_mut().find(|(key, ..)| key == "proxy")
+        {
+            setting.1 = serde_json::to_value(self.proxy())?.to_string();
+            setting.2 = ConfigSource::Env;
+        }
+        if log_dir_env().is_some()
+            && let Some(setting) = settings.iter_mut().find(|(key, ..)| key == "log_dir")
+        {
+            setting.2 = ConfigSource::Env;
+        }
+        Ok(settings)
+    }
+
+    /// Inbound ports declared in the core config, read on first use.
+    pub fn listen_ports(&self) -> &ListenPorts {
+        self.detected_ports.get_or_init(|| {
+            std::fs::read_to_string(self.core_config_path())
+                .map(|content| ListenPorts::parse(self.core, &content))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Port of the core's local HTTP/mixed proxy inbound. The core config
+    /// wins when it declares one (it may have been edited by hand);
+    /// otherwise the stored mixed port is used.
+    pub fn proxy_port(&self) -> u16 {
+        self.listen_ports()
+            .http_proxy()
+            .unwrap_or_else(|| self.mixed_port())
+    }
+
+    /// Download settings for crash's own HTTP downloads.
+    pub fn download_options(&self) -> DownloadOptions {
+        DownloadOptions {
+            max_concurrent: self.max_concurrent_downloads,
+            rate_limit: self.download_rate_limit_bytes_per_sec,
+            retry: self.retry,
+            timeout: self.timeout,
+            cancel: None,
+        }
+    }
+
+    /// Directory log files go to: `CRASH_LOG_DIR`, then `log_dir`, then
+    /// the default next to the config.
+    pub fn log_dir(&self) -> PathBuf {
+        log_dir_env().unwrap_or_else(|| self.log_dir.clone().unwrap_or_else(get_log_dir))
+    }
+
     /// Total size in bytes of the on-disk crash config directory.
     pub fn get_size(&self) -> u64 {
         get_dir_size(&get_config_dir())
     }
 }
 
+/// Check that `path` now holds `expected`, as written by `save`.
+fn verify_persisted(path: &Path, expected: &str) -> Result<()> {
+    match std::fs::read_to_string(path) {
+        Ok(content) if content == expected => Ok(()),
+        Ok(_) => Err(CrashError::Config(format!(
+            "Configuration was not persisted: {} still has other content (directory read-only?)",
+            path.display()
+        ))),
+        Err(e) => Err(CrashError::Config(format!(
+            "Configuration was not persisted: reading {} back failed: {} (directory read-only?)",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+fn default_task_components() -> Vec<TaskComponent> {
+    TaskComponent::DEFAULT.to_vec()
+}
+
+fn default_log_archives() -> usize {
+    DEFAULT_LOG_ARCHIVES
+}
+
+/// Proxy given with `--proxy`, used instead of the stored one for this
+/// invocation only.
+static PROXY_OVERRIDE: OnceLock<Proxy> = OnceLock::new();
+
+/// Use `proxy` for every download of this invocation, without saving it.
+pub fn override_proxy(proxy: Proxy) {
+    let _ = PROXY_OVERRIDE.set(proxy);
+}
+
+/// Whether `CRASH_NO_PROXY` asks for direct downloads.
+pub fn no_proxy_env() -> bool {
+    matches!(std::env::var(NO_PROXY_ENV).as_deref(), Ok("1" | "true"))
+}
+
+/// Log directory set by `CRASH_LOG_DIR`, if any.
+fn log_dir_env() -> Option<PathBuf> {
+    std::env::var_os(LOG_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Collect the leaves of `value` below `prefix`, marking those present
+/// (and not null) in the matching part of `file` as coming from the file.
+fn flatten_settings(
+    prefix: String,
+    value: &serde_json::Value,
+    file: Option<&serde_json::Value>,
+    settings: &mut Vec<(String, String, ConfigSource)>,
+) {
+    if let serde_json::Value::Object(map) = value
+        && !map.is_empty()
+    {
+        for (key, child) in map {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            flatten_settings(path, child, file.and_then(|f| f.get(key)), settings);
+        }
+        return;
+    }
+
+    let source = match file {
+        Some(v) if !v.is_null() => ConfigSource::File,
+        _ => ConfigSource::Default,
+    };
+    settings.push((prefix, value.to_string(), source));
+}
+
+/// Placeholder for masked secrets.
+const REDACTED: &str = "***";
+
+/// Keep only the scheme and host of a URL, which usually identify the
+/// provider without leaking the subscription token. Local paths are kept.
+fn mask_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    format!("{}://{}/{}", scheme, host, REDACTED)
+}
+
+#[cfg(test)]
+thread_local! {
+    static CONFIG_DIR_OVERRIDE: std::cell::RefCell<Option<PathBuf>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Guard returned by `override_config_dir`; restores the exe-relative
+/// config dir when dropped.
+#[cfg(test)]
+pub(crate) struct ConfigDirOverride(());
+
+#[cfg(test)]
+impl Drop for ConfigDirOverride {
+    fn drop(&mut self) {
+        CONFIG_DIR_OVERRIDE.with(|dir| *dir.borrow_mut() = None);
+    }
+}
+
+/// Point `get_config_dir` (and everything derived from it) at `dir` for the
+/// current test thread, so tests never touch the real install directory.
+#[cfg(test)]
+pub(crate) fn override_config_dir(dir: &std::path::Path) -> ConfigDirOverride {
+    CONFIG_DIR_OVERRIDE.with(|d| *d.borrow_mut() = Some(dir.to_path_buf()));
+    ConfigDirOverride(())
+}
+
 /// Directory holding the crash config, logs and installed assets.
 /// Lives next to the `crash` executable so an install is self-contained.
 pub fn get_config_dir() -> PathBuf {
+    #[cfg(test)]
+    if let Some(dir) = CONFIG_DIR_OVERRIDE.with(|d| d.borrow().clone()) {
+        return dir;
+    }
+
     std::env::current_exe()
         .ok()
         .and_then(|p| p.parent().map(|parent| parent.to_path_buf()))
@@ -166,6 +872,231 @@ pub fn get_config_path() -> PathBuf {
     get_config_dir().join(APP_CONFIG_NAME)
 }
 
+/// Default log directory, used unless `CrashConfig::log_dir` says otherwise.
 pub fn get_log_dir() -> PathBuf {
     get_config_dir().join(APP_LOG_DIR)
 }
+
+pub fn get_daemon_pid_path() -> PathBuf {
+    get_config_dir().join(DAEMON_PID_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_chain_starts_with_the_proxy_without_repeats() {
+        let config = CrashConfig {
+            proxy: Proxy::Xget,
+            proxy_fallbacks: vec![Proxy::Github, Proxy::Xget, Proxy::Jsdelivr, Proxy::Github],
+            ..Default::default()
+        };
+        if !no_proxy_env() {
+            assert_eq!(
+                config.proxy_chain(),
+                [Proxy::Xget, Proxy::Github, Proxy::Jsdelivr]
+            );
+        }
+    }
+
+    #[test]
+    fn log_dir_defaults_next_to_the_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+
+        if std::env::var_os(LOG_DIR_ENV).is_none() {
+            let mut config = CrashConfig::default();
+            assert_eq!(config.log_dir(), tmp.path().join(APP_LOG_DIR));
+            config.log_dir = Some(PathBuf::from("/var/log/crash"));
+            assert_eq!(config.log_dir(), PathBuf::from("/var/log/crash"));
+        }
+    }
+
+    #[test]
+    fn parses_repo_names() {
+        assert_eq!(
+            parse_repo_name("me/crash-assets").unwrap(),
+            ("me", "crash-assets")
+        );
+        assert_eq!(parse_repo_name(" a.b/c_d ").unwrap(), ("a.b", "c_d"));
+        for name in ["crash-assets", "/repo", "owner/", "a/b/c", "a b/c"] {
+            assert!(parse_repo_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn load_creates_default_in_overridden_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+
+        let config = CrashConfig::load().unwrap();
+        assert_eq!(get_config_path(), tmp.path().join(APP_CONFIG_NAME));
+        assert!(get_config_path().exists());
+        assert_eq!(config.web.host, WebConfig::default().host);
+    }
+
+    #[test]
+    fn unpersisted_save_is_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(APP_CONFIG_NAME);
+        std::fs::write(&path, "{}").unwrap();
+
+        assert!(verify_persisted(&path, "{}").is_ok());
+        let err = verify_persisted(&path, "{\"proxy\": \"Xget\"}").unwrap_err();
+        assert!(err.to_string().contains("not persisted"));
+        assert!(verify_persisted(&tmp.path().join("missing.json"), "{}").is_err());
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+
+        let config = CrashConfig {
+            url: "https://example.com/sub.yaml".to_string(),
+            max_runtime_hours: 12,
+            ..Default::default()
+        };
+        config.save().unwrap();
+
+        let loaded = CrashConfig::load().unwrap();
+        assert_eq!(loaded.url, config.url);
+        assert_eq!(loaded.max_runtime_hours, 12);
+    }
+
+    #[test]
+    fn load_rejects_invalid_host() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+
+        let mut config = CrashConfig::default();
+        config.web.host = "9090".to_string();
+        config.save().unwrap();
+
+        assert!(CrashConfig::load().is_err());
+    }
+
+    #[test]
+    fn issues_cover_host_and_missing_core_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+
+        let mut config = CrashConfig::default();
+        config.web.host = "9090".to_string();
+        let issues = config.issues();
+        assert!(issues.iter().any(|i| i.problem.contains("web host")));
+        assert!(issues.iter().any(|i| i.problem.contains("is missing")));
+
+        config.web.host = ":9090".to_string();
+        std::fs::write(config.core_config_path(), r#"{"outbounds": []}"#).unwrap();
+        let issues = config.issues();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].problem.contains("crash core singbox"));
+
+        std::fs::write(get_config_path(), "{ not json").unwrap();
+        assert!(
+            CrashConfig::check_file()[0]
+                .problem
+                .contains("cannot be read")
+        );
+    }
+
+    #[test]
+    fn redaction_masks_secrets() {
+        let mut config = CrashConfig {
+            url: "https://user:pw@sub.example.com/api/v1?token=abc".to_string(),
+            ..Default::default()
+        };
+        config.web.secret = "hunter2".to_string();
+
+        let redacted = config.redacted();
+        assert_eq!(redacted.url, "https://sub.example.com/***");
+        assert_eq!(redacted.web.secret, "***");
+
+        let log = format!("fetching {} with secret hunter2", config.url);
+        assert_eq!(
+            config.redact(&log),
+            "fetching https://sub.example.com/*** with secret ***"
+        );
+    }
+
+    #[test]
+    fn read_does_not_create_the_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+
+        assert!(CrashConfig::read().is_ok());
+        assert!(!get_config_path().exists());
+
+        CrashConfig::load().unwrap();
+        assert!(get_config_path().exists());
+    }
+
+    #[test]
+    fn effective_settings_mark_sources() {
+        let mut config = CrashConfig::default();
+        config.web.secret = "hunter2".to_string();
+        let file = serde_json::json!({ "web": { "secret": "hunter2" }, "mixed_port": null });
+
+        let settings = config.effective_settings(Some(&file)).unwrap();
+        let get = |key: &str| settings.iter().find(|(k, _, _)| k == key).unwrap();
+
+        assert_eq!(get("web.secret").1, "\"***\"");
+        assert_eq!(get("web.secret").2, ConfigSource::File);
+        assert_eq!(get("web.host").2, ConfigSource::Default);
+        assert_eq!(get("mixed_port").1, "7890");
+        assert_eq!(get("mixed_port").2, ConfigSource::Default);
+    }
+
+    #[test]
+    fn core_baseline_only_moves_defaults() {
+        let mut config = CrashConfig {
+            core: Core::Singbox,
+            mixed_port: Some(Core::Mihomo.default_ports().mixed),
+            ..Default::default()
+        };
+        let applied = config.apply_core_baseline(Core::Mihomo);
+        assert_eq!(config.mixed_port(), Core::Singbox.default_ports().mixed);
+        assert_eq!(
+            applied,
+            [
+                "mixed port 2080",
+                "env ENABLE_DEPRECATED_SPECIAL_OUTBOUNDS=true"
+            ]
+        );
+
+        let mut config = CrashConfig {
+            core: Core::Singbox,
+            mixed_port: Some(7899),
+            core_env: BTreeMap::from([(
+                "ENABLE_DEPRECATED_SPECIAL_OUTBOUNDS".to_string(),
+                "false".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let applied = config.apply_core_baseline(Core::Mihomo);
+        assert_eq!(config.mixed_port, Some(7899));
+        assert_eq!(
+            applied[1],
+            "env ENABLE_DEPRECATED_SPECIAL_OUTBOUNDS=false kept (recommended: true)"
+        );
+    }
+
+    #[test]
+    fn reconcile_clears_stale_start_time_only() {
+        let mut config = CrashConfig {
+            start_time: 1_700_000_000,
+            ..Default::default()
+        };
+        assert!(!config.reconcile_start_time(true));
+        assert_eq!(config.start_time, 1_700_000_000);
+
+        assert!(config.reconcile_start_time(false));
+        assert_eq!(config.start_time, 0);
+
+        // Running with no start time is left for `status` to show as unknown.
+        assert!(!config.reconcile_start_time(true));
+        assert_eq!(config.start_time, 0);
+    }
+}
@@ -0,0 +1,108 @@
+// Notifications about core crashes and restarts.
+//
+// Only Telegram is supported for now. Sending is best effort: a failed
+// notification is logged and never fails the command that triggered it.
+
+use crate::error::{CrashError, Result};
+use crate::utils::download::new_client;
+use crate::utils::get_user;
+use crate::{log_debug, log_warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Timeout for a single notification request.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Telegram bot credentials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelegramConfig {
+    pub token: String,
+    pub chat_id: String,
+}
+
+/// Notification backends; all configured backends receive every message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifyConfig {
+    #[serde(default)]
+    pub telegram: Option<TelegramConfig>,
+}
+
+impl NotifyConfig {
+    /// Whether any backend is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.telegram.is_some()
+    }
+
+    /// Send `message` to every configured backend, logging failures.
+    pub async fn notify(&self, message: &str) {
+        if let Err(e) = self.try_notify(message).await {
+            log_warn!("Failed to send notification: {}", e);
+        }
+    }
+
+    /// Send `message` to every configured backend.
+    pub async fn try_notify(&self, message: &str) -> Result<()> {
+        let text = format!("[crash@{}] {}", get_user(), message);
+
+        if let Some(telegram) = &self.telegram {
+            send_telegram(telegram, &text).await?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for NotifyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.telegram {
+            Some(telegram) => write!(f, "telegram (chat {})", telegram.chat_id),
+            None => write!(f, "off"),
+        }
+    }
+}
+
+async fn send_telegram(telegram: &TelegramConfig, text: &str) -> Result<()> {
+    let url = format!("https://api.telegram.org/bot{}/sendMessage", telegram.token);
+    let body = serde_json::json!({
+        "chat_id": telegram.chat_id,
+        "text": text,
+    });
+    // The bot token is part of the URL, so keep it out of error messages.
+    let response = new_client()
+        .post(url)
+        .timeout(NOTIFY_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| {
+            CrashError::Download(format!("Telegram request failed: {}", e.without_url()))
+        })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CrashError::Download(format!(
+            "Telegram returned {}",
+            status
+        )));
+    }
+
+    log_debug!("Telegram notification sent");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_hides_token() {
+        let notify = NotifyConfig {
+            telegram: Some(TelegramConfig {
+                token: "123:secret".to_string(),
+                chat_id: "42".to_string(),
+            }),
+        };
+        assert_eq!(notify.to_string(), "telegram (chat 42)");
+        assert_eq!(NotifyConfig::default().to_string(), "off");
+    }
+}
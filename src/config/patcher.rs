@@ -1,54 +1,423 @@
 // Configuration patching per core type.
 //
 // `patch_config` is a free function rather than a method on `CrashConfig`
-// because it only reads `core` and `web` and never needs `&self` state.
+// because it only reads `core`, `web`, the mixed port and DNS overrides and
+// never needs `&self` state.
 // Keeping it standalone makes it trivial to unit-test in isolation.
 
-use super::core::Core;
+use super::core::{CHINA_IPV6_LIST, Core};
+use super::dns::DnsConfig;
+use super::ports::yaml_top_level_value;
 use super::web::WebConfig;
+use crate::log_warn;
 use serde_json::{Value, json};
 
 /// Default TUN block appended to Mihomo configs that don't already define one.
 const MIHOMO_TUN_YAML: &str = include_str!("../assets/mihomo_tun.yaml");
 
+/// Default config for the YAML cores (Mihomo / Clash).
+const MIHOMO_DEFAULT_YAML: &str = include_str!("../assets/mihomo.yaml");
+
+/// Minimal Singbox config; inbounds and clash_api are filled in by `patch_singbox`.
+const SINGBOX_DEFAULT_JSON: &str = r#"{
+  "log": { "level": "info" },
+  "inbounds": [],
+  "outbounds": [{ "type": "direct", "tag": "direct" }]
+}"#;
+
+/// Rule provider name of `CHINA_IPV6_LIST` in the Mihomo config.
+const CHINA_IPV6_PROVIDER: &str = "china_ipv6";
+
+/// Top-level YAML port keys that would fight the mixed port for the same socket.
+const YAML_PORT_KEYS: [&str; 3] = ["port", "socks-port", "redir-port"];
+
 /// Patch a raw downloaded configuration so it is usable by the target core.
-pub fn patch_config(core: Core, web: &WebConfig, config: &str) -> String {
+/// With `geo_ipv6`, Mihomo gets a rule provider for the IPv6 list downloaded
+/// next to the geo databases. `ui_url` is where a core that manages the
+/// dashboard itself downloads it from.
+pub fn patch_config(
+    core: Core,
+    web: &WebConfig,
+    mixed_port: u16,
+    dns: &DnsConfig,
+    geo_ipv6: bool,
+    ui_url: Option<&str>,
+    config: &str,
+) -> String {
     match core {
         Core::Mihomo => {
+            let config = set_yaml_dns(&set_yaml_mixed_port(config, mixed_port), dns);
+            let config = patch_secret(core, &web.secret, &config);
+            let config = if geo_ipv6 {
+                add_yaml_ipv6_provider(&config)
+            } else {
+                config
+            };
+            let config = match ui_url {
+                Some(url) => set_yaml_external_ui(&config, web.ui_name(), url),
+                None => config,
+            };
             let has_tun = config.lines().any(|i| i.starts_with("tun"));
             if has_tun {
-                config.to_string()
+                config
             } else {
                 format!("{}\n{}", config, MIHOMO_TUN_YAML)
             }
         }
-        Core::Clash => config.replace("- 'RULE-SET,", "#- 'RULE-SET,").to_string(),
-        Core::Singbox => patch_singbox(web, config),
+        Core::Clash => {
+            let config = config.replace("- 'RULE-SET,", "#- 'RULE-SET,");
+            let config = set_yaml_dns(&set_yaml_mixed_port(&config, mixed_port), dns);
+            patch_secret(core, &web.secret, &config)
+        }
+        Core::Singbox => patch_singbox(web, mixed_port, dns, ui_url, config),
     }
 }
 
-/// Patch a Singbox JSON configuration: coerce string `server_port` values to
-/// numbers and merge in the clash_api / external_ui block from the web config.
-fn patch_singbox(web: &WebConfig, config: &str) -> String {
-    let Ok(mut v) = serde_json::from_str::<Value>(config) else {
+/// Render the config written on first install for `core`.
+pub fn default_config(
+    core: Core,
+    web: &WebConfig,
+    mixed_port: u16,
+    dns: &DnsConfig,
+    ui_url: Option<&str>,
+) -> String {
+    match core {
+        Core::Mihomo | Core::Clash => {
+            let config = set_yaml_dns(&set_yaml_mixed_port(MIHOMO_DEFAULT_YAML, mixed_port), dns);
+            let config = patch_secret(core, &web.secret, &config);
+            match ui_url {
+                Some(url) => set_yaml_external_ui(&config, web.ui_name(), url),
+                None => config,
+            }
+        }
+        Core::Singbox => patch_singbox(web, mixed_port, dns, ui_url, SINGBOX_DEFAULT_JSON),
+    }
+}
+
+/// Set the controller secret of an already patched config, leaving the rest
+/// of it untouched. An empty secret keeps whatever a YAML config declares.
+pub fn patch_secret(core: Core, secret: &str, config: &str) -> String {
+    match core {
+        Core::Mihomo | Core::Clash if secret.is_empty() => config.to_string(),
+        Core::Mihomo | Core::Clash => {
+            // A JSON string is a valid double-quoted YAML scalar.
+            let entry = format!("secret: {}", Value::from(secret));
+            let mut lines: Vec<String> = config
+                .lines()
+                .filter(|line| yaml_top_level_value(line, "secret").is_none())
+                .map(str::to_string)
+                .collect();
+            lines.push(entry);
+
+            let mut out = lines.join("\n");
+            if config.is_empty() || config.ends_with('\n') {
+                out.push('\n');
+            }
+            out
+        }
+        Core::Singbox => {
+            let Ok(mut v) = serde_json::from_str::<Value>(config) else {
+                return config.to_string();
+            };
+            merge_json(
+                &mut v,
+                &json!({ "experimental": { "clash_api": { "secret": secret } } }),
+            );
+            serde_json::to_string_pretty(&v).unwrap_or_else(|_| config.to_string())
+        }
+    }
+}
+
+/// Set the top-level `mixed-port` of a YAML config, appending it when absent.
+/// Other top-level port entries on the same port are dropped so the core
+/// doesn't try to bind it twice.
+fn set_yaml_mixed_port(config: &str, port: u16) -> String {
+    let entry = format!("mixed-port: {}", port);
+    let port_str = port.to_string();
+    let mut found = false;
+    let mut lines = Vec::new();
+
+    for line in config.lines() {
+        if yaml_top_level_value(line, "mixed-port").is_some() {
+            if !found {
+                lines.push(entry.clone());
+                found = true;
+            }
+            continue;
+        }
+        let clashes = YAML_PORT_KEYS
+            .iter()
+            .any(|key| yaml_top_level_value(line, key) == Some(port_str.as_str()));
+        if !clashes {
+            lines.push(line.to_string());
+        }
+    }
+    if !found {
+        lines.push(entry);
+    }
+
+    let mut out = lines.join("\n");
+    if config.is_empty() || config.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Set the top-level `external-ui` directory and the `external-ui-url` the
+/// core downloads it from, replacing any existing entries.
+fn set_yaml_external_ui(config: &str, ui: &str, url: &str) -> String {
+    const KEYS: [&str; 2] = ["external-ui", "external-ui-url"];
+    let mut lines: Vec<String> = config
+        .lines()
+        .filter(|line| {
+            !KEYS
+                .iter()
+                .any(|key| yaml_top_level_value(line, key).is_some())
+        })
+        .map(str::to_string)
+        .collect();
+    lines.push(format!("external-ui: {}", ui));
+    lines.push(format!("external-ui-url: \"{}\"", url));
+
+    let mut out = lines.join("\n");
+    if config.is_empty() || config.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Add the `china_ipv6` rule provider (the downloaded IPv6 list, usable in
+/// rules as `RULE-SET,china_ipv6,DIRECT`) to the top-level `rule-providers:`
+/// block, creating the block when absent. A provider already named so is
+/// left alone.
+fn add_yaml_ipv6_provider(config: &str) -> String {
+    let name = CHINA_IPV6_PROVIDER;
+    let mut lines: Vec<String> = config.lines().map(str::to_string).collect();
+    let block = lines
+        .iter()
+        .position(|line| yaml_top_level_value(line, "rule-providers") == Some(""));
+    let provider = |indent: usize| {
+        let pad = " ".repeat(indent);
+        vec![
+            format!("{}{}:", pad, name),
+            format!("{}{}type: file", pad, pad),
+            format!("{}{}behavior: ipcidr", pad, pad),
+            format!("{}{}format: text", pad, pad),
+            format!("{}{}path: ./{}", pad, pad, CHINA_IPV6_LIST),
+        ]
+    };
+
+    match block {
+        Some(start) => {
+            let entries = lines[start + 1..]
+                .iter()
+                .take_while(|line| line.starts_with([' ', '\t']) || line.trim().is_empty());
+            if entries
+                .clone()
+                .any(|line| yaml_top_level_value(line.trim_start(), name).is_some())
+            {
+                return config.to_string();
+            }
+            // Match the indentation of the existing entries.
+            let indent = entries
+                .map(|line| line.len() - line.trim_start().len())
+                .find(|&indent| indent > 0)
+                .unwrap_or(2);
+            lines.splice(start + 1..start + 1, provider(indent));
+        }
+        None => {
+            lines.push("rule-providers:".to_string());
+            lines.extend(provider(2));
+        }
+    }
+
+    let mut out = lines.join("\n");
+    if config.is_empty() || config.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Apply DNS overrides to the top-level `dns:` block of a YAML config,
+/// creating the block when absent. Only the overridden keys are replaced;
+/// the rest of an existing block is kept.
+fn set_yaml_dns(config: &str, dns: &DnsConfig) -> String {
+    if dns.is_empty() {
         return config.to_string();
+    }
+
+    let mut lines: Vec<String> = config.lines().map(str::to_string).collect();
+    let start = match lines
+        .iter()
+        .position(|l| yaml_top_level_value(l, "dns").is_some())
+    {
+        Some(start) => start,
+        None => {
+            lines.push("dns:".to_string());
+            lines.push("  enable: true".to_string());
+            lines.len() - 2
+        }
     };
+    let end = lines[start + 1..]
+        .iter()
+        .position(|l| !l.is_empty() && !l.starts_with([' ', '\t', '#']))
+        .map_or(lines.len(), |i| start + 1 + i);
+
+    let indent = lines[start + 1..end]
+        .iter()
+        .find(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .map(|l| l[..l.len() - l.trim_start().len()].to_string())
+        .unwrap_or_else(|| "  ".to_string());
 
-    // Some providers emit `server_port` as a string; sing-box requires a
-    // number. Coerce any string ports to numbers.
-    //   FATAL[0000] outbounds[5].server_port: json: cannot unmarshal string
-    //   into Go value of type uint16
-    if let Some(outbounds) = v.get_mut("outbounds").and_then(|o| o.as_array_mut()) {
-        for item in outbounds {
-            if let Some(port_val) = item.get_mut("server_port")
-                && let Some(port_str) = port_val.as_str()
-                && let Ok(port_num) = port_str.parse::<u64>()
+    let mut overrides = Vec::new();
+    if let Some(mode) = dns.enhanced_mode {
+        overrides.push((
+            "enhanced-mode",
+            vec![format!("{}enhanced-mode: {}", indent, mode)],
+        ));
+    }
+    for (key, servers) in [("nameserver", &dns.nameserver), ("fallback", &dns.fallback)] {
+        if servers.is_empty() {
+            continue;
+        }
+        let mut entry = vec![format!("{}{}:", indent, key)];
+        entry.extend(servers.iter().map(|s| format!("{}  - {}", indent, s)));
+        overrides.push((key, entry));
+    }
+
+    // Drop the overridden keys (and their nested list items) from the block.
+    let mut block = Vec::new();
+    let mut skipping = false;
+    for line in &lines[start + 1..end] {
+        let is_child = line.starts_with(&indent) && !line[indent.len()..].starts_with([' ', '\t']);
+        if is_child {
+            let child = &line[indent.len()..];
+            if overrides
+                .iter()
+                .any(|(key, _)| yaml_top_level_value(child, key).is_some())
             {
-                *port_val = json!(port_num);
+                skipping = true;
+                continue;
+            }
+            if !(skipping && child.starts_with('-')) {
+                skipping = false;
             }
         }
+        if !skipping {
+            block.push(line.clone());
+        }
     }
 
+    let mut out = lines[..=start].to_vec();
+    out.extend(overrides.into_iter().flat_map(|(_, entry)| entry));
+    out.extend(block);
+    out.extend_from_slice(&lines[end..]);
+
+    let mut out = out.join("\n");
+    if config.is_empty() || config.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Prepend the DNS override nameservers to Singbox `dns.servers` and make the
+/// first one `dns.final`. Singbox has no fallback group or enhanced mode, so
+/// those overrides only apply to Mihomo/Clash.
+fn set_singbox_dns(v: &mut Value, dns: &DnsConfig) {
+    if dns.nameserver.is_empty() {
+        return;
+    }
+    let Some(root) = v.as_object_mut() else {
+        return;
+    };
+    let Some(dns_obj) = root
+        .entry("dns")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+    else {
+        return;
+    };
+
+    let mut servers: Vec<Value> = dns
+        .nameserver
+        .iter()
+        .enumerate()
+        .map(|(i, address)| json!({ "tag": format!("crash-dns-{}", i), "address": address }))
+        .collect();
+    if let Some(existing) = dns_obj.get("servers").and_then(Value::as_array) {
+        servers.extend(
+            existing
+                .iter()
+                .filter(|s| {
+                    !s.get("tag")
+                        .and_then(Value::as_str)
+                        .is_some_and(|tag| tag.starts_with("crash-dns-"))
+                })
+                .cloned(),
+        );
+    }
+
+    dns_obj.insert("servers".to_string(), Value::Array(servers));
+    dns_obj.insert("final".to_string(), json!("crash-dns-0"));
+}
+
+/// Point the first `mixed` inbound at `port`, or add one when no inbound
+/// exists with that type. Nothing is added if another inbound already
+/// listens on `port`.
+fn set_singbox_mixed_port(v: &mut Value, port: u16) {
+    let Some(root) = v.as_object_mut() else {
+        return;
+    };
+    let Some(inbounds) = root
+        .entry("inbounds")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+    else {
+        return;
+    };
+
+    if let Some(mixed) = inbounds
+        .iter_mut()
+        .find(|i| i.get("type").and_then(Value::as_str) == Some("mixed"))
+    {
+        mixed["listen_port"] = json!(port);
+        return;
+    }
+
+    let taken = inbounds
+        .iter()
+        .any(|i| i.get("listen_port").and_then(Value::as_u64) == Some(port as u64));
+    if !taken {
+        inbounds.push(json!({
+            "type": "mixed",
+            "tag": "mixed-in",
+            "listen": "::",
+            "listen_port": port
+        }));
+    }
+}
+
+/// Patch a Singbox JSON configuration: coerce string `server_port` values to
+/// numbers, ensure the mixed inbound, apply DNS overrides and merge in the clash_api / external_ui
+/// block from the web config.
+fn patch_singbox(
+    web: &WebConfig,
+    mixed_port: u16,
+    dns: &DnsConfig,
+    ui_url: Option<&str>,
+    config: &str,
+) -> String {
+    let Ok(mut v) = serde_json::from_str::<Value>(config) else {
+        return config.to_string();
+    };
+
+    for invalid in coerce_singbox_ports(&mut v) {
+        log_warn!("Invalid Singbox port left unchanged: {}", invalid);
+    }
+
+    set_singbox_mixed_port(&mut v, mixed_port);
+    set_singbox_dns(&mut v, dns);
+
     let ui = web.ui.to_string();
     let secret = web.secret.to_string();
     let patch = json!({
@@ -57,17 +426,69 @@ fn patch_singbox(web: &WebConfig, config: &str) -> String {
                 "enabled": true
             },
             "clash_api": {
-                "external_controller": ":9090",
+                "external_controller": web.host,
                 "external_ui": ui,
                 "secret": secret
             }
         }
     });
     merge_json(&mut v, &patch);
+    if let Some(url) = ui_url {
+        merge_json(
+            &mut v,
+            &json!({ "experimental": { "clash_api": { "external_ui_download_url": url } } }),
+        );
+    }
 
     serde_json::to_string_pretty(&v).unwrap_or_else(|_| config.to_string())
 }
 
+/// Some providers emit `server_port` as a string; sing-box requires a
+/// number:
+///   FATAL[0000] outbounds[5].server_port: json: cannot unmarshal string
+///   into Go value of type uint16
+/// Coerce every string `server_port` anywhere in `v` (detours and other
+/// nested objects included) to a number. Values that are not a port in
+/// 1-65535 are left as they are and returned as `path: value`.
+pub fn coerce_singbox_ports(v: &mut Value) -> Vec<String> {
+    let mut invalid = Vec::new();
+    coerce_ports_at(v, "", &mut invalid);
+    invalid
+}
+
+fn coerce_ports_at(v: &mut Value, path: &str, invalid: &mut Vec<String>) {
+    match v {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                if key == "server_port" && !child.is_object() && !child.is_array() {
+                    let port = match &*child {
+                        Value::String(s) => s.trim().parse::<u16>().ok(),
+                        Value::Number(n) => n.as_u64().and_then(|n| u16::try_from(n).ok()),
+                        _ => None,
+                    };
+                    match port {
+                        Some(port) if port > 0 => *child = json!(port),
+                        _ => invalid.push(format!("{}: {}", child_path, child)),
+                    }
+                } else {
+                    coerce_ports_at(child, &child_path, invalid);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                coerce_ports_at(item, &format!("{}[{}]", path, i), invalid);
+            }
+        }
+        _ => {}
+    }
+}
+
 /// Recursively merge `src` into `dst`. For objects, matching keys are merged
 /// recursively; non-matching keys are inserted. For any non-object value
 /// (or when the types disagree), `src` replaces `dst`.
@@ -93,15 +514,29 @@ fn merge_json(dst: &mut Value, src: &Value) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::dns::EnhancedMode;
+    use crate::config::ports::http_proxy_port;
 
     fn web() -> WebConfig {
         WebConfig::default()
     }
 
+    fn no_dns() -> DnsConfig {
+        DnsConfig::default()
+    }
+
+    fn dns() -> DnsConfig {
+        DnsConfig {
+            nameserver: vec!["223.5.5.5".to_string(), "tls://1.1.1.1".to_string()],
+            fallback: vec![],
+            enhanced_mode: Some(EnhancedMode::FakeIp),
+        }
+    }
+
     #[test]
     fn mihomo_appends_tun_when_absent() {
         let input = "port: 7890\n";
-        let out = patch_config(Core::Mihomo, &web(), input);
+        let out = patch_config(Core::Mihomo, &web(), 7891, &no_dns(), false, None, input);
         assert!(out.contains("tun:"));
         assert!(out.starts_with("port: 7890"));
     }
@@ -109,22 +544,139 @@ mod tests {
     #[test]
     fn mihomo_keeps_existing_tun() {
         let input = "tun:\n  enable: false\n";
-        let out = patch_config(Core::Mihomo, &web(), input);
+        let out = patch_config(Core::Mihomo, &web(), 7890, &no_dns(), false, None, input);
         // Should not append the default tun block since one already exists.
         assert!(!out.contains("device: Meta"));
     }
 
+    #[test]
+    fn mihomo_adds_ipv6_rule_provider() {
+        let provider = "  china_ipv6:\n    type: file\n    behavior: ipcidr\n    format: text\n    path: ./china_ipv6_list.txt\n";
+
+        let out = patch_config(
+            Core::Mihomo,
+            &web(),
+            7890,
+            &no_dns(),
+            true,
+            None,
+            "tun:\n  enable: false\n",
+        );
+        assert!(out.contains(&format!("rule-providers:\n{}", provider)));
+
+        let input = "rule-providers:\n    cn:\n        type: http\nrules:\n  - MATCH,DIRECT\n";
+        let out = add_yaml_ipv6_provider(input);
+        assert!(out.starts_with("rule-providers:\n    china_ipv6:\n        type: file\n"));
+        assert!(out.contains("    cn:\n        type: http\nrules:\n"));
+        assert!(out.ends_with('\n'));
+        // Idempotent.
+        assert_eq!(add_yaml_ipv6_provider(&out), out);
+
+        let out = patch_config(
+            Core::Clash,
+            &web(),
+            7890,
+            &no_dns(),
+            true,
+            None,
+            "mixed-port: 7890\n",
+        );
+        assert!(!out.contains("china_ipv6"));
+    }
+
+    #[test]
+    fn core_managed_ui_sets_the_download_url() {
+        let url = "https://example.com/metacubexd.tgz";
+        let input = "external-ui: ui\nexternal-ui-url: https://old.example\nmode: rule\n";
+        let out = patch_config(
+            Core::Mihomo,
+            &web(),
+            7890,
+            &no_dns(),
+            false,
+            Some(url),
+            input,
+        );
+        assert!(out.starts_with("mode: rule\n"));
+        assert!(out.contains(&format!(
+            "external-ui: {}\nexternal-ui-url: \"{}\"\n",
+            web().ui_name(),
+            url
+        )));
+        assert_eq!(out.matches("external-ui:").count(), 1);
+
+        let out = patch_config(
+            Core::Singbox,
+            &web(),
+            7890,
+            &no_dns(),
+            false,
+            Some(url),
+            "{}",
+        );
+        let v: Value = serde_json::from_str(&out).unwrap();
+        let clash_api = &v["experimental"]["clash_api"];
+        assert_eq!(clash_api["external_ui_download_url"], json!(url));
+        assert_eq!(clash_api["external_ui"], json!(web().ui_name()));
+    }
+
+    #[test]
+    fn secret_is_replaced_in_place() {
+        let yaml = "mixed-port: 7890\nsecret: old # from the provider\nmode: rule\n";
+        assert_eq!(
+            patch_secret(Core::Mihomo, "n\"ew", yaml),
+            "mixed-port: 7890\nmode: rule\nsecret: \"n\\\"ew\"\n"
+        );
+        assert_eq!(patch_secret(Core::Clash, "", yaml), yaml);
+
+        let json = r#"{"experimental":{"clash_api":{"secret":"old","external_ui":"ui"}}}"#;
+        let v: Value = serde_json::from_str(&patch_secret(Core::Singbox, "new", json)).unwrap();
+        assert_eq!(v["experimental"]["clash_api"]["secret"], "new");
+        assert_eq!(v["experimental"]["clash_api"]["external_ui"], "ui");
+    }
+
     #[test]
     fn clash_disables_rule_set() {
         let input = "rules:\n- 'RULE-SET,cn,/path'\n";
-        let out = patch_config(Core::Clash, &web(), input);
+        let out = patch_config(Core::Clash, &web(), 7890, &no_dns(), false, None, input);
         assert!(out.contains("#- 'RULE-SET,cn,/path'"));
     }
 
+    #[test]
+    fn singbox_coerces_nested_ports_and_reports_invalid_ones() {
+        let mut v = json!({
+            "outbounds": [
+                {"type": "selector", "outbounds": ["a", "b"]},
+                {"type": "shadowsocks", "server_port": " 8388 ",
+                 "detour": {"type": "socks", "server_port": "1080"}},
+                {"type": "vmess", "server_port": "abc"},
+                {"type": "trojan", "server_port": 70000}
+            ],
+            "endpoints": [{"peers": [{"server_port": "51820"}]}]
+        });
+
+        let invalid = coerce_singbox_ports(&mut v);
+        assert_eq!(v["outbounds"][1]["server_port"], json!(8388));
+        assert_eq!(v["outbounds"][1]["detour"]["server_port"], json!(1080));
+        assert_eq!(v["endpoints"][0]["peers"][0]["server_port"], json!(51820));
+        assert_eq!(v["outbounds"][0]["outbounds"], json!(["a", "b"]));
+        assert_eq!(
+            invalid,
+            vec![
+                "outbounds[2].server_port: \"abc\"".to_string(),
+                "outbounds[3].server_port: 70000".to_string(),
+            ]
+        );
+        assert_eq!(
+            coerce_singbox_ports(&mut json!({"server_port": "0"})).len(),
+            1
+        );
+    }
+
     #[test]
     fn singbox_coerces_string_server_port() {
         let input = r#"{"outbounds":[{"type":"socks","server_port":"1080"}]}"#;
-        let out = patch_config(Core::Singbox, &web(), input);
+        let out = patch_config(Core::Singbox, &web(), 7890, &no_dns(), false, None, input);
         let v: Value = serde_json::from_str(&out).expect("output is valid json");
         assert_eq!(v["outbounds"][0]["server_port"], json!(1080));
     }
@@ -132,10 +684,67 @@ mod tests {
     #[test]
     fn singbox_invalid_json_returned_unchanged() {
         let input = "not json";
-        let out = patch_config(Core::Singbox, &web(), input);
+        let out = patch_config(Core::Singbox, &web(), 7890, &no_dns(), false, None, input);
         assert_eq!(out, input);
     }
 
+    #[test]
+    fn mihomo_sets_mixed_port_and_drops_clashing_port() {
+        let input = "port: 7890\nsocks-port: 7891\nmixed-port: 7893\n";
+        let out = set_yaml_mixed_port(input, 7890);
+        assert_eq!(out, "socks-port: 7891\nmixed-port: 7890\n");
+    }
+
+    #[test]
+    fn singbox_updates_existing_mixed_inbound() {
+        let input = r#"{"inbounds":[{"type":"tun"},{"type":"mixed","listen_port":1}]}"#;
+        let out = patch_config(Core::Singbox, &web(), 2080, &no_dns(), false, None, input);
+        let v: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(v["inbounds"].as_array().unwrap().len(), 2);
+        assert_eq!(v["inbounds"][1]["listen_port"], json!(2080));
+    }
+
+    #[test]
+    fn default_config_uses_configured_port() {
+        for core in [Core::Mihomo, Core::Clash, Core::Singbox] {
+            for port in [core.default_ports().mixed, 12345] {
+                let out = default_config(core, &web(), port, &no_dns(), None);
+                assert_eq!(http_proxy_port(core, &out), Some(port), "{core}");
+            }
+        }
+    }
+
+    #[test]
+    fn yaml_dns_replaces_only_overridden_keys() {
+        let input = "port: 1\ndns:\n  enable: true\n  nameserver:\n    - 8.8.8.8\n  fallback: [1.0.0.1]\nrules:\n  - MATCH,DIRECT\n";
+        let out = set_yaml_dns(input, &dns());
+        assert_eq!(
+            out,
+            "port: 1\ndns:\n  enhanced-mode: fake-ip\n  nameserver:\n    - 223.5.5.5\n    - tls://1.1.1.1\n  enable: true\n  fallback: [1.0.0.1]\nrules:\n  - MATCH,DIRECT\n"
+        );
+    }
+
+    #[test]
+    fn yaml_dns_block_added_when_absent() {
+        let out = set_yaml_dns("port: 1\n", &dns());
+        assert!(out.starts_with("port: 1\ndns:\n  enhanced-mode: fake-ip\n  nameserver:\n"));
+        assert!(out.contains("  enable: true\n"));
+        assert_eq!(set_yaml_dns("port: 1\n", &no_dns()), "port: 1\n");
+    }
+
+    #[test]
+    fn singbox_dns_servers_prepended_once() {
+        let input = r#"{"dns":{"servers":[{"tag":"remote","address":"8.8.8.8"}]}}"#;
+        let once = patch_config(Core::Singbox, &web(), 2080, &dns(), false, None, input);
+        let twice = patch_config(Core::Singbox, &web(), 2080, &dns(), false, None, &once);
+        let v: Value = serde_json::from_str(&twice).unwrap();
+        let servers = v["dns"]["servers"].as_array().unwrap();
+        assert_eq!(servers.len(), 3);
+        assert_eq!(servers[0]["address"], json!("223.5.5.5"));
+        assert_eq!(servers[2]["tag"], json!("remote"));
+        assert_eq!(v["dns"]["final"], json!("crash-dns-0"));
+    }
+
     #[test]
     fn merge_json_deep_merge() {
         let mut dst = json!({"a": {"b": 1, "c": 2}});
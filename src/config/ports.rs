@@ -0,0 +1,194 @@
+// Discovery of the local proxy ports declared in a core configuration.
+//
+// Only the handful of top-level keys needed to reach the proxy are read,
+// so a full YAML parser isn't required for Mihomo/Clash configs.
+
+use super::core::Core;
+use serde_json::Value;
+
+/// Local ports a core listens on by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefaultPorts {
+    /// Mixed HTTP + SOCKS proxy inbound.
+    pub mixed: u16,
+    /// External controller (dashboard API).
+    pub controller: u16,
+}
+
+/// Proxy inbound ports declared in a core config, by kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListenPorts {
+    /// Mixed HTTP + SOCKS inbound (`mixed-port`, a `mixed` inbound).
+    pub mixed: Option<u16>,
+    /// HTTP inbound (`port`, an `http` inbound).
+    pub http: Option<u16>,
+    /// SOCKS inbound (`socks-port`, a `socks` inbound).
+    pub socks: Option<u16>,
+    /// Any other inbound, e.g. redir/tproxy or further inbounds of a kind.
+    pub other: Vec<u16>,
+}
+
+impl ListenPorts {
+    /// Read the inbound ports from the core config `content`: the
+    /// top-level port keys for Mihomo/Clash, each inbound's `listen_port`
+    /// for Singbox. Port 0 (disabled) is skipped.
+    pub fn parse(core: Core, content: &str) -> Self {
+        let mut ports = ListenPorts::default();
+        match core {
+            Core::Mihomo | Core::Clash => {
+                let port = |key| yaml_top_level_port(content, key).filter(|&port| port != 0);
+                ports.mixed = port("mixed-port");
+                ports.http = port("port");
+                ports.socks = port("socks-port");
+                ports.other = ["redir-port", "tproxy-port"]
+                    .into_iter()
+                    .filter_map(port)
+                    .collect();
+            }
+            Core::Singbox => {
+                let Ok(v) = serde_json::from_str::<Value>(content) else {
+                    return ports;
+                };
+                let inbounds = v["inbounds"].as_array().into_iter().flatten();
+                for inbound in inbounds {
+                    let Some(port) = inbound["listen_port"]
+                        .as_u64()
+                        .and_then(|port| u16::try_from(port).ok())
+                    else {
+                        continue;
+                    };
+                    let slot = match inbound["type"].as_str() {
+                        Some("mixed") => &mut ports.mixed,
+                        Some("http") => &mut ports.http,
+                        Some("socks") => &mut ports.socks,
+                        _ => {
+                            ports.other.push(port);
+                            continue;
+                        }
+                    };
+                    if slot.is_none() {
+                        *slot = Some(port);
+                    } else {
+                        ports.other.push(port);
+                    }
+                }
+            }
+        }
+        ports
+    }
+
+    /// Port a client can use as an HTTP proxy: the mixed inbound, falling
+    /// back to the HTTP one.
+    pub fn http_proxy(&self) -> Option<u16> {
+        self.mixed.or(self.http)
+    }
+
+    /// Port a client can use as a SOCKS proxy: the mixed inbound, falling
+    /// back to the SOCKS one.
+    pub fn socks_proxy(&self) -> Option<u16> {
+        self.mixed.or(self.socks)
+    }
+
+    /// Every declared port.
+    pub fn all(&self) -> Vec<u16> {
+        [self.mixed, self.http, self.socks]
+            .into_iter()
+            .flatten()
+            .chain(self.other.iter().copied())
+            .collect()
+    }
+}
+
+/// Find the port of the local HTTP-capable proxy inbound (`mixed-port`,
+/// falling back to `port`, for Mihomo/Clash; a `mixed` or `http` inbound
+/// for Singbox) in the core config `content`.
+pub fn http_proxy_port(core: Core, content: &str) -> Option<u16> {
+    ListenPorts::parse(core, content).http_proxy()
+}
+
+/// Every local port the core config `content` listens on.
+pub fn listen_ports(core: Core, content: &str) -> Vec<u16> {
+    ListenPorts::parse(core, content).all()
+}
+
+/// Port of a controller address such as `:9090` or `127.0.0.1:9090`.
+pub fn host_port(host: &str) -> Option<u16> {
+    host.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Read a top-level `key: <port>` entry from a YAML document.
+fn yaml_top_level_port(content: &str, key: &str) -> Option<u16> {
+    content
+        .lines()
+        .find_map(|line| yaml_top_level_value(line, key))
+        .and_then(|value| value.parse().ok())
+}
+
+/// Value of `line` if it is a top-level `key: value` YAML entry, with any
+/// trailing comment and quotes removed.
+pub(crate) fn yaml_top_level_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let value = line.strip_prefix(key)?.trim_start().strip_prefix(':')?;
+    let value = value.split('#').next().unwrap_or_default();
+    Some(value.trim().trim_matches(|c| c == '"' || c == '\''))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mihomo_prefers_mixed_port() {
+        let config = "port: 7890\nsocks-port: 7891\nmixed-port: 7893 # all-in-one\n";
+        assert_eq!(http_proxy_port(Core::Mihomo, config), Some(7893));
+    }
+
+    #[test]
+    fn mihomo_falls_back_to_http_port() {
+        assert_eq!(http_proxy_port(Core::Mihomo, "port: 7890\n"), Some(7890));
+        assert_eq!(http_proxy_port(Core::Clash, "tun:\n  port: 1\n"), None);
+    }
+
+    #[test]
+    fn collects_listen_ports() {
+        let config = "port: 7890\nsocks-port: 7891\nmixed-port: 7893\nredir-port: 0\n";
+        assert_eq!(listen_ports(Core::Mihomo, config), vec![7893, 7890, 7891]);
+
+        let config = r#"{"inbounds":[{"type":"tun"},{"type":"mixed","listen_port":2080}]}"#;
+        assert_eq!(listen_ports(Core::Singbox, config), vec![2080]);
+
+        assert_eq!(host_port(":9090"), Some(9090));
+        assert_eq!(host_port("[::1]:9091"), Some(9091));
+        assert_eq!(host_port("localhost"), None);
+    }
+
+    #[test]
+    fn listen_ports_by_kind() {
+        let config = "socks-port: 7891\nport: 7890\ntproxy-port: 7895\n";
+        let ports = ListenPorts::parse(Core::Mihomo, config);
+        assert_eq!(ports.mixed, None);
+        assert_eq!(ports.http_proxy(), Some(7890));
+        assert_eq!(ports.socks_proxy(), Some(7891));
+        assert_eq!(ports.other, [7895]);
+
+        let config = r#"{"inbounds":[
+            {"type":"socks","listen_port":1080},
+            {"type":"mixed","listen_port":2080},
+            {"type":"mixed","listen_port":2081}
+        ]}"#;
+        let ports = ListenPorts::parse(Core::Singbox, config);
+        assert_eq!(ports.socks_proxy(), Some(2080));
+        assert_eq!(ports.all(), [2080, 1080, 2081]);
+        assert_eq!(ListenPorts::parse(Core::Mihomo, ""), ListenPorts::default());
+    }
+
+    #[test]
+    fn singbox_reads_mixed_inbound() {
+        let config = r#"{"inbounds":[
+            {"type":"tun","tag":"tun-in"},
+            {"type":"socks","listen_port":1080},
+            {"type":"mixed","listen_port":2080}
+        ]}"#;
+        assert_eq!(http_proxy_port(Core::Singbox, config), Some(2080));
+        assert_eq!(http_proxy_port(Core::Singbox, "not json"), None);
+    }
+}
@@ -0,0 +1,289 @@
+// Pre-download of the remote providers a Mihomo/Clash config references.
+//
+// `proxy-providers` and `rule-providers` of `type: http` are fetched by the
+// core on first use, which fails when the provider host is blocked and the
+// core has no working proxy yet. `update-url --prefetch-providers` fetches
+// them through crash's own downloader into the `path` the core reads them
+// from, so the core starts with them already on disk.
+
+use super::CrashConfig;
+use super::core::Core;
+use super::get_config_dir;
+use super::ports::yaml_top_level_value;
+use crate::error::{CrashError, Result};
+use crate::utils::download::download_file;
+use crate::utils::fs::ensure_dir;
+use crate::{log_info, log_warn};
+use std::path::{Component, Path, PathBuf};
+use strum::Display;
+
+/// Which top-level block a provider was declared in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ProviderKind {
+    Proxy,
+    Rule,
+}
+
+impl ProviderKind {
+    const ALL: [ProviderKind; 2] = [ProviderKind::Proxy, ProviderKind::Rule];
+
+    fn block(self) -> &'static str {
+        match self {
+            ProviderKind::Proxy => "proxy-providers",
+            ProviderKind::Rule => "rule-providers",
+        }
+    }
+}
+
+/// An `http` provider of a core config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provider {
+    pub kind: ProviderKind,
+    pub name: String,
+    pub url: String,
+    /// File the core caches the provider in, relative to its home directory.
+    pub path: Option<String>,
+}
+
+impl Provider {
+    /// Where the core looks for the provider under `home`. `None` without a
+    /// `path` (the core then derives one from a hash of the URL) or when it
+    /// points outside `home`, which the core refuses as well.
+    pub fn local_path(&self, home: &Path) -> Option<PathBuf> {
+        let path = Path::new(self.path.as_deref()?);
+        let inside = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        let relative: PathBuf = path
+            .components()
+            .filter(|&c| c != Component::CurDir)
+            .collect();
+        inside.then(|| home.join(relative))
+    }
+}
+
+/// Every `type: http` entry of the top-level `proxy-providers` and
+/// `rule-providers` blocks of a YAML config, in declaration order. Both the
+/// block (`url:` on its own line) and the flow (`{type: http, url: …}`)
+/// style are read; nested keys such as `health-check.url` are not.
+pub fn parse_providers(content: &str) -> Vec<Provider> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut providers = Vec::new();
+    for kind in ProviderKind::ALL {
+        let Some(start) = lines
+            .iter()
+            .position(|line| yaml_top_level_value(line, kind.block()) == Some(""))
+        else {
+            continue;
+        };
+        let block = lines[start + 1..]
+            .iter()
+            .filter(|line| !is_blank(line))
+            .take_while(|line| line.starts_with([' ', '\t']));
+
+        let mut entry_indent = None;
+        let mut field_indent = None;
+        let mut current: Option<(String, Vec<(String, String)>)> = None;
+        for line in block {
+            let indent = line.len() - line.trim_start().len();
+            let Some((key, value)) = split_entry(line.trim_start()) else {
+                continue;
+            };
+            if *entry_indent.get_or_insert(indent) == indent {
+                providers.extend(
+                    current
+                        .take()
+                        .and_then(|(name, fields)| provider(kind, name, &fields)),
+                );
+                let fields = match value.strip_prefix('{') {
+                    Some(flow) => flow
+                        .trim_end_matches('}')
+                        .split(',')
+                        .filter_map(|field| split_entry(field.trim()))
+                        .map(|(key, value)| (key, scalar(&value)))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                current = Some((key, fields));
+                field_indent = None;
+            } else if let Some((_, fields)) = current.as_mut()
+                && *field_indent.get_or_insert(indent) == indent
+            {
+                fields.push((key, scalar(&value)));
+            }
+        }
+        providers.extend(current.and_then(|(name, fields)| provider(kind, name, &fields)));
+    }
+    providers
+}
+
+fn provider(kind: ProviderKind, name: String, fields: &[(String, String)]) -> Option<Provider> {
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .filter(|v| !v.is_empty())
+    };
+    if field("type").as_deref() != Some("http") {
+        return None;
+    }
+    Some(Provider {
+        kind,
+        name,
+        url: field("url")?,
+        path: field("path"),
+    })
+}
+
+fn is_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// Split `key: value` (or a bare `key:`), unquoting the key.
+fn split_entry(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(':')?;
+    if !value.is_empty() && !value.starts_with([' ', '\t']) {
+        return None;
+    }
+    let key = key.trim().trim_matches(|c| c == '"' || c == '\'');
+    Some((key.to_string(), value.trim().to_string()))
+}
+
+/// A plain or quoted YAML scalar without its trailing comment. Unlike
+/// `yaml_top_level_value`, a `#` inside a URL is kept.
+fn scalar(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(rest) = value.strip_prefix(quote) {
+            return rest.split(quote).next().unwrap_or_default().to_string();
+        }
+    }
+    value
+        .split(" #")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+impl CrashConfig {
+    /// Download the http providers of the core config into the paths the
+    /// core reads them from. Each provider's outcome is returned; a failed
+    /// one is left for the core to fetch itself.
+    pub async fn prefetch_providers(&self) -> Result<Vec<(Provider, Result<PathBuf>)>> {
+        if self.core == Core::Singbox {
+            log_info!("Provider prefetch only applies to Mihomo/Clash configs");
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(self.core_config_path())?;
+        let home = get_config_dir();
+        let providers = parse_providers(&content);
+        if !providers.is_empty() {
+            self.check_free_space()?;
+        }
+
+        let mut results = Vec::new();
+        for provider in providers {
+            let result = self.prefetch_provider(&provider, &home).await;
+            match &result {
+                Ok(path) => log_info!(
+                    "Prefetched {} provider {:?} to {}",
+                    provider.kind,
+                    provider.name,
+                    path.display()
+                ),
+                Err(e) => log_warn!(
+                    "Could not prefetch {} provider {:?}: {}",
+                    provider.kind,
+                    provider.name,
+                    e
+                ),
+            }
+            results.push((provider, result));
+        }
+        Ok(results)
+    }
+
+    async fn prefetch_provider(&self, provider: &Provider, home: &Path) -> Result<PathBuf> {
+        let dest = provider.local_path(home).ok_or_else(|| {
+            CrashError::Config(match &provider.path {
+                Some(path) => format!("path {} is outside {}", path, home.display()),
+                None => "no path set, the core picks its own cache file".to_string(),
+            })
+        })?;
+        if let Some(parent) = dest.parent() {
+            ensure_dir(parent)?;
+        }
+        download_file(&provider.url, &dest, &self.download_options()).await?;
+        Ok(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_http_providers_of_both_blocks() {
+        let config = r#"
+mixed-port: 7890
+proxy-providers:
+  # comment
+  airport:
+    type: http
+    url: "https://sub.example.com/clash?token=a#b"
+    interval: 3600
+    path: ./proxy_providers/airport.yaml
+    health-check:
+      enable: true
+      url: https://www.gstatic.com/generate_204
+  local:
+    type: file
+    path: ./local.yaml
+  'no path': {type: http, url: https://example.org/p.yaml}
+rules:
+  - MATCH,DIRECT
+rule-providers:
+    reject:
+        type: http
+        behavior: domain
+        url: https://example.org/reject.txt # upstream list
+        path: ../outside.txt
+"#;
+        let providers = parse_providers(config);
+        assert_eq!(
+            providers,
+            [
+                Provider {
+                    kind: ProviderKind::Proxy,
+                    name: "airport".to_string(),
+                    url: "https://sub.example.com/clash?token=a#b".to_string(),
+                    path: Some("./proxy_providers/airport.yaml".to_string()),
+                },
+                Provider {
+                    kind: ProviderKind::Proxy,
+                    name: "no path".to_string(),
+                    url: "https://example.org/p.yaml".to_string(),
+                    path: None,
+                },
+                Provider {
+                    kind: ProviderKind::Rule,
+                    name: "reject".to_string(),
+                    url: "https://example.org/reject.txt".to_string(),
+                    path: Some("../outside.txt".to_string()),
+                },
+            ]
+        );
+
+        let home = Path::new("/home/crash");
+        assert_eq!(
+            providers[0].local_path(home),
+            Some(home.join("proxy_providers/airport.yaml"))
+        );
+        assert_eq!(providers[1].local_path(home), None);
+        assert_eq!(providers[2].local_path(home), None);
+        assert!(parse_providers("proxies: []\n").is_empty());
+    }
+}
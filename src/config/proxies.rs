@@ -0,0 +1,202 @@
+// Proxy group selection through the core's controller API.
+//
+// Mihomo, Clash and Singbox's clash_api all expose `GET /proxies` and
+// `PUT /proxies/<group>`, so node switching works the same for every core.
+
+use super::CrashConfig;
+use crate::error::{CrashError, Result};
+use crate::log_info;
+use serde_json::Value;
+
+/// Group listing every other group, in the order the config declares them.
+const GLOBAL_GROUP: &str = "GLOBAL";
+
+/// A group whose node can be chosen (a `Selector` in the API).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyGroup {
+    pub name: String,
+    /// Currently selected node.
+    pub now: String,
+    pub nodes: Vec<ProxyNode>,
+}
+
+/// A member of a proxy group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyNode {
+    pub name: String,
+    /// Last measured delay in milliseconds; `None` if never tested or it
+    /// timed out.
+    pub delay: Option<u64>,
+}
+
+/// Extract the selectable groups from a `GET /proxies` response, in config
+/// order (taken from `GLOBAL`), with `GLOBAL` itself last.
+pub fn parse_groups(body: &Value) -> Vec<ProxyGroup> {
+    let Some(proxies) = body.get("proxies").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    let delay = |name: &str| {
+        proxies
+            .get(name)
+            .and_then(|p| p.get("history"))
+            .and_then(Value::as_array)
+            .and_then(|history| history.last())
+            .and_then(|entry| entry.get("delay"))
+            .and_then(Value::as_u64)
+            .filter(|&ms| ms > 0)
+    };
+    let members = |proxy: &Value| -> Vec<String> {
+        proxy
+            .get("all")
+            .and_then(Value::as_array)
+            .map(|all| {
+                all.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let order = proxies.get(GLOBAL_GROUP).map(members).unwrap_or_default();
+    let mut groups: Vec<ProxyGroup> = proxies
+        .iter()
+        .filter(|(_, proxy)| proxy.get("type").and_then(Value::as_str) == Some("Selector"))
+        .map(|(name, proxy)| ProxyGroup {
+            name: name.clone(),
+            now: proxy
+                .get("now")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            nodes: members(proxy)
+                .into_iter()
+                .map(|node| ProxyNode {
+                    delay: delay(&node),
+                    name: node,
+                })
+                .collect(),
+        })
+        .collect();
+    groups.sort_by_key(|group| {
+        order
+            .iter()
+            .position(|name| *name == group.name)
+            .unwrap_or(usize::MAX)
+    });
+    groups
+}
+
+/// Percent-encode a group name for use as a URL path segment.
+fn encode_segment(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+impl CrashConfig {
+    /// Fetch the selectable proxy groups from the running core.
+    pub async fn proxy_groups(&self) -> Result<Vec<ProxyGroup>> {
+        let response = self
+            .controller_request(reqwest::Method::GET, "/proxies")?
+            .send()
+            .await
+            .map_err(|e| {
+                CrashError::Process(format!(
+                    "Could not reach the {} controller ({}); is the core running?",
+                    self.core, e
+                ))
+            })?;
+        if !response.status().is_success() {
+            return Err(CrashError::Process(format!(
+                "{} controller returned {} for /proxies",
+                self.core,
+                response.status()
+            )));
+        }
+        let body: Value = serde_json::from_str(&response.text().await?)?;
+        Ok(parse_groups(&body))
+    }
+
+    /// Switch `group` to `node` on the running core. Both are checked
+    /// against the core's groups first, for a clearer error than the API's.
+    pub async fn select_proxy(&self, group: &str, node: &str) -> Result<()> {
+        let groups = self.proxy_groups().await?;
+        let Some(found) = groups.iter().find(|g| g.name == group) else {
+            let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+            return Err(CrashError::Config(format!(
+                "No selectable group {:?}; available: {}",
+                group,
+                names.join(", ")
+            )));
+        };
+        if !found.nodes.iter().any(|n| n.name == node) {
+            return Err(CrashError::Config(format!(
+                "Group {:?} has no node {:?}",
+                group, node
+            )));
+        }
+
+        let path = format!("/proxies/{}", encode_segment(group));
+        let response = self
+            .controller_request(reqwest::Method::PUT, &path)?
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::json!({ "name": node }).to_string())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(CrashError::Process(format!(
+                "{} refused to select {:?} in {:?} ({})",
+                self.core,
+                node,
+                group,
+                response.status()
+            )));
+        }
+        log_info!("Selected {:?} in proxy group {:?}", node, group);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_in_config_order_with_delays() {
+        let body = serde_json::json!({
+            "proxies": {
+                "Auto": { "type": "URLTest", "now": "hk", "all": ["hk", "jp"] },
+                "GLOBAL": { "type": "Selector", "now": "DIRECT", "all": ["Proxy", "Media", "DIRECT"] },
+                "Media": { "type": "Selector", "now": "jp", "all": ["hk", "jp"] },
+                "Proxy": { "type": "Selector", "now": "Auto", "all": ["Auto", "hk"] },
+                "hk": { "type": "Shadowsocks", "history": [{ "delay": 300 }, { "delay": 120 }] },
+                "jp": { "type": "Vmess", "history": [{ "delay": 0 }] },
+            }
+        });
+
+        let groups = parse_groups(&body);
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Proxy", "Media", "GLOBAL"]);
+        assert_eq!(groups[1].now, "jp");
+        assert_eq!(
+            groups[1].nodes,
+            [
+                ProxyNode {
+                    name: "hk".to_string(),
+                    delay: Some(120)
+                },
+                ProxyNode {
+                    name: "jp".to_string(),
+                    delay: None
+                },
+            ]
+        );
+        assert_eq!(encode_segment("🚀 Proxy/1"), "%F0%9F%9A%80%20Proxy%2F1");
+    }
+}
@@ -5,18 +5,47 @@
 
 use super::CrashConfig;
 use super::core::Core;
+use super::dns::DnsConfig;
+use super::events::{EventKind, EventReason, record_event};
 use super::get_config_dir;
+use super::patcher::{default_config, patch_config, patch_secret};
+use super::ports::{host_port, listen_ports};
+use super::tun::{check_tun_device, privilege_warnings};
+use super::web::{WebConfig, generate_secret};
 use crate::error::{CrashError, Result};
 use crate::utils::check_connectivity;
-use crate::utils::command::execute;
+use crate::utils::command::{execute, execute_combined};
 use crate::utils::current_timestamp;
-use crate::utils::process::{get_pid, start, stop};
-use crate::{log_debug, log_info};
+use crate::utils::decode::decode_subscription_body;
+use crate::utils::download::controller_client;
+use crate::utils::fs::atomic_write;
+use crate::utils::process::{
+    get_pid, is_running, kill_pid, listening_pid, start, start_attached, stop,
+};
+use crate::{log_debug, log_info, log_warn};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a restart waits for the old core to exit before giving up.
+pub(super) const RESTART_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between `is_running` polls while waiting for the core to exit.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 impl CrashConfig {
     /// Start the proxy core, restarting it first if `force` or if the runtime
     /// budget has been exceeded.
     pub async fn start(&mut self, force: bool) -> Result<()> {
+        self.start_with(force, true).await
+    }
+
+    /// `start`, optionally skipping the check that the core's ports are free.
+    /// A core in safe mode keeps running from the safe config; only
+    /// `leave_safe_mode` goes back to the normal one.
+    pub async fn start_with(&mut self, force: bool, check_ports: bool) -> Result<()> {
         log_info!("Starting proxy core: {}", self.core.name());
 
         if self.stop_force {
@@ -30,7 +59,23 @@ impl CrashConfig {
             }
         }
 
-        if get_pid(&self.core.exe_name()).is_ok() {
+        let running = get_pid(&self.core.exe_name()).is_ok();
+
+        // `stop` resets `start_time`, so a non-zero value with no process
+        // means the core died on its own.
+        let crashed = !running && self.start_time > 0;
+        if crashed {
+            self.notify
+                .notify(&format!("{} was not running, restarting it", self.core))
+                .await;
+        }
+        let mut event = if crashed {
+            (EventKind::Restart, EventReason::Crashed)
+        } else {
+            (EventKind::Start, EventReason::Manual)
+        };
+
+        if running {
             let current_time = current_timestamp();
             let runtime_seconds = current_time.saturating_sub(self.start_time);
             let max_runtime_seconds = self.max_runtime_hours * 3600;
@@ -43,54 +88,323 @@ impl CrashConfig {
             // this request is captured by the TUN device and forwarded
             // through the proxy, so a failure means the proxy is not
             // forwarding correctly — restart it to reload the config.
-            let check_url = self
-                .check_url
-                .as_deref()
-                .unwrap_or("https://www.google.com");
-            let connectivity_ok = check_connectivity(check_url).await;
+            let connectivity_ok = check_connectivity(self.check_url()).await;
 
             let needs_restart = force || exceeds_runtime || !connectivity_ok;
 
             if needs_restart {
-                let reason = if force {
-                    "manual force stop"
+                let (reason, event_reason) = if force {
+                    ("manual force stop", EventReason::Manual)
                 } else if exceeds_runtime {
-                    "maximum runtime exceeded"
+                    ("maximum runtime exceeded", EventReason::MaxRuntime)
                 } else {
-                    "connectivity check failed"
+                    ("connectivity check failed", EventReason::Connectivity)
                 };
                 log_info!("Stopping process, reason: {}", reason);
-                self.stop(false)?;
+                if !force {
+                    self.notify
+                        .notify(&format!("Restarting {}: {}", self.core, reason))
+                        .await;
+                }
+                event = (EventKind::Restart, event_reason);
+                self.stop_process(false)?;
+                // The old core may still hold the TUN device for a moment.
+                self.wait_stopped(RESTART_STOP_TIMEOUT).await?;
             } else {
                 return Ok(());
             }
         }
 
-        self.start_core()?;
+        self.start_core(check_ports)?;
         self.start_time = current_timestamp();
         self.save()?;
+        record_event(self.core, event.0, event.1);
 
         log_info!("Proxy core started successfully");
         Ok(())
     }
 
-    /// Spawn the core executable with the right arguments for the current core.
-    pub fn start_core(&self) -> Result<()> {
+    /// Restart the core from a minimal generated config for recovery: proxy
+    /// ports only, no TUN, DNS hijacking or rule providers, and the
+    /// controller bound to localhost. The stored core config is not touched
+    /// and is used again by the next `start` without `--safe`.
+    pub async fn start_safe(&mut self, check_ports: bool) -> Result<()> {
+        log_warn!("Starting {} in safe mode", self.core);
+
+        let safe_path = self.safe_config_path();
+        let config = default_config(
+            self.core,
+            &self.safe_web()?,
+            self.mixed_port(),
+            &DnsConfig::default(),
+            None,
+        );
+        atomic_write(&safe_path, &config)?;
+
+        let running = is_running(&self.core.exe_name());
+        if running {
+            self.stop_process(false)?;
+            self.wait_stopped(RESTART_STOP_TIMEOUT).await?;
+        }
+        self.stop_force = false;
+        self.safe_mode = true;
+        self.start_core(check_ports)?;
+        self.start_time = current_timestamp();
+        self.save()?;
+        let kind = if running {
+            EventKind::Restart
+        } else {
+            EventKind::Start
+        };
+        record_event(self.core, kind, EventReason::Manual);
+
+        log_info!(
+            "Proxy core started in safe mode from {}",
+            safe_path.display()
+        );
+        Ok(())
+    }
+
+    /// Switch back to the normal config for the next start. Returns whether
+    /// the core was in safe mode; the caller then restarts it with `force`.
+    pub fn leave_safe_mode(&mut self) -> bool {
+        if !self.safe_mode {
+            return false;
+        }
+        log_info!("Leaving safe mode");
+        self.safe_mode = false;
+        true
+    }
+
+    /// Config file written by `start_safe`, next to the normal one.
+    pub fn safe_config_path(&self) -> PathBuf {
+        get_config_dir().join(format!("safe-{}", self.core.config_file_name()))
+    }
+
+    /// Config file the core is started from: the safe one in safe mode.
+    fn run_config_path(&self) -> PathBuf {
+        if self.safe_mode {
+            self.safe_config_path()
+        } else {
+            self.core_config_path()
+        }
+    }
+
+    /// The web settings with the controller on localhost, for safe mode.
+    fn safe_web(&self) -> Result<WebConfig> {
+        let port = host_port(&self.web.host).unwrap_or(self.core.default_ports().controller);
+        Ok(WebConfig {
+            host: format!("127.0.0.1:{}", port),
+            ..self.web.resolved()?
+        })
+    }
+
+    /// Start the core only if it is not already running, without the health
+    /// check or runtime budget of `start`. Returns whether it was started.
+    pub fn ensure_started(&mut self, check_ports: bool) -> Result<bool> {
+        if is_running(&self.core.exe_name()) {
+            log_info!("Proxy core already running: {}", self.core.name());
+            return Ok(false);
+        }
+
+        // A recorded start time means the core was running and died.
+        let (kind, reason) = if self.start_time > 0 {
+            (EventKind::Restart, EventReason::Crashed)
+        } else {
+            (EventKind::Start, EventReason::Manual)
+        };
+        self.start_core(check_ports)?;
+        self.start_time = current_timestamp();
+        self.save()?;
+        record_event(self.core, kind, reason);
+
+        log_info!("Proxy core started successfully");
+        Ok(true)
+    }
+
+    /// Spawn the core executable with the right arguments for the current
+    /// core. With `check_ports`, refuse to start while one of its ports is
+    /// taken by another process.
+    pub fn start_core(&self, check_ports: bool) -> Result<()> {
+        let exe_path = self.check_startable(check_ports)?;
+        let envs = self.core_envs();
+        start(
+            &exe_path,
+            self.core_args(),
+            envs.iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
+        )?;
+        Ok(())
+    }
+
+    /// Run the core attached to the terminal until it exits or Ctrl-C is
+    /// pressed, then stop it. `start_time` is left alone, as nothing
+    /// outlives this invocation.
+    pub async fn run_foreground(&self, check_ports: bool) -> Result<()> {
+        if is_running(&self.core.exe_name()) {
+            return Err(CrashError::Process(format!(
+                "{} is already running, stop it first with 'crash stop'",
+                self.core
+            )));
+        }
+        let exe_path = self.check_startable(check_ports)?;
+        let envs = self.core_envs();
+        let mut child = start_attached(
+            &exe_path,
+            self.core_args(),
+            envs.iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
+        )?;
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            if let Some(status) = child.try_wait()? {
+                log_info!("Foreground {} exited: {}", self.core, status);
+                if status.success() {
+                    return Ok(());
+                }
+                return Err(CrashError::Process(format!(
+                    "{} exited with {}",
+                    self.core, status
+                )));
+            }
+            tokio::select! {
+                _ = &mut ctrl_c => break,
+                _ = tokio::time::sleep(Duration::from_millis(200)) => {}
+            }
+        }
+
+        // The terminal's Ctrl-C usually reached the core too; give it the
+        // same time to exit as a restart, then kill it.
+        log_info!("Stopping foreground {}", self.core);
+        let _ = kill_pid(child.id());
+        let deadline = Instant::now() + RESTART_STOP_TIMEOUT;
+        while child.try_wait()?.is_none() {
+            if Instant::now() >= deadline {
+                log_warn!("Foreground {} did not exit, killing it", self.core);
+                child.kill()?;
+                child.wait()?;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
+
+    /// Check that the core can be started: executable present, secret and
+    /// TUN device usable and, with `check_ports`, its ports free. Returns
+    /// the executable's path.
+    fn check_startable(&self, check_ports: bool) -> Result<PathBuf> {
+        // A `${NAME}` secret whose variable is gone would leave the
+        // controller open, so refuse to start.
+        self.web.resolved()?;
+
         let exe_path = self.core.exe_path(&get_config_dir());
 
         if !exe_path.exists() {
+            let hint = match self.installed_cores().as_slice() {
+                [] => String::new(),
+                installed => format!(
+                    " (installed: {}; switch with 'crash core <name>')",
+                    installed
+                        .iter()
+                        .map(|c| c.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
             return Err(CrashError::Process(format!(
-                "Core executable not found: {}. Please run 'install' first.",
-                exe_path.display()
+                "Core executable not found: {}. Please run 'install' first.{}",
+                exe_path.display(),
+                hint
             )));
         }
 
-        let args = match self.core {
+        let content = std::fs::read_to_string(self.run_config_path()).unwrap_or_default();
+        check_tun_device(self.core, &content)?;
+        for warning in self.privilege_warnings() {
+            log_warn!("{}", warning);
+        }
+        if check_ports {
+            self.check_ports_free(&content)?;
+        }
+
+        let envs = self.core_envs();
+        let extra: Vec<&str> = envs
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .filter(|key| !self.core.envs().iter().any(|(k, _)| k == key))
+            .collect();
+        if !extra.is_empty() {
+            log_info!("Starting core with extra environment: {}", extra.join(", "));
+        }
+        Ok(exe_path)
+    }
+
+    /// Environment the core is spawned with: the core's own variables,
+    /// overridden by `core_env`, then by `start --env`.
+    pub fn core_envs(&self) -> Vec<(String, String)> {
+        let mut envs: BTreeMap<String, String> = self
+            .core
+            .envs()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        envs.extend(self.core_env.clone());
+        envs.extend(self.start_env.clone());
+        envs.into_iter().collect()
+    }
+
+    /// Features of the core config that won't work with the current
+    /// privileges, see `tun::privilege_warnings`.
+    pub fn privilege_warnings(&self) -> Vec<String> {
+        let content = std::fs::read_to_string(self.run_config_path()).unwrap_or_default();
+        privilege_warnings(self.core, &self.core.exe_path(&get_config_dir()), &content)
+    }
+
+    /// Fail if the controller port or a port the core config `content`
+    /// listens on is already bound, naming the owner when it can be found.
+    fn check_ports_free(&self, content: &str) -> Result<()> {
+        let controller = host_port(&self.web.host).map(|port| ("controller", port));
+        let listen = listen_ports(self.core, content)
+            .into_iter()
+            .map(|port| ("listen", port));
+
+        for (kind, port) in controller.into_iter().chain(listen) {
+            match TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)) {
+                Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                    let owner = listening_pid(port)
+                        .map(|pid| format!(" by PID {}", pid))
+                        .unwrap_or_default();
+                    return Err(CrashError::Process(format!(
+                        "{} port {} is already in use{}; stop that process, change the port \
+                         or pass --ignore-port-conflict",
+                        kind, port, owner
+                    )));
+                }
+                // Anything else (e.g. no permission for a low port) is for
+                // the core to report.
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Command-line arguments the core is spawned with.
+    pub fn core_args(&self) -> Vec<String> {
+        match self.core {
             Core::Mihomo | Core::Clash => vec![
                 "-f".to_string(),
-                self.core_config_path().to_string_lossy().to_string(),
+                self.run_config_path().to_string_lossy().to_string(),
                 "-ext-ctl".to_string(),
-                self.web.host.clone(),
+                if self.safe_mode {
+                    self.safe_web().map(|web| web.host).unwrap_or_default()
+                } else {
+                    self.web.host.clone()
+                },
                 "-ext-ui".to_string(),
                 self.web.ui_name().to_string(),
                 "-d".to_string(),
@@ -99,18 +413,76 @@ impl CrashConfig {
             Core::Singbox => vec![
                 "run".to_string(),
                 "-c".to_string(),
-                self.core_config_path().to_string_lossy().to_string(),
+                self.run_config_path().to_string_lossy().to_string(),
                 "-D".to_string(),
                 get_config_dir().to_string_lossy().to_string(),
             ],
+        }
+    }
+
+    /// Everything `start_core` would use to spawn the core, as `(key, value)`
+    /// pairs, so a failing start can be reproduced by hand.
+    pub fn start_info(&self) -> Vec<(&'static str, String)> {
+        let exe_path = self.core.exe_path(&get_config_dir());
+        let config_path = self.core_config_path();
+
+        let exe_state = if exe_path.exists() {
+            "found"
+        } else {
+            "missing, run 'crash install core'"
         };
 
-        start(&exe_path, args, self.core.envs())?;
-        Ok(())
+        let config_state = match std::fs::read_to_string(&config_path) {
+            Err(e) => format!("unreadable: {}", e),
+            Ok(content) => match self.core {
+                Core::Singbox => match serde_json::from_str::<serde_json::Value>(&content) {
+                    Ok(_) => "valid JSON".to_string(),
+                    Err(e) => format!("invalid JSON: {}", e),
+                },
+                Core::Mihomo | Core::Clash if content.trim().is_empty() => "empty".to_string(),
+                Core::Mihomo | Core::Clash => {
+                    format!("{} lines (YAML not validated)", content.lines().count())
+                }
+            },
+        };
+
+        let envs = self
+            .core_envs()
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let working_dir = std::env::current_dir()
+            .map(|d| d.to_string_lossy().to_string())
+            .unwrap_or_else(|e| format!("unknown: {}", e));
+
+        vec![
+            ("exe", format!("{} ({})", exe_path.display(), exe_state)),
+            ("args", self.core_args().join(" ")),
+            ("env", envs),
+            (
+                "config",
+                format!("{} ({})", config_path.display(), config_state),
+            ),
+            ("cwd", working_dir),
+        ]
     }
 
-    /// Stop the proxy core.
+    /// Stop the proxy core. Stopping it on purpose leaves safe mode too.
     pub fn stop(&mut self, force: bool) -> Result<()> {
+        let running = is_running(&self.core.exe_name());
+        self.safe_mode = false;
+        self.stop_process(force)?;
+        if running {
+            record_event(self.core, EventKind::Stop, EventReason::Manual);
+        }
+        Ok(())
+    }
+
+    /// `stop` without recording an event or leaving safe mode, for the
+    /// first half of a restart.
+    fn stop_process(&mut self, force: bool) -> Result<()> {
         log_info!("Stopping proxy core: {}", self.core.name());
 
         self.stop_force = force;
@@ -124,6 +496,205 @@ impl CrashConfig {
         Ok(())
     }
 
+    /// Stop every known core, not just the configured one, so a core left
+    /// running from before a core switch is cleaned up too. Named instances
+    /// run under their own executable names and are left alone. Returns the
+    /// cores that were running, with their PIDs.
+    pub fn stop_all(&mut self, force: bool) -> Result<Vec<(Core, u32)>> {
+        log_info!("Stopping all proxy cores");
+
+        let mut stopped = Vec::new();
+        for core in Core::ALL {
+            let exe_name = core.exe_name();
+            if let Ok(pid) = get_pid(&exe_name) {
+                stop(&exe_name)?;
+                record_event(core, EventKind::Stop, EventReason::Manual);
+                stopped.push((core, pid));
+            }
+        }
+
+        self.stop_force = force;
+        self.safe_mode = false;
+        self.start_time = 0;
+        self.save()?;
+        Ok(stopped)
+    }
+
+    /// Bring `start_time` in line with whether the core is actually
+    /// `running`: a core killed outside crash leaves a stale timestamp
+    /// behind, which is cleared. Returns whether the config changed.
+    pub fn reconcile_start_time(&mut self, running: bool) -> bool {
+        if !running && self.start_time > 0 {
+            log_info!(
+                "{} is not running, clearing stale start time {}",
+                self.core,
+                self.start_time
+            );
+            self.start_time = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Request to `path` on the core's controller API, authenticated with
+    /// the web secret.
+    pub(super) fn controller_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+    ) -> Result<reqwest::RequestBuilder> {
+        let port = host_port(&self.web.host).ok_or_else(|| {
+            CrashError::Config(format!("No controller port in {:?}", self.web.host))
+        })?;
+        let secret = self.web.resolved()?.secret;
+        let request = controller_client(&self.timeout)
+            .request(method, format!("http://127.0.0.1:{}{}", port, path));
+        Ok(if secret.is_empty() {
+            request
+        } else {
+            request.bearer_auth(secret)
+        })
+    }
+
+    /// Make the running core pick up its edited config: Mihomo and Clash
+    /// reload it through `PUT /configs`, Singbox (or a failed reload) is
+    /// restarted. Returns whether the core was restarted.
+    pub async fn reload_core(&mut self) -> Result<bool> {
+        if matches!(self.core, Core::Mihomo | Core::Clash) {
+            // In safe mode the core reloads the safe config, not the edit.
+            let path = self.run_config_path().to_string_lossy().to_string();
+            let reloaded = self
+                .controller_request(reqwest::Method::PUT, "/configs?force=true")?
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::json!({ "path": path }).to_string())
+                .send()
+                .await;
+            match reloaded {
+                Ok(response) if response.status().is_success() => {
+                    log_info!("{} reloaded its configuration", self.core);
+                    return Ok(false);
+                }
+                Ok(response) => log_warn!(
+                    "{} refused to reload its configuration ({}), restarting it",
+                    self.core,
+                    response.status()
+                ),
+                Err(e) => log_warn!(
+                    "Could not ask {} to reload its configuration ({}), restarting it",
+                    self.core,
+                    e
+                ),
+            }
+        }
+        self.start(true).await?;
+        Ok(true)
+    }
+
+    /// Replace the web secret with a fresh random one: saved to the config,
+    /// written into the core config on disk, and a running core is
+    /// restarted so the old secret stops working right away. Returns the
+    /// new secret and whether the core was restarted.
+    pub async fn rotate_secret(&mut self) -> Result<(String, bool)> {
+        if let Some(var) = self.web.secret_env_var() {
+            return Err(CrashError::Config(format!(
+                "Web secret is read from ${}; change it there, or set a literal \
+                 secret with 'crash config secret <value>' first",
+                var
+            )));
+        }
+
+        let secret = generate_secret()?;
+        self.web.secret = secret.clone();
+        self.save()?;
+        log_info!("Web secret rotated");
+
+        let config_path = self.core_config_path();
+        if config_path.exists() {
+            let content = std::fs::read_to_string(&config_path)?;
+            atomic_write(&config_path, &patch_secret(self.core, &secret, &content))?;
+        }
+
+        let running = is_running(&self.core.exe_name());
+        if running {
+            self.start(true).await?;
+        }
+        Ok((secret, running))
+    }
+
+    /// Poll until the core process has exited, failing after `timeout`.
+    pub async fn wait_stopped(&self, timeout: Duration) -> Result<()> {
+        wait_exited(&self.core.exe_name(), timeout).await
+    }
+
+    /// Like `wait_stopped`, for every core type (after `stop_all`).
+    pub async fn wait_all_stopped(&self, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        for core in Core::ALL {
+            wait_exited(&core.exe_name(), timeout.saturating_sub(started.elapsed())).await?;
+        }
+        Ok(())
+    }
+
+    /// Patch the config at `path` into a temporary file and run the core's
+    /// own config check on it. Returns the core's output on success; on
+    /// failure the error carries the core's diagnostics. Neither the live
+    /// config nor a running core is touched.
+    pub fn test_config(&self, path: &Path) -> Result<String> {
+        let exe_path = self.core.exe_path(&get_config_dir());
+        if !exe_path.exists() {
+            return Err(CrashError::Process(format!(
+                "Core executable not found: {}. Please run 'install' first.",
+                exe_path.display()
+            )));
+        }
+
+        let bytes = std::fs::read(path)
+            .map_err(|e| CrashError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        let content = decode_subscription_body(&bytes)?;
+        let patched = patch_config(
+            self.core,
+            &self.web.resolved()?,
+            self.mixed_port(),
+            &self.dns,
+            self.geo_ipv6,
+            self.core_ui_url().as_deref(),
+            &content,
+        );
+
+        let tmp_path = std::env::temp_dir().join(format!(
+            "crash-test-{}-{}",
+            std::process::id(),
+            self.core.config_file_name()
+        ));
+        std::fs::write(&tmp_path, patched)?;
+
+        let result = self.check_config_file(&tmp_path);
+        let _ = std::fs::remove_file(&tmp_path);
+
+        match result? {
+            (true, output) => Ok(output.trim().to_string()),
+            (false, output) => Err(CrashError::Config(format!(
+                "{} rejected {}:\n{}",
+                self.core,
+                path.display(),
+                output.trim()
+            ))),
+        }
+    }
+
+    /// Run the core's own check (`-t` / `check`) on the ready-to-use config
+    /// at `path`. Returns whether it passed, with the core's output.
+    pub(super) fn check_config_file(&self, path: &Path) -> Result<(bool, String)> {
+        let exe_path = self.core.exe_path(&get_config_dir());
+        let path = path.to_string_lossy().to_string();
+        let config_dir = get_config_dir().to_string_lossy().to_string();
+        let args = match self.core {
+            Core::Mihomo | Core::Clash => ["-t", "-f", &path, "-d", &config_dir],
+            Core::Singbox => ["check", "-c", &path, "-D", &config_dir],
+        };
+        execute_combined(exe_path.to_string_lossy().as_ref(), &args)
+    }
+
     /// Get the version of the installed proxy core.
     pub fn get_version(&self) -> Result<String> {
         log_debug!("Getting version for core: {}", self.core.name());
@@ -135,18 +706,88 @@ impl CrashConfig {
             return Err(CrashError::Config("Core executable not found".to_string()));
         }
 
-        let args = match self.core {
-            Core::Mihomo | Core::Clash => &["-v"],
-            Core::Singbox => &["version"],
-        };
-        let output = execute(exe_path.to_string_lossy().as_ref(), args)?;
+        let output = execute(
+            exe_path.to_string_lossy().as_ref(),
+            self.core.version_args(),
+        )?;
 
-        // Parse version from output (format: "Mihomo version 1.19.15")
-        let Some(version) = output.split_whitespace().nth(2).map(|s| s.to_string()) else {
-            return Err(CrashError::Config("Core version not found".to_string()));
+        let Some(version) = self.core.parse_version(&output).map(|s| s.to_string()) else {
+            return Err(CrashError::Config(format!(
+                "Core version not found in {:?}",
+                output.lines().next().unwrap_or_default()
+            )));
         };
 
         log_debug!("Core version: {:?}", version);
         Ok(version)
     }
 }
+
+/// Poll until no process named `exe_name` is running, failing after `timeout`.
+async fn wait_exited(exe_name: &str, timeout: Duration) -> Result<()> {
+    let started = Instant::now();
+
+    while is_running(exe_name) {
+        if started.elapsed() >= timeout {
+            return Err(CrashError::Process(format!(
+                "{} is still running after {}",
+                exe_name,
+                humantime::format_duration(timeout)
+            )));
+        }
+        tokio::time::sleep(STOP_POLL_INTERVAL).await;
+    }
+
+    log_debug!("{} exited after {:?}", exe_name, started.elapsed());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::override_config_dir;
+
+    #[tokio::test]
+    async fn safe_mode_survives_a_plain_start() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        let mut config = CrashConfig {
+            core: Core::Mihomo,
+            ..Default::default()
+        };
+
+        // No core binary here, so starting fails after the config is written.
+        assert!(config.start_safe(false).await.is_err());
+        let safe = std::fs::read_to_string(config.safe_config_path()).unwrap();
+        assert!(!safe.contains("tun:"), "{}", safe);
+        assert!(config.safe_mode);
+
+        let safe_path = config.safe_config_path().to_string_lossy().to_string();
+        assert_eq!(config.run_config_path(), config.safe_config_path());
+        let args = config.core_args();
+        assert_eq!(args[1], safe_path);
+        // `-ext-ctl` overrides the controller address of the config.
+        assert_eq!(args[3], "127.0.0.1:9090");
+
+        // What the scheduled `start` and reloads run.
+        assert!(config.start(true).await.is_err());
+        assert!(config.safe_mode);
+        assert_eq!(config.core_args()[1], safe_path);
+
+        // The first half of a restart of a running core (forced, over its
+        // runtime budget or failing the connectivity check).
+        config.stop_process(false).unwrap();
+        assert!(config.safe_mode);
+        assert_eq!(config.core_args()[1], safe_path);
+
+        assert!(config.leave_safe_mode());
+        assert!(!config.leave_safe_mode());
+        assert_eq!(config.run_config_path(), config.core_config_path());
+
+        // A user stop leaves safe mode.
+        config.safe_mode = true;
+        config.stop(false).unwrap();
+        assert!(!config.safe_mode);
+        assert!(!CrashConfig::load().unwrap().safe_mode);
+    }
+}
@@ -0,0 +1,227 @@
+// Privilege checks before starting a core.
+//
+// Creating the TUN device needs root/administrator (or CAP_NET_ADMIN on
+// Linux) and, on Linux, the `/dev/net/tun` node; listening on a port below
+// 1024 on Linux needs root or CAP_NET_BIND_SERVICE. Without them the core
+// still starts but those features silently don't work, with the reason
+// buried in its own log, so start warns up front and says what to do.
+
+use super::core::Core;
+use super::ports::yaml_top_level_value;
+use crate::error::{CrashError, Result};
+use serde_json::Value;
+use std::path::Path;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TUN_DEVICE: &str = "/dev/net/tun";
+
+/// Whether the core config `content` enables TUN: `tun.enable: true` for
+/// Mihomo/Clash, a `tun` inbound for Singbox.
+pub fn tun_enabled(core: Core, content: &str) -> bool {
+    match core {
+        Core::Mihomo | Core::Clash => yaml_block_value(content, "tun", "enable") == Some("true"),
+        Core::Singbox => serde_json::from_str::<Value>(content)
+            .ok()
+            .and_then(|v| {
+                v.get("inbounds")?
+                    .as_array()
+                    .map(|inbounds| inbounds.iter().any(|i| i["type"] == "tun"))
+            })
+            .unwrap_or(false),
+    }
+}
+
+/// Value of `key` directly inside the top-level block `block` of a YAML
+/// document (`tun:` / `  enable: true`).
+fn yaml_block_value<'a>(content: &'a str, block: &str, key: &str) -> Option<&'a str> {
+    let mut lines = content.lines();
+    lines.find(|line| yaml_top_level_value(line, block) == Some(""))?;
+    lines
+        .take_while(|line| {
+            line.starts_with([' ', '\t']) || line.trim().is_empty() || line.starts_with('#')
+        })
+        .find_map(|line| yaml_top_level_value(line.trim_start(), key))
+}
+
+/// Fail if the core config `content` enables TUN but the system has no TUN
+/// device to create it from.
+pub fn check_tun_device(core: Core, content: &str) -> Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if tun_enabled(core, content) && !Path::new(TUN_DEVICE).exists() {
+        return Err(CrashError::Platform(format!(
+            "TUN is enabled in the {} config but {} is missing: load the tun module \
+             ('modprobe tun') or disable tun in the config",
+            core, TUN_DEVICE
+        )));
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let _ = (core, content);
+    Ok(())
+}
+
+/// Features of the core config `content` that won't work because neither
+/// crash nor the core at `exe_path` has the privileges they need, each
+/// with what to do about it. Warnings rather than errors: capabilities can
+/// also be granted in ways that can't be checked here (e.g. by systemd).
+pub fn privilege_warnings(core: Core, exe_path: &Path, content: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if is_admin::is_admin() {
+        return warnings;
+    }
+
+    if tun_enabled(core, content) && !has_capability(exe_path, CAP_NET_ADMIN) {
+        warnings.push(format!(
+            "TUN is enabled in the {} config but crash is not running with enough privileges, \
+             so no traffic will be routed through it: {}, or disable tun in the config",
+            core,
+            privilege_hint(exe_path)
+        ));
+    }
+
+    let ports = privileged_ports(core, content);
+    if !ports.is_empty() && !has_capability(exe_path, CAP_NET_BIND_SERVICE) {
+        warnings.push(format!(
+            "The {} config listens on privileged port(s) {}, which the core can't bind \
+             without more privileges: {}, or move them to unprivileged ports",
+            core,
+            ports
+                .iter()
+                .map(u16::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            privilege_hint(exe_path)
+        ));
+    }
+    warnings
+}
+
+/// Inbound and DNS ports of the core config `content` that only root (or
+/// CAP_NET_BIND_SERVICE) may bind.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn privileged_ports(core: Core, content: &str) -> Vec<u16> {
+    use super::ports::{host_port, listen_ports};
+
+    let start = unprivileged_port_start();
+    let mut ports = listen_ports(core, content);
+    if matches!(core, Core::Mihomo | Core::Clash) {
+        ports.extend(yaml_block_value(content, "dns", "listen").and_then(host_port));
+    }
+    ports.retain(|&port| port != 0 && port < start);
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// Binding any port is unprivileged on Windows and, since Mojave, macOS.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn privileged_ports(_core: Core, _content: &str) -> Vec<u16> {
+    Vec::new()
+}
+
+/// `net.ipv4.ip_unprivileged_port_start`, 1024 unless lowered.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn unprivileged_port_start() -> u16 {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(1024)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn privilege_hint(exe_path: &Path) -> String {
+    format!(
+        "run it with sudo, grant the core the capability with \
+         'sudo setcap cap_net_admin,cap_net_bind_service+ep {}'",
+        exe_path.display()
+    )
+}
+
+#[cfg(windows)]
+fn privilege_hint(_exe_path: &Path) -> String {
+    "run it as administrator".to_string()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+fn privilege_hint(_exe_path: &Path) -> String {
+    "run it with sudo".to_string()
+}
+
+const CAP_NET_BIND_SERVICE: u32 = 10;
+const CAP_NET_ADMIN: u32 = 12;
+
+/// Whether the file capabilities of `exe_path` include `cap` in the
+/// permitted set (`setcap cap_net_admin+ep`).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn has_capability(exe_path: &Path, cap: u32) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(exe_path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // `vfs_cap_data`: magic/flags, then permitted and inheritable masks for
+    // the low 32 capabilities (and the high 32 for v2/v3).
+    let mut data = [0u8; 24];
+    // SAFETY: both names are NUL-terminated and `data` outlives the call,
+    // which writes at most `data.len()` bytes.
+    let len = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            c"security.capability".as_ptr(),
+            data.as_mut_ptr().cast(),
+            data.len(),
+        )
+    };
+    len >= 8 && u32::from_le_bytes([data[4], data[5], data[6], data[7]]) & (1 << cap) != 0
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn has_capability(_exe_path: &Path, _cap: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_tun_in_yaml_and_json() {
+        let enabled = "mixed-port: 7890\ntun:\n  # device: Meta\n  enable: true\n  stack: gVisor\n";
+        assert!(tun_enabled(Core::Mihomo, enabled));
+
+        let disabled = "tun:\n  enable: false\ndns:\n  enable: true\n";
+        assert!(!tun_enabled(Core::Mihomo, disabled));
+        // `enable` of another block doesn't count.
+        assert!(!tun_enabled(
+            Core::Clash,
+            "tun:\n  stack: system\ndns:\n  enable: true\n"
+        ));
+
+        let singbox = r#"{"inbounds":[{"type":"mixed"},{"type":"tun"}]}"#;
+        assert!(tun_enabled(Core::Singbox, singbox));
+        assert!(!tun_enabled(
+            Core::Singbox,
+            r#"{"inbounds":[{"type":"mixed"}]}"#
+        ));
+    }
+
+    #[test]
+    fn tun_disabled_needs_no_privileges() {
+        let content = "mixed-port: 7890\ntun:\n  enable: false\n";
+        assert!(check_tun_device(Core::Mihomo, content).is_ok());
+        assert!(privilege_warnings(Core::Mihomo, Path::new("mihomo"), content).is_empty());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn finds_privileged_ports() {
+        let content = "mixed-port: 7890\nport: 80\ndns:\n  enable: true\n  listen: 0.0.0.0:53\n";
+        let expected: Vec<u16> = [53, 80]
+            .into_iter()
+            .filter(|&port| port < unprivileged_port_start())
+            .collect();
+        assert_eq!(privileged_ports(Core::Mihomo, content), expected);
+
+        let singbox = r#"{"inbounds":[{"type":"mixed","listen_port":2080}]}"#;
+        assert!(privileged_ports(Core::Singbox, singbox).is_empty());
+    }
+}
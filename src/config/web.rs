@@ -1,32 +1,70 @@
 // Web UI configuration
 
-use clap::ValueEnum;
-use github_proxy::{Proxy, Resource};
+use super::AssetsRepo;
+use crate::error::{CrashError, Result};
+use github_proxy::Proxy;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
-use strum::{Display, EnumString, IntoStaticStr};
+use std::str::FromStr;
 
 /// UI type enumeration
-#[derive(
-    Debug,
-    Clone,
-    Copy,
-    PartialEq,
-    Eq,
-    Hash,
-    Default,
-    Display,
-    EnumString,
-    IntoStaticStr,
-    Serialize,
-    Deserialize,
-    ValueEnum,
-)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub enum UiType {
     #[default]
     CrashUI,
     Metacubexd,
     Yacd,
+    /// Any other dashboard, identified by its directory name. Must be
+    /// installed from `WebConfig::ui_url_override`.
+    Custom(String),
+}
+
+impl UiType {
+    /// Built-in dashboards shipped in the crash-assets release.
+    pub const BUILTIN: [UiType; 3] = [UiType::CrashUI, UiType::Metacubexd, UiType::Yacd];
+
+    /// Get the UI type name, which is also its directory name
+    pub fn name(&self) -> &str {
+        match self {
+            UiType::CrashUI => "CrashUI",
+            UiType::Metacubexd => "Metacubexd",
+            UiType::Yacd => "Yacd",
+            UiType::Custom(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for UiType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for UiType {
+    type Err = CrashError;
+
+    /// Built-in names match case-insensitively; anything else becomes a
+    /// `Custom` dashboard as long as it is usable as a directory name.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(ui) = Self::BUILTIN
+            .into_iter()
+            .find(|ui| ui.name().eq_ignore_ascii_case(s))
+        {
+            return Ok(ui);
+        }
+
+        if s.is_empty()
+            || s == "."
+            || s == ".."
+            || s.contains(|c: char| c == '/' || c == '\\' || c.is_whitespace())
+        {
+            return Err(CrashError::Config(format!("Invalid UI name: {:?}", s)));
+        }
+
+        Ok(UiType::Custom(s.to_string()))
+    }
 }
 
 /// Web configuration structure
@@ -34,7 +72,24 @@ pub enum UiType {
 pub struct WebConfig {
     pub ui: UiType,
     pub host: String,
+    /// Controller secret, or `${NAME}` to read it from the environment
+    /// variable `NAME` when the core config is rendered.
     pub secret: String,
+
+    /// Optional URL of a dashboard tarball to install instead of the
+    /// crash-assets release for `ui` (e.g. a pinned metacubexd version or a fork).
+    #[serde(default)]
+    pub ui_url_override: Option<String>,
+
+    /// Address shown in the dashboard URL of `status`, overriding the
+    /// auto-detected LAN IP.
+    #[serde(default)]
+    pub display_ip: Option<IpAddr>,
+
+    /// Let the core download and update the dashboard itself instead of
+    /// installing it with crash, where the core supports it.
+    #[serde(default)]
+    pub core_managed: bool,
 }
 
 impl Default for WebConfig {
@@ -43,14 +98,48 @@ impl Default for WebConfig {
             ui: UiType::default(),
             host: ":9090".to_string(),
             secret: String::new(),
+            ui_url_override: None,
+            display_ip: None,
+            core_managed: false,
         }
     }
 }
 
+/// Random 128-bit controller secret from the OS RNG, as 32 hex digits.
+pub fn generate_secret() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| CrashError::Platform(format!("Failed to generate a secret: {}", e)))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
 impl WebConfig {
     /// Get the UI type name as a string
-    pub fn ui_name(&self) -> &'static str {
-        self.ui.into()
+    pub fn ui_name(&self) -> &str {
+        self.ui.name()
+    }
+
+    /// Environment variable named by a `${NAME}` secret.
+    pub fn secret_env_var(&self) -> Option<&str> {
+        self.secret.strip_prefix("${")?.strip_suffix('}')
+    }
+
+    /// Copy of this config with a `${NAME}` secret replaced by the value of
+    /// `NAME`, failing when the variable is not set.
+    pub fn resolved(&self) -> Result<WebConfig> {
+        let Some(name) = self.secret_env_var() else {
+            return Ok(self.clone());
+        };
+        let secret = std::env::var(name).map_err(|_| {
+            CrashError::Config(format!(
+                "Web secret is read from ${{{}}}, but that environment variable is not set",
+                name
+            ))
+        })?;
+        Ok(WebConfig {
+            secret,
+            ..self.clone()
+        })
     }
 
     /// Get the UI assets directory path
@@ -82,7 +171,7 @@ impl WebConfig {
                     }
                 }
             }
-            UiType::Yacd => {}
+            UiType::Yacd | UiType::Custom(_) => {}
         }
         None
     }
@@ -90,24 +179,111 @@ impl WebConfig {
     /// Get the release file name for the UI
     fn ui_release_file_name(&self) -> String {
         use UiType::*;
-        match self.ui {
+        match &self.ui {
             Yacd => "yacd.tar.gz".to_string(),
             Metacubexd => "metacubexd.tar.gz".to_string(),
             CrashUI => "crash-ui.tar.gz".to_string(),
+            Custom(name) => format!("{}.tar.gz", name),
         }
     }
 
-    /// Get the download URL for the UI
-    pub fn ui_url(&self) -> crate::error::Result<String> {
-        Resource::File {
-            owner: "ahaoboy".to_string(),
-            repo: "crash-assets".to_string(),
-            reference: "main".to_string(),
-            path: self.ui_release_file_name(),
+    /// Get the download URL for the UI from the crash-assets `reference`,
+    /// preferring `ui_url_override` when set
+    pub fn ui_url(&self, assets: &AssetsRepo) -> Result<String> {
+        self.ui_url_via(assets, Proxy::Github)
+    }
+
+    /// Download URL of the UI through `proxy`; `ui_url_override` is used as is.
+    pub fn ui_url_via(&self, assets: &AssetsRepo, proxy: Proxy) -> Result<String> {
+        if let Some(url) = &self.ui_url_override {
+            return Ok(url.clone());
         }
-        .url(&Proxy::Github)
-        .ok_or_else(|| {
-            crate::error::CrashError::Download("Failed to get UI download URL".to_string())
-        })
+
+        if let UiType::Custom(name) = &self.ui {
+            return Err(CrashError::Config(format!(
+                "Custom UI '{}' has no download URL. Set one with 'crash config ui-url <url>'",
+                name
+            )));
+        }
+
+        assets
+            .file(self.ui_release_file_name())
+            .url(&proxy)
+            .ok_or_else(|| CrashError::Download("Failed to get UI download URL".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_type_parses_builtin_case_insensitively() {
+        assert_eq!("metacubexd".parse::<UiType>().unwrap(), UiType::Metacubexd);
+        assert_eq!("CRASHUI".parse::<UiType>().unwrap(), UiType::CrashUI);
+    }
+
+    #[test]
+    fn ui_type_parses_custom_and_rejects_paths() {
+        assert_eq!(
+            "zashboard".parse::<UiType>().unwrap(),
+            UiType::Custom("zashboard".to_string())
+        );
+        assert!("../etc".parse::<UiType>().is_err());
+        assert!("".parse::<UiType>().is_err());
+    }
+
+    #[test]
+    fn custom_ui_serde_round_trip() {
+        let web = WebConfig {
+            ui: UiType::Custom("zashboard".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&web).unwrap();
+        let back: WebConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.ui, web.ui);
+        assert_eq!(
+            back.ui_dir(Path::new("cfg")),
+            Path::new("cfg").join("zashboard")
+        );
+    }
+
+    #[test]
+    fn custom_ui_requires_url_override() {
+        let mut web = WebConfig {
+            ui: UiType::Custom("zashboard".to_string()),
+            ..Default::default()
+        };
+        assert!(web.ui_url(&AssetsRepo::default()).is_err());
+        web.ui_url_override = Some("https://example.com/zashboard.tar.gz".to_string());
+        assert_eq!(
+            web.ui_url(&AssetsRepo::default()).unwrap(),
+            "https://example.com/zashboard.tar.gz"
+        );
+    }
+
+    #[test]
+    fn generated_secrets_differ() {
+        let secret = generate_secret().unwrap();
+        assert_eq!(secret.len(), 32);
+        assert!(secret.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(secret, generate_secret().unwrap());
+    }
+
+    #[test]
+    fn env_secret_is_resolved_at_render_time() {
+        let mut web = WebConfig::default();
+        assert_eq!(web.resolved().unwrap().secret, "");
+
+        web.secret = "${CRASH_TEST_WEB_SECRET_UNSET}".to_string();
+        assert_eq!(web.secret_env_var(), Some("CRASH_TEST_WEB_SECRET_UNSET"));
+        assert!(web.resolved().is_err());
+
+        // PATH is set in any test environment.
+        web.secret = "${PATH}".to_string();
+        assert_eq!(
+            web.resolved().unwrap().secret,
+            std::env::var("PATH").unwrap()
+        );
     }
 }
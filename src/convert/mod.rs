@@ -0,0 +1,533 @@
+// Native subscription conversion.
+//
+// Many providers serve a base64 list of share links (`vmess://`, `ss://`,
+// `trojan://`, `vless://`) instead of a core config. The common schemes are
+// turned into a Mihomo `proxies:` list here, so the subscription doesn't
+// have to be sent to a third-party converter. Anything else is reported so
+// the caller can fall back to an external converter.
+
+use crate::config::format::json_to_yaml;
+use crate::error::{CrashError, Result};
+use crate::utils::download::{DownloadOptions, download_text};
+use crate::{log_info, log_warn};
+use base64::Engine as _;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use serde_json::{Map, Value, json};
+
+/// Base config the converted proxies are added to.
+const TEMPLATE_YAML: &str = include_str!("../assets/mihomo.yaml");
+
+/// Name of the selector group holding every converted proxy.
+const PROXY_GROUP: &str = "PROXY";
+
+/// subconverter instance used for whatever native conversion can't handle.
+pub const DEFAULT_CONVERTER: &str = "https://api.v1.mk";
+
+/// Share-link schemes the native converter understands.
+pub const NATIVE_SCHEMES: [&str; 4] = ["vmess", "ss", "trojan", "vless"];
+
+/// Outcome of converting a subscription body.
+#[derive(Debug, Default)]
+pub struct Conversion {
+    /// Mihomo proxy entries, with unique names.
+    pub proxies: Vec<Value>,
+    /// Links that were not converted, with the reason.
+    pub skipped: Vec<(String, String)>,
+}
+
+impl Conversion {
+    /// The proxies merged into the default config, behind one selector
+    /// group that all traffic goes through.
+    pub fn to_mihomo_config(&self) -> String {
+        let mut members: Vec<Value> = self.proxies.iter().map(|p| p["name"].clone()).collect();
+        members.push(json!("DIRECT"));
+        let body = json!({
+            "proxies": self.proxies,
+            "proxy-groups": [{ "name": PROXY_GROUP, "type": "select", "proxies": members }],
+            "rules": [format!("MATCH,{}", PROXY_GROUP)],
+        });
+
+        let mut config = TEMPLATE_YAML.trim_end().to_string();
+        config.push('\n');
+        config.push_str(&json_to_yaml(&body));
+        config
+    }
+}
+
+/// Convert a decoded subscription body (one share link per line) into Mihomo
+/// proxies. Blank lines are ignored; unsupported or malformed links end up
+/// in `skipped`.
+pub fn convert_subscription(body: &str) -> Conversion {
+    let mut conversion = Conversion::default();
+    for line in body.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match parse_share_link(line) {
+            Ok(mut proxy) => {
+                let name = unique_name(&conversion.proxies, proxy["name"].as_str().unwrap_or(""));
+                proxy["name"] = Value::String(name);
+                conversion.proxies.push(proxy);
+            }
+            Err(e) => {
+                log_warn!("Skipping share link: {}", e);
+                conversion.skipped.push((line.to_string(), e.to_string()));
+            }
+        }
+    }
+    conversion
+}
+
+/// Have the subconverter at `converter` turn the subscription at `source`
+/// into a Clash config. The provider URL is sent to that service.
+pub async fn convert_external(
+    converter: &str,
+    source: &str,
+    options: &DownloadOptions,
+) -> Result<String> {
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/sub", converter.trim_end_matches('/')),
+        [("target", "clash"), ("url", source)],
+    )
+    .map_err(|e| CrashError::Config(format!("Invalid converter URL {:?}: {}", converter, e)))?;
+    log_info!("Converting subscription with {}", converter);
+    download_text(url.as_str(), options).await
+}
+
+/// Parse one share link into a Mihomo proxy entry.
+pub fn parse_share_link(link: &str) -> Result<Value> {
+    let (scheme, rest) = link
+        .split_once("://")
+        .ok_or_else(|| CrashError::Config(format!("Not a share link: {:?}", link)))?;
+    let proxy = match scheme.to_ascii_lowercase().as_str() {
+        "vmess" => parse_vmess(rest)?,
+        "ss" => parse_ss(rest)?,
+        "trojan" => parse_trojan(link)?,
+        "vless" => parse_vless(link)?,
+        other => {
+            return Err(CrashError::Config(format!(
+                "Unsupported scheme {}:// (native conversion handles {})",
+                other,
+                NATIVE_SCHEMES.join(", ")
+            )));
+        }
+    };
+    Ok(Value::Object(proxy))
+}
+
+/// `vmess://` + base64 of the v2rayN JSON object.
+fn parse_vmess(rest: &str) -> Result<Map<String, Value>> {
+    let info: Value = serde_json::from_str(&decode_base64(rest)?)
+        .map_err(|e| CrashError::Config(format!("Invalid vmess link: {}", e)))?;
+    let field = |key: &str| match &info[key] {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    };
+
+    let server = field("add");
+    let mut proxy = base_proxy(&field("ps"), "vmess", &server, parse_port(&field("port"))?);
+    proxy.insert("uuid".into(), json!(field("id")));
+    proxy.insert(
+        "alterId".into(),
+        json!(field("aid").parse::<u32>().unwrap_or(0)),
+    );
+    let cipher = field("scy");
+    proxy.insert(
+        "cipher".into(),
+        json!(if cipher.is_empty() { "auto" } else { &cipher }),
+    );
+    if field("tls") == "tls" {
+        proxy.insert("tls".into(), json!(true));
+        set_nonempty(&mut proxy, "servername", &field("sni"));
+    }
+    set_nonempty(&mut proxy, "client-fingerprint", &field("fp"));
+    set_alpn(&mut proxy, &field("alpn"));
+    set_transport(&mut proxy, &field("net"), &field("host"), &field("path"));
+    Ok(proxy)
+}
+
+/// `ss://` in SIP002 form (`userinfo@host:port`, userinfo base64 or
+/// percent-encoded) or the legacy all-base64 form, with `#name`.
+fn parse_ss(rest: &str) -> Result<Map<String, Value>> {
+    let (rest, name) = split_fragment(rest);
+    let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+    if query.split('&').any(|pair| pair.starts_with("plugin=")) {
+        return Err(CrashError::Config(
+            "Unsupported ss:// plugin (native conversion handles plain ss only)".to_string(),
+        ));
+    }
+
+    let (userinfo, host_port) = match rest.rsplit_once('@') {
+        Some((userinfo, host_port)) => {
+            let userinfo = decode_base64(userinfo)
+                .ok()
+                .filter(|decoded| decoded.contains(':'))
+                .unwrap_or_else(|| percent_decode(userinfo));
+            (userinfo, host_port.trim_end_matches('/').to_string())
+        }
+        None => {
+            let decoded = decode_base64(rest)?;
+            let (userinfo, host_port) = decoded
+                .rsplit_once('@')
+                .ok_or_else(|| CrashError::Config(format!("Invalid ss link: {:?}", rest)))?;
+            (userinfo.to_string(), host_port.to_string())
+        }
+    };
+    let (cipher, password) = userinfo
+        .split_once(':')
+        .ok_or_else(|| CrashError::Config("Invalid ss link: missing cipher".to_string()))?;
+    let (server, port) = split_host_port(&host_port)?;
+
+    let mut proxy = base_proxy(&name, "ss", &server, port);
+    proxy.insert("cipher".into(), json!(cipher));
+    proxy.insert("password".into(), json!(password));
+    Ok(proxy)
+}
+
+/// `trojan://password@host:port?sni=..&type=ws&path=..#name`
+fn parse_trojan(link: &str) -> Result<Map<String, Value>> {
+    let url = parse_url(link)?;
+    let query = |key: &str| query_value(&url, key);
+
+    let mut proxy = base_proxy(
+        &percent_decode(url.fragment().unwrap_or("")),
+        "trojan",
+        &url_host(&url)?,
+        url_port(&url)?,
+    );
+    proxy.insert("password".into(), json!(percent_decode(url.username())));
+    set_nonempty(
+        &mut proxy,
+        "sni",
+        &query("sni").or_else(|| query("peer")).unwrap_or_default(),
+    );
+    if matches!(query("allowInsecure").as_deref(), Some("1" | "true")) {
+        proxy.insert("skip-cert-verify".into(), json!(true));
+    }
+    set_nonempty(
+        &mut proxy,
+        "client-fingerprint",
+        &query("fp").unwrap_or_default(),
+    );
+    set_alpn(&mut proxy, &query("alpn").unwrap_or_default());
+    set_transport(
+        &mut proxy,
+        &query("type").unwrap_or_default(),
+        &query("host").unwrap_or_default(),
+        &query("path")
+            .or_else(|| query("serviceName"))
+            .unwrap_or_default(),
+    );
+    Ok(proxy)
+}
+
+/// `vless://uuid@host:port?security=tls|reality&sni=..&pbk=..#name`
+fn parse_vless(link: &str) -> Result<Map<String, Value>> {
+    let url = parse_url(link)?;
+    let query = |key: &str| query_value(&url, key);
+    if let Some(encryption) = query("encryption")
+        && encryption != "none"
+    {
+        return Err(CrashError::Config(format!(
+            "Unsupported vless encryption {:?}",
+            encryption
+        )));
+    }
+
+    let mut proxy = base_proxy(
+        &percent_decode(url.fragment().unwrap_or("")),
+        "vless",
+        &url_host(&url)?,
+        url_port(&url)?,
+    );
+    proxy.insert("uuid".into(), json!(percent_decode(url.username())));
+    set_nonempty(&mut proxy, "flow", &query("flow").unwrap_or_default());
+    let security = query("security").unwrap_or_default();
+    if security == "tls" || security == "reality" {
+        proxy.insert("tls".into(), json!(true));
+        set_nonempty(&mut proxy, "servername", &query("sni").unwrap_or_default());
+    }
+    if security == "reality" {
+        let mut reality = Map::new();
+        set_nonempty(
+            &mut reality,
+            "public-key",
+            &query("pbk").unwrap_or_default(),
+        );
+        set_nonempty(&mut reality, "short-id", &query("sid").unwrap_or_default());
+        proxy.insert("reality-opts".into(), Value::Object(reality));
+    }
+    set_nonempty(
+        &mut proxy,
+        "client-fingerprint",
+        &query("fp").unwrap_or_default(),
+    );
+    set_alpn(&mut proxy, &query("alpn").unwrap_or_default());
+    set_transport(
+        &mut proxy,
+        &query("type").unwrap_or_default(),
+        &query("host").unwrap_or_default(),
+        &query("path")
+            .or_else(|| query("serviceName"))
+            .unwrap_or_default(),
+    );
+    Ok(proxy)
+}
+
+fn base_proxy(name: &str, kind: &str, server: &str, port: u16) -> Map<String, Value> {
+    let name = if name.trim().is_empty() {
+        format!("{}:{}", server, port)
+    } else {
+        name.trim().to_string()
+    };
+    let mut proxy = Map::new();
+    proxy.insert("name".into(), json!(name));
+    proxy.insert("type".into(), json!(kind));
+    proxy.insert("server".into(), json!(server));
+    proxy.insert("port".into(), json!(port));
+    proxy.insert("udp".into(), json!(true));
+    proxy
+}
+
+fn set_nonempty(proxy: &mut Map<String, Value>, key: &str, value: &str) {
+    if !value.is_empty() {
+        proxy.insert(key.into(), json!(value));
+    }
+}
+
+fn set_alpn(proxy: &mut Map<String, Value>, alpn: &str) {
+    let alpn: Vec<&str> = alpn
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .collect();
+    if !alpn.is_empty() {
+        proxy.insert("alpn".into(), json!(alpn));
+    }
+}
+
+/// Transport (`network`) options shared by the v2ray-style schemes.
+fn set_transport(proxy: &mut Map<String, Value>, network: &str, host: &str, path: &str) {
+    match network {
+        "ws" => {
+            let mut opts = Map::new();
+            set_nonempty(&mut opts, "path", path);
+            if !host.is_empty() {
+                opts.insert("headers".into(), json!({ "Host": host }));
+            }
+            proxy.insert("network".into(), json!("ws"));
+            proxy.insert("ws-opts".into(), Value::Object(opts));
+        }
+        "grpc" => {
+            proxy.insert("network".into(), json!("grpc"));
+            proxy.insert("grpc-opts".into(), json!({ "grpc-service-name": path }));
+        }
+        "h2" | "http" => {
+            let mut opts = Map::new();
+            if !host.is_empty() {
+                opts.insert("host".into(), json!([host]));
+            }
+            set_nonempty(&mut opts, "path", path);
+            proxy.insert("network".into(), json!("h2"));
+            proxy.insert("h2-opts".into(), Value::Object(opts));
+        }
+        // tcp (or unset) needs no options.
+        _ => {}
+    }
+}
+
+/// `name`, or `name 2`, `name 3`… if a proxy already uses it.
+fn unique_name(proxies: &[Value], name: &str) -> String {
+    let taken = |candidate: &str| proxies.iter().any(|p| p["name"] == candidate);
+    if !taken(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{} {}", name, n))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_default()
+}
+
+fn parse_url(link: &str) -> Result<reqwest::Url> {
+    reqwest::Url::parse(link)
+        .map_err(|e| CrashError::Config(format!("Invalid share link {:?}: {}", link, e)))
+}
+
+fn url_host(url: &reqwest::Url) -> Result<String> {
+    url.host_str()
+        .map(|host| host.trim_matches(|c| c == '[' || c == ']').to_string())
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| CrashError::Config(format!("Share link without a server: {}", url)))
+}
+
+fn url_port(url: &reqwest::Url) -> Result<u16> {
+    url.port()
+        .ok_or_else(|| CrashError::Config(format!("Share link without a port: {}", url)))
+}
+
+fn query_value(url: &reqwest::Url, key: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.trim().to_string())
+}
+
+fn parse_port(port: &str) -> Result<u16> {
+    port.trim()
+        .parse()
+        .ok()
+        .filter(|&p| p != 0)
+        .ok_or_else(|| CrashError::Config(format!("Invalid port {:?}", port)))
+}
+
+/// `host:port`, with the host optionally in `[...]` for IPv6.
+fn split_host_port(s: &str) -> Result<(String, u16)> {
+    let (host, port) = s
+        .rsplit_once(':')
+        .ok_or_else(|| CrashError::Config(format!("Missing port in {:?}", s)))?;
+    let host = host.trim_matches(|c| c == '[' || c == ']');
+    if host.is_empty() {
+        return Err(CrashError::Config(format!("Missing server in {:?}", s)));
+    }
+    Ok((host.to_string(), parse_port(port)?))
+}
+
+fn split_fragment(s: &str) -> (&str, String) {
+    match s.split_once('#') {
+        Some((rest, name)) => (rest, percent_decode(name)),
+        None => (s, String::new()),
+    }
+}
+
+/// Decode base64 (standard or URL-safe, padding optional) into UTF-8.
+fn decode_base64(s: &str) -> Result<String> {
+    let compact = s.trim().trim_end_matches('=');
+    let bytes = STANDARD_NO_PAD
+        .decode(compact)
+        .or_else(|_| URL_SAFE_NO_PAD.decode(compact))
+        .map_err(|e| CrashError::Config(format!("Invalid base64 in share link: {}", e)))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Decode `%XX` escapes; invalid escapes are kept as they are.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn b64(s: &str) -> String {
+        base64::engine::general_purpose::STANDARD.encode(s)
+    }
+
+    #[test]
+    fn vmess_link() {
+        let info = r#"{"v":"2","ps":"HK 01","add":"hk.example.com","port":"443","id":"a3482e88-686a-4a58-8126-99c9df64b7bf","aid":0,"scy":"auto","net":"ws","host":"cdn.example.com","path":"/ray","tls":"tls","sni":"hk.example.com"}"#;
+        let proxy = parse_share_link(&format!("vmess://{}", b64(info))).unwrap();
+        assert_eq!(proxy["name"], "HK 01");
+        assert_eq!(proxy["type"], "vmess");
+        assert_eq!(proxy["port"], 443);
+        assert_eq!(proxy["uuid"], "a3482e88-686a-4a58-8126-99c9df64b7bf");
+        assert_eq!(proxy["alterId"], 0);
+        assert_eq!(proxy["tls"], true);
+        assert_eq!(proxy["servername"], "hk.example.com");
+        assert_eq!(proxy["network"], "ws");
+        assert_eq!(proxy["ws-opts"]["path"], "/ray");
+        assert_eq!(proxy["ws-opts"]["headers"]["Host"], "cdn.example.com");
+    }
+
+    #[test]
+    fn ss_links() {
+        let sip002 = format!(
+            "ss://{}@1.2.3.4:8388#JP%20%E4%B8%9C%E4%BA%AC",
+            b64("aes-256-gcm:pa:ss")
+        );
+        let proxy = parse_share_link(&sip002).unwrap();
+        assert_eq!(proxy["name"], "JP 东京");
+        assert_eq!(proxy["server"], "1.2.3.4");
+        assert_eq!(proxy["port"], 8388);
+        assert_eq!(proxy["cipher"], "aes-256-gcm");
+        assert_eq!(proxy["password"], "pa:ss");
+
+        let plain = "ss://2022-blake3-aes-128-gcm:a%2Bb@[2001:db8::1]:443/#v6";
+        let proxy = parse_share_link(plain).unwrap();
+        assert_eq!(proxy["cipher"], "2022-blake3-aes-128-gcm");
+        assert_eq!(proxy["password"], "a+b");
+        assert_eq!(proxy["server"], "2001:db8::1");
+
+        let legacy = format!(
+            "ss://{}#old",
+            b64("chacha20-ietf-poly1305:pw@example.com:1080")
+        );
+        let proxy = parse_share_link(&legacy).unwrap();
+        assert_eq!(proxy["server"], "example.com");
+        assert_eq!(proxy["port"], 1080);
+
+        let plugin = format!("ss://{}@h:1?plugin=obfs-local", b64("aes-128-gcm:pw"));
+        assert!(parse_share_link(&plugin).is_err());
+    }
+
+    #[test]
+    fn trojan_link() {
+        let link = "trojan://p%40ss@example.com:443?sni=sni.example.com&allowInsecure=1&type=grpc&serviceName=svc#US";
+        let proxy = parse_share_link(link).unwrap();
+        assert_eq!(proxy["name"], "US");
+        assert_eq!(proxy["type"], "trojan");
+        assert_eq!(proxy["password"], "p@ss");
+        assert_eq!(proxy["sni"], "sni.example.com");
+        assert_eq!(proxy["skip-cert-verify"], true);
+        assert_eq!(proxy["network"], "grpc");
+        assert_eq!(proxy["grpc-opts"]["grpc-service-name"], "svc");
+    }
+
+    #[test]
+    fn vless_link() {
+        let link = "vless://a3482e88-686a-4a58-8126-99c9df64b7bf@example.com:443?encryption=none&security=reality&sni=www.microsoft.com&fp=chrome&pbk=PUBKEY&sid=6ba8&flow=xtls-rprx-vision&type=tcp#SG";
+        let proxy = parse_share_link(link).unwrap();
+        assert_eq!(proxy["name"], "SG");
+        assert_eq!(proxy["type"], "vless");
+        assert_eq!(proxy["flow"], "xtls-rprx-vision");
+        assert_eq!(proxy["tls"], true);
+        assert_eq!(proxy["servername"], "www.microsoft.com");
+        assert_eq!(proxy["client-fingerprint"], "chrome");
+        assert_eq!(proxy["reality-opts"]["public-key"], "PUBKEY");
+        assert_eq!(proxy["reality-opts"]["short-id"], "6ba8");
+        assert!(proxy.get("network").is_none());
+    }
+
+    #[test]
+    fn subscription_to_config() {
+        let body = format!(
+            "trojan://pw@a.example.com:443#same\n\nhysteria2://pw@b.example.com:443#h2\nss://{}@c.example.com:8388#same\n",
+            b64("aes-128-gcm:pw")
+        );
+        let conversion = convert_subscription(&body);
+        assert_eq!(conversion.proxies.len(), 2);
+        assert_eq!(conversion.proxies[1]["name"], "same 2");
+        assert_eq!(conversion.skipped.len(), 1);
+        assert!(conversion.skipped[0].0.starts_with("hysteria2://"));
+
+        let config = conversion.to_mihomo_config();
+        assert!(config.starts_with("mixed-port: 7890\n"));
+        assert!(config.contains("\nproxies:\n  - name: \"same\"\n"));
+        assert!(config.contains("\nrules:\n  - \"MATCH,PROXY\"\n"));
+    }
+}
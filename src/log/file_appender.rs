@@ -10,32 +10,120 @@
 // file contents into memory, which matters on memory-constrained devices
 // like routers. This also matches the rolling-file behaviour documented in
 // the README.
+//
+// With `LogRotation::Gzip` the full log is instead streamed into
+// `crash.log.<timestamp>.gz` and emptied, and archives beyond the configured
+// count are deleted. `crash logs rotate` does the same on demand.
 
 use crate::error::{CrashError, Result};
-use crate::log::LogLevel;
+use crate::log::{LogLevel, LogRotation};
+use crate::utils::current_timestamp;
 use crate::utils::fs::ensure_dir;
+use flate2::Compression;
+use flate2::write::GzEncoder;
 use std::fs::{File, OpenOptions};
 use std::io::Write as _;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Number of rotated backup files to keep (`crash.log.1` .. `crash.log.N`).
 const MAX_BACKUPS: usize = 5;
 
+/// Existing log files in `log_dir`, oldest first (`crash.log.N` .. `crash.log`).
+pub fn log_files(log_dir: &Path) -> Vec<PathBuf> {
+    (1..=MAX_BACKUPS)
+        .rev()
+        .map(|n| log_dir.join(format!("crash.log.{}", n)))
+        .chain(std::iter::once(log_dir.join("crash.log")))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// Timestamp of a `crash.log.<timestamp>.gz` archive name.
+fn archive_timestamp(name: &str) -> Option<u64> {
+    name.strip_prefix("crash.log.")?
+        .strip_suffix(".gz")?
+        .parse()
+        .ok()
+}
+
+/// Compressed log archives in `log_dir`, oldest first.
+pub fn log_archives(log_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(log_dir) else {
+        return Vec::new();
+    };
+    let mut archives: Vec<(u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let ts = archive_timestamp(&entry.file_name().to_string_lossy())?;
+            Some((ts, entry.path()))
+        })
+        .collect();
+    archives.sort();
+    archives.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Compress `crash.log` into `crash.log.<timestamp>.gz`, empty it, and delete
+/// all but the newest `keep` archives (never the new one). The log is copied then truncated
+/// rather than renamed, so other crash processes (the daemon) holding it
+/// open in append mode carry on writing into the fresh log. Returns the new
+/// archive, or `None` if there was nothing to archive.
+pub fn archive_log(log_dir: &Path, keep: usize) -> Result<Option<PathBuf>> {
+    let log_path = log_dir.join("crash.log");
+    let log_err = |e: std::io::Error| {
+        CrashError::Log(format!("Failed to archive {}: {}", log_path.display(), e))
+    };
+    let mut archived = None;
+
+    if std::fs::metadata(&log_path).is_ok_and(|m| m.len() > 0) {
+        let mut ts = current_timestamp();
+        while log_dir.join(format!("crash.log.{}.gz", ts)).exists() {
+            ts += 1;
+        }
+        let archive = log_dir.join(format!("crash.log.{}.gz", ts));
+
+        let mut encoder = GzEncoder::new(File::create(&archive)?, Compression::default());
+        std::io::copy(&mut File::open(&log_path).map_err(log_err)?, &mut encoder)
+            .map_err(log_err)?;
+        encoder.finish().map_err(log_err)?;
+        OpenOptions::new()
+            .write(true)
+            .open(&log_path)
+            .and_then(|file| file.set_len(0))
+            .map_err(log_err)?;
+        archived = Some(archive);
+    }
+
+    let archives = log_archives(log_dir);
+    for old in &archives[..archives.len().saturating_sub(keep.max(1))] {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(archived)
+}
+
 pub struct FileAppender {
     log_dir: PathBuf,
     current_file: Option<File>,
     current_size: u64,
     max_file_size: u64,
+    rotation: LogRotation,
+    max_archives: usize,
 }
 
 impl FileAppender {
-    pub fn new(log_dir: PathBuf, max_file_size: u64) -> Result<Self> {
+    pub fn new(
+        log_dir: PathBuf,
+        max_file_size: u64,
+        rotation: LogRotation,
+        max_archives: usize,
+    ) -> Result<Self> {
         ensure_dir(&log_dir)?;
         let mut appender = Self {
             log_dir,
             current_file: None,
             current_size: 0,
             max_file_size,
+            rotation,
+            max_archives,
         };
         appender.open_current_file()?;
         Ok(appender)
@@ -75,6 +163,13 @@ impl FileAppender {
     /// backups down and dropping the one that falls off the end. Cheap: a
     /// handful of `rename` calls, no file reads.
     fn rotate(&mut self) -> Result<()> {
+        if self.rotation == LogRotation::Gzip {
+            // The file stays open: it is emptied, and appends go to its end.
+            archive_log(&self.log_dir, self.max_archives)?;
+            self.current_size = 0;
+            return Ok(());
+        }
+
         self.current_file = None;
 
         // Drop the oldest backup if it exists.
@@ -123,3 +218,70 @@ impl FileAppender {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{get_log_dir, override_config_dir};
+    use std::io::Read as _;
+
+    #[test]
+    fn rotates_into_backups_when_full() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _guard = override_config_dir(tmp.path());
+        let log_dir = get_log_dir();
+
+        let mut appender = FileAppender::new(log_dir.clone(), 8, LogRotation::Numbered, 0).unwrap();
+        for i in 0..3 {
+            appender
+                .write_log(LogLevel::Info, &format!("line {}", i))
+                .unwrap();
+        }
+
+        assert_eq!(
+            log_files(&log_dir),
+            vec![
+                log_dir.join("crash.log.2"),
+                log_dir.join("crash.log.1"),
+                log_dir.join("crash.log"),
+            ]
+        );
+        let current = std::fs::read_to_string(log_dir.join("crash.log")).unwrap();
+        assert_eq!(current, "line 2\n");
+    }
+
+    #[test]
+    fn gzip_rotation_archives_and_prunes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log_dir = tmp.path().to_path_buf();
+        for ts in [100, 200, 300] {
+            std::fs::write(log_dir.join(format!("crash.log.{}.gz", ts)), b"").unwrap();
+        }
+
+        let mut appender = FileAppender::new(log_dir.clone(), 8, LogRotation::Gzip, 2).unwrap();
+        appender.write_log(LogLevel::Info, "line 0").unwrap();
+        appender.write_log(LogLevel::Info, "line 1").unwrap();
+
+        // line 0 went into a new archive, the two oldest were dropped.
+        let archives = log_archives(&log_dir);
+        assert_eq!(archives.len(), 2);
+        assert_eq!(archives[0], log_dir.join("crash.log.300.gz"));
+        let mut archived = String::new();
+        flate2::read::GzDecoder::new(File::open(&archives[1]).unwrap())
+            .read_to_string(&mut archived)
+            .unwrap();
+        assert_eq!(archived, "line 0\n");
+        let current = std::fs::read_to_string(log_dir.join("crash.log")).unwrap();
+        assert_eq!(current, "line 1\n");
+
+        // Nothing to archive: only pruning happens.
+        std::fs::write(log_dir.join("crash.log"), b"").unwrap();
+        assert_eq!(archive_log(&log_dir, 1).unwrap(), None);
+        assert_eq!(log_archives(&log_dir), archives[1..]);
+
+        // Keeping none would delete the archive just written.
+        std::fs::write(log_dir.join("crash.log"), b"line 2\n").unwrap();
+        let archive = archive_log(&log_dir, 0).unwrap().unwrap();
+        assert_eq!(log_archives(&log_dir), [archive]);
+    }
+}
@@ -0,0 +1,114 @@
+// Log reading and filtering for the `logs` command.
+//
+// Parses both the `[timestamp] [LEVEL] [module] message` text lines and
+// the JSON lines written by `LogFormatter`. Lines that don't parse (e.g. continuation lines of a
+// multi-line message) inherit the verdict of the entry they follow.
+
+use crate::log::LogLevel;
+use std::time::{Duration, SystemTime};
+
+/// Filter applied to log lines by the `logs` command.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogFilter {
+    /// Minimum level to show.
+    pub level: Option<LogLevel>,
+    /// Only show entries newer than `now - since`.
+    pub since: Option<Duration>,
+}
+
+impl LogFilter {
+    pub fn is_active(&self) -> bool {
+        self.level.is_some() || self.since.is_some()
+    }
+
+    /// Return the lines of `content` that pass the filter, relative to `now`.
+    pub fn apply<'a>(&self, content: &'a str, now: SystemTime) -> Vec<&'a str> {
+        let cutoff = self.since.and_then(|since| now.checked_sub(since));
+        let mut keep = !self.is_active();
+        let mut out = Vec::new();
+
+        for line in content.lines() {
+            if let Some((timestamp, level)) = parse_line(line) {
+                keep = self.level.is_none_or(|min| level >= min)
+                    && cutoff.is_none_or(|cutoff| timestamp >= cutoff);
+            }
+            if keep {
+                out.push(line);
+            }
+        }
+
+        out
+    }
+}
+
+/// Parse the timestamp and level of a `LogFormatter` line.
+pub fn parse_line(line: &str) -> Option<(SystemTime, LogLevel)> {
+    if line.starts_with('{') {
+        let v: serde_json::Value = serde_json::from_str(line).ok()?;
+        let timestamp = humantime::parse_rfc3339_weak(v["ts"].as_str()?).ok()?;
+        let level = v["level"].as_str()?.parse().ok()?;
+        return Some((timestamp, level));
+    }
+
+    let rest = line.strip_prefix('[')?;
+    let (timestamp, rest) = rest.split_once("] [")?;
+    let (level, _) = rest.split_once(']')?;
+
+    let timestamp = humantime::parse_rfc3339_weak(timestamp).ok()?;
+    let level = level.parse().ok()?;
+    Some((timestamp, level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[2026-07-02T12:00:00.000Z] [INFO] [crash] starting
+[2026-07-02T12:30:00.000Z] [WARN] [crash::config] slow mirror
+[2026-07-02T12:59:00.000Z] [ERROR] [crash] failed:
+  caused by: timeout
+[2026-07-02T12:59:30.000Z] [DEBUG] [crash] details";
+
+    fn now() -> SystemTime {
+        humantime::parse_rfc3339("2026-07-02T13:00:00Z").unwrap()
+    }
+
+    #[test]
+    fn parses_formatter_output() {
+        let (_, level) = parse_line("[2026-07-02T12:34:56.123Z] [WARN] [m] msg").unwrap();
+        assert_eq!(level, LogLevel::Warn);
+        assert!(parse_line("  caused by: timeout").is_none());
+
+        let json = r#"{"ts":"2026-07-02T12:34:56.123Z","level":"ERROR","module":"m","msg":"x"}"#;
+        assert_eq!(parse_line(json).unwrap().1, LogLevel::Error);
+    }
+
+    #[test]
+    fn no_filter_passes_everything() {
+        let lines = LogFilter::default().apply(LOG, now());
+        assert_eq!(lines.len(), LOG.lines().count());
+    }
+
+    #[test]
+    fn level_filter_keeps_continuation_lines() {
+        let filter = LogFilter {
+            level: Some(LogLevel::Warn),
+            since: None,
+        };
+        let lines = filter.apply(LOG, now());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], "  caused by: timeout");
+    }
+
+    #[test]
+    fn since_filter_drops_old_entries() {
+        let filter = LogFilter {
+            level: None,
+            since: Some(Duration::from_secs(10 * 60)),
+        };
+        let lines = filter.apply(LOG, now());
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("[ERROR]"));
+    }
+}
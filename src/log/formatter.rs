@@ -2,6 +2,7 @@
 
 use crate::log::LogLevel;
 use humantime::format_rfc3339_millis;
+use serde_json::json;
 use std::time::SystemTime;
 
 pub struct LogFormatter;
@@ -21,6 +22,18 @@ impl LogFormatter {
         )
     }
 
+    /// Format a log message as a single-line JSON object with `ts`, `level`,
+    /// `module` and `msg` fields, for log aggregation.
+    pub fn format_json(level: LogLevel, module: &str, message: &str) -> String {
+        json!({
+            "ts": format_rfc3339_millis(SystemTime::now()).to_string(),
+            "level": level.as_str(),
+            "module": module,
+            "msg": Self::sanitize_sensitive_info(message),
+        })
+        .to_string()
+    }
+
     /// Sanitize sensitive information from log messages
     /// Replaces common patterns for secrets, passwords, tokens, etc.
     fn sanitize_sensitive_info(message: &str) -> String {
@@ -58,3 +71,27 @@ impl LogFormatter {
         sanitized
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn json_output_is_valid_single_line() {
+        let line = LogFormatter::format_json(LogLevel::Warn, "crash::x", "a \"quoted\"\nmsg");
+        assert!(!line.contains('\n'));
+        let v: Value = serde_json::from_str(&line).expect("valid json");
+        assert_eq!(v["level"], "WARN");
+        assert_eq!(v["module"], "crash::x");
+        assert_eq!(v["msg"], "a \"quoted\"\nmsg");
+        assert!(humantime::parse_rfc3339(v["ts"].as_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn json_output_is_sanitized() {
+        let line = LogFormatter::format_json(LogLevel::Info, "m", "secret=hunter2 ok");
+        let v: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(v["msg"], "secret=******* ok");
+    }
+}
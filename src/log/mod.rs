@@ -2,17 +2,23 @@
 
 use crate::config::get_log_dir;
 use crate::error::{CrashError, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex, OnceLock};
+use strum::Display;
 
 mod file_appender;
+mod filter;
 mod formatter;
 
-pub use file_appender::FileAppender;
+pub use file_appender::{FileAppender, archive_log, log_archives, log_files};
+pub use filter::LogFilter;
 pub use formatter::LogFormatter;
 
 /// Log level enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum LogLevel {
     Trace = 0,
     Debug = 1,
@@ -33,12 +39,60 @@ impl LogLevel {
     }
 }
 
+impl FromStr for LogLevel {
+    type Err = CrashError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            LogLevel::Trace,
+            LogLevel::Debug,
+            LogLevel::Info,
+            LogLevel::Warn,
+            LogLevel::Error,
+        ]
+        .into_iter()
+        .find(|level| level.as_str().eq_ignore_ascii_case(s))
+        .ok_or_else(|| CrashError::Log(format!("Unknown log level: {}", s)))
+    }
+}
+
+/// On-disk log line format
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Display, Serialize, Deserialize, ValueEnum,
+)]
+pub enum LogFormat {
+    /// `[timestamp] [LEVEL] [module] message`
+    #[default]
+    Text,
+    /// One `{ts, level, module, msg}` JSON object per line
+    Json,
+}
+
+/// What happens to `crash.log` when it is full
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Display, Serialize, Deserialize, ValueEnum,
+)]
+pub enum LogRotation {
+    /// Shift into `crash.log.1` .. `crash.log.5`
+    #[default]
+    Numbered,
+    /// Compress into `crash.log.<timestamp>.gz`, keeping a configurable count
+    Gzip,
+}
+
+/// Compressed archives kept by default with `LogRotation::Gzip`.
+pub const DEFAULT_LOG_ARCHIVES: usize = 5;
+
 /// Configuration for the logging system
 #[derive(Debug, Clone)]
 pub struct LogConfig {
     pub log_dir: PathBuf,
     pub log_level: LogLevel,
     pub max_file_size: u64,
+    pub format: LogFormat,
+    pub rotation: LogRotation,
+    /// Archives kept by `LogRotation::Gzip`.
+    pub max_archives: usize,
 }
 
 impl Default for LogConfig {
@@ -47,6 +101,9 @@ impl Default for LogConfig {
             log_dir: get_log_dir(),
             log_level: LogLevel::Info,
             max_file_size: 1024 * 1024, // 1MB
+            format: LogFormat::default(),
+            rotation: LogRotation::default(),
+            max_archives: DEFAULT_LOG_ARCHIVES,
         }
     }
 }
@@ -59,7 +116,12 @@ pub struct Logger {
 
 impl Logger {
     fn new(config: LogConfig) -> Result<Self> {
-        let appender = FileAppender::new(config.log_dir.clone(), config.max_file_size)?;
+        let appender = FileAppender::new(
+            config.log_dir.clone(),
+            config.max_file_size,
+            config.rotation,
+            config.max_archives,
+        )?;
 
         Ok(Self {
             config,
@@ -72,7 +134,10 @@ impl Logger {
             return;
         }
 
-        let formatted = LogFormatter::format_with_timestamp(level, module, message);
+        let formatted = match self.config.format {
+            LogFormat::Text => LogFormatter::format_with_timestamp(level, module, message),
+            LogFormat::Json => LogFormatter::format_json(level, module, message),
+        };
 
         if let Ok(mut appender) = self.appender.lock() {
             let _ = appender.write_log(level, &formatted);
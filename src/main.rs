@@ -7,8 +7,10 @@
 // )]
 
 use clap::Parser;
-use crash::cli::Cli;
 use crash::cli::commands::handle;
+use crash::cli::setup::is_first_run;
+use crash::cli::{Cli, Commands};
+use crash::config::{CrashConfig, get_log_dir};
 use crash::error::Result;
 use crash::log::{LogConfig, init_logger};
 use crash::{log_error, log_info};
@@ -39,6 +41,8 @@ fn attach_console() {
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
+    let first_run = is_first_run();
+
     // Initialize logging system
     if let Err(e) = init_logging() {
         eprintln!("Failed to initialize logging: {}", e);
@@ -48,7 +52,7 @@ async fn main() {
     log_info!("Crash application starting");
 
     // Run the application and handle errors
-    if let Err(e) = run().await {
+    if let Err(e) = run(first_run).await {
         log_error!("Application error: {}", e);
         eprintln!("Error: {}", e);
         std::process::exit(1);
@@ -59,14 +63,37 @@ async fn main() {
 
 /// Initialize the logging system
 fn init_logging() -> Result<()> {
-    let config = LogConfig::default();
+    // The logger isn't up yet, so a config that fails to load just falls
+    // back to defaults here; the command itself will report the error.
+    let crash_config = CrashConfig::read().unwrap_or_default();
+    let config = LogConfig {
+        log_dir: crash_config.log_dir(),
+        format: crash_config.log_format,
+        rotation: crash_config.log_rotation,
+        max_archives: crash_config.log_archives,
+        ..LogConfig::default()
+    };
 
-    init_logger(config)?;
-    Ok(())
+    let fallback = get_log_dir();
+    match init_logger(config.clone()) {
+        Err(e) if config.log_dir != fallback => {
+            eprintln!(
+                "Warning: cannot log to {} ({}), logging to {} instead",
+                config.log_dir.display(),
+                e,
+                fallback.display()
+            );
+            init_logger(LogConfig {
+                log_dir: fallback,
+                ..config
+            })
+        }
+        result => result,
+    }
 }
 
 /// Main application logic
-async fn run() -> Result<()> {
+async fn run(first_run: bool) -> Result<()> {
     #[cfg(windows)]
     {
         if std::env::args().find(|i| i == "--schedule").is_none() {
@@ -77,6 +104,10 @@ async fn run() -> Result<()> {
     let cli = Cli::parse();
 
     log_info!("Parsed CLI arguments");
-    handle(cli.command).await?;
+    let command = match cli.command {
+        None if first_run => Some(Commands::Setup),
+        command => command,
+    };
+    handle(command).await?;
     Ok(())
 }
@@ -0,0 +1,148 @@
+// Archive extraction used when `ei` cannot install a download.
+//
+// As in `decode`, the format is detected from the content rather than the
+// URL: zip archives, and tarballs or single files compressed with
+// gzip/xz/zstd (or not at all), which covers the core and UI releases.
+
+use crate::error::{CrashError, Result};
+use crate::utils::decode::decompress;
+use crate::utils::fs::ensure_dir;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// `ustar` marker at offset 257 of the first tar header.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// Compression suffixes stripped from `file_name` when the download is a
+/// single compressed file rather than an archive.
+const COMPRESSED_SUFFIXES: [&str; 3] = [".gz", ".xz", ".zst"];
+
+/// Extract `bytes` into `dest`. A download that is neither a zip nor a
+/// tarball is written as a single file named after `file_name`.
+pub fn extract_archive(bytes: &[u8], file_name: &str, dest: &Path) -> Result<()> {
+    ensure_dir(dest)?;
+
+    if bytes.starts_with(ZIP_MAGIC) {
+        return zip::ZipArchive::new(Cursor::new(bytes))
+            .and_then(|mut archive| archive.extract(dest))
+            .map_err(|e| CrashError::Download(format!("Failed to extract zip: {}", e)));
+    }
+
+    let data = decompress(bytes)?;
+    if data.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+        return tar::Archive::new(&data[..])
+            .unpack(dest)
+            .map_err(|e| CrashError::Download(format!("Failed to extract tar: {}", e)));
+    }
+
+    let name = COMPRESSED_SUFFIXES
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .unwrap_or(file_name);
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(CrashError::Download(format!(
+            "Cannot derive a file name from {:?}",
+            file_name
+        )));
+    }
+    std::fs::write(dest.join(name), data)?;
+    Ok(())
+}
+
+/// Directory holding the extracted content: `dir` itself, or its only
+/// entry when the archive wraps everything in a single top-level folder.
+pub fn content_root(dir: &Path) -> Result<PathBuf> {
+    let entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    match entries.as_slice() {
+        [entry] if entry.path().is_dir() => Ok(entry.path()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+/// Largest regular file below `dir`, which for core releases is the binary
+/// (archives may also carry a README or LICENSE).
+pub fn largest_file(dir: &Path) -> Result<Option<PathBuf>> {
+    let mut largest: Option<(u64, PathBuf)> = None;
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else if meta.is_file() && largest.as_ref().is_none_or(|(size, _)| meta.len() > *size)
+            {
+                largest = Some((meta.len(), entry.path()));
+            }
+        }
+    }
+
+    Ok(largest.map(|(_, path)| path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn tar_gz(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn extracts_tar_gz_and_finds_wrapped_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bytes = tar_gz(&[
+            ("dist/index.html", b"<html></html>"),
+            ("dist/assets/app.js", b"console.log(1)"),
+        ]);
+
+        extract_archive(&bytes, "ui.tar.gz", tmp.path()).unwrap();
+        let root = content_root(tmp.path()).unwrap();
+        assert_eq!(root, tmp.path().join("dist"));
+        assert!(root.join("index.html").is_file());
+    }
+
+    #[test]
+    fn extracts_zip_and_picks_largest_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("README.md", options).unwrap();
+        zip.write_all(b"readme").unwrap();
+        zip.start_file("sing-box/sing-box.exe", options).unwrap();
+        zip.write_all(&[0u8; 4096]).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        extract_archive(&bytes, "sing-box.zip", tmp.path()).unwrap();
+        assert_eq!(
+            largest_file(tmp.path()).unwrap(),
+            Some(tmp.path().join("sing-box").join("sing-box.exe"))
+        );
+    }
+
+    #[test]
+    fn single_gzip_file_is_named_after_download() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"\x7fELF").unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        extract_archive(&bytes, "mihomo-linux-amd64.gz", tmp.path()).unwrap();
+        assert_eq!(
+            std::fs::read(tmp.path().join("mihomo-linux-amd64")).unwrap(),
+            b"\x7fELF"
+        );
+    }
+}
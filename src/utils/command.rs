@@ -4,10 +4,35 @@ use crate::{
     error::{CrashError, Result},
     log_info,
 };
-use std::process::{Command, Stdio};
+use std::process::{Command, Output, Stdio};
 
 /// Execute a command synchronously and return its output
 pub fn execute(cmd: &str, args: &[&str]) -> Result<String> {
+    let output = run(cmd, args)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(CrashError::Platform(format!(
+            "Command '{}' failed with status {}: {}",
+            cmd, output.status, stderr
+        )))
+    }
+}
+
+/// Execute a command synchronously and return whether it succeeded along
+/// with its stdout and stderr combined, for tools that report diagnostics
+/// on either stream.
+pub fn execute_combined(cmd: &str, args: &[&str]) -> Result<(bool, String)> {
+    let output = run(cmd, args)?;
+
+    let mut combined = String::from_utf8_lossy(&output.stdout).to_string();
+    combined.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), combined))
+}
+
+fn run(cmd: &str, args: &[&str]) -> Result<Output> {
     log_info!("execute {} {}", cmd, args.join(" "));
     let mut c = Command::new(cmd);
     c.args(args);
@@ -23,17 +48,6 @@ pub fn execute(cmd: &str, args: &[&str]) -> Result<String> {
         .stderr(Stdio::piped())
         .stdout(Stdio::piped());
 
-    let output = c
-        .output()
-        .map_err(|e| CrashError::Platform(format!("Failed to execute command '{}': {}", cmd, e)))?;
-
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(CrashError::Platform(format!(
-            "Command '{}' failed with status {}: {}",
-            cmd, output.status, stderr
-        )))
-    }
+    c.output()
+        .map_err(|e| CrashError::Platform(format!("Failed to execute command '{}': {}", cmd, e)))
 }
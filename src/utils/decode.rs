@@ -0,0 +1,130 @@
+// Decoding of downloaded subscription/config bodies.
+//
+// Providers may serve the config compressed (gzip, xz, zstd) or as a
+// base64 blob. The codec is detected from the content itself rather than
+// from HTTP headers, which many mirrors get wrong. Pure-Rust decoders are
+// used so cross builds for router targets don't need a C toolchain.
+
+use crate::error::{CrashError, Result};
+use base64::Engine as _;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use std::io::Read;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decode a downloaded config body into text: decompress gzip/xz/zstd
+/// (detected by magic bytes), then unwrap a base64-encoded body if the
+/// result is one. Plain text passes through unchanged.
+pub fn decode_subscription_body(bytes: &[u8]) -> Result<String> {
+    let bytes = decompress(bytes)?;
+    let text = String::from_utf8(bytes)?;
+    Ok(decode_base64(&text).unwrap_or(text))
+}
+
+/// Decompress `bytes` according to their magic number, or return them as-is.
+pub(crate) fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    if bytes.starts_with(GZIP_MAGIC) {
+        flate2::read::MultiGzDecoder::new(bytes)
+            .read_to_end(&mut out)
+            .map_err(|e| CrashError::Download(format!("Invalid gzip data: {}", e)))?;
+    } else if bytes.starts_with(XZ_MAGIC) {
+        lzma_rs::xz_decompress(&mut &bytes[..], &mut out)
+            .map_err(|e| CrashError::Download(format!("Invalid xz data: {}", e)))?;
+    } else if bytes.starts_with(ZSTD_MAGIC) {
+        ruzstd::decoding::StreamingDecoder::new(bytes)
+            .map_err(|e| CrashError::Download(format!("Invalid zstd data: {}", e)))?
+            .read_to_end(&mut out)
+            .map_err(|e| CrashError::Download(format!("Invalid zstd data: {}", e)))?;
+    } else {
+        out.extend_from_slice(bytes);
+    }
+
+    Ok(out)
+}
+
+/// Decode `text` if it is entirely base64 (standard or URL-safe, padding
+/// optional, line breaks allowed) and decodes to UTF-8 text.
+fn decode_base64(text: &str) -> Option<String> {
+    let compact: String = text.split_whitespace().collect();
+    if compact.is_empty() {
+        return None;
+    }
+    let compact = compact.trim_end_matches('=');
+
+    let decoded = STANDARD_NO_PAD
+        .decode(compact)
+        .or_else(|_| URL_SAFE_NO_PAD.decode(compact))
+        .ok()?;
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const CONFIG: &str = "port: 7890\nmode: rule\nproxies:\n  - name: a\n";
+
+    #[test]
+    fn plain_text_passes_through() {
+        assert_eq!(decode_subscription_body(CONFIG.as_bytes()).unwrap(), CONFIG);
+        let json = r#"{"outbounds":[]}"#;
+        assert_eq!(decode_subscription_body(json.as_bytes()).unwrap(), json);
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(CONFIG.as_bytes()).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(decode_subscription_body(&bytes).unwrap(), CONFIG);
+    }
+
+    #[test]
+    fn xz_round_trip() {
+        let mut bytes = Vec::new();
+        lzma_rs::xz_compress(&mut CONFIG.as_bytes(), &mut bytes).unwrap();
+        assert!(bytes.starts_with(XZ_MAGIC));
+        assert_eq!(decode_subscription_body(&bytes).unwrap(), CONFIG);
+    }
+
+    #[test]
+    fn zstd_round_trip() {
+        let bytes = ruzstd::encoding::compress_to_vec(
+            CONFIG.as_bytes(),
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        assert!(bytes.starts_with(ZSTD_MAGIC));
+        assert_eq!(decode_subscription_body(&bytes).unwrap(), CONFIG);
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(CONFIG);
+        assert_eq!(
+            decode_subscription_body(encoded.as_bytes()).unwrap(),
+            CONFIG
+        );
+
+        // Wrapped lines, as some providers serve them.
+        let wrapped = encoded
+            .as_bytes()
+            .chunks(16)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            decode_subscription_body(wrapped.as_bytes()).unwrap(),
+            CONFIG
+        );
+    }
+
+    #[test]
+    fn corrupt_compressed_data_is_an_error() {
+        assert!(decode_subscription_body(&[0x1f, 0x8b, 0, 1, 2]).is_err());
+    }
+}
@@ -1,10 +1,26 @@
 // Download management module with retry logic
 
 use crate::error::{CrashError, Result};
+use crate::utils::decode::decode_subscription_body;
 use crate::{log_debug, log_error, log_info, log_warn};
 use reqwest::Client;
-use std::sync::OnceLock;
-use std::time::Duration;
+use reqwest::redirect::Policy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, Semaphore};
+
+/// Redirect hops followed before a download is abandoned. GitHub proxies
+/// normally need one or two.
+const MAX_REDIRECTS: usize = 5;
+
+/// Message of the error a cancelled download fails with.
+const CANCELLED: &str = "cancelled";
 
 pub fn new_client() -> &'static Client {
     static CLIENT: OnceLock<Client> = OnceLock::new();
@@ -13,58 +29,465 @@ pub fn new_client() -> &'static Client {
             .timeout(Duration::from_secs(600))
             .connect_timeout(Duration::from_secs(30))
             .tcp_keepalive(Duration::from_secs(60))
+            .redirect(redirect_policy())
             .build()
             .expect("Failed to create HTTP client")
     })
 }
 
-const INITIAL_DELAY_MS: u64 = 1000; // 1 second
-const MAX_DELAY_MS: u64 = 30000; // 30 seconds
-const MAX_RETRIES: u32 = 3;
+/// Client for crash's own downloads. Unlike `new_client` it has no total
+/// timeout, so a slow but progressing download isn't cut off; stalls are
+/// caught by the idle timeout instead. Built with the connect timeout of the
+/// first download.
+fn download_client(timeout: &TimeoutConfig) -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let mut builder = reqwest::Client::builder()
+            .tcp_keepalive(Duration::from_secs(60))
+            .redirect(redirect_policy());
+        if let Some(connect) = timeout.connect() {
+            builder = builder.connect_timeout(connect);
+        }
+        builder.build().expect("Failed to create HTTP client")
+    })
+}
+
+/// Client for the core's controller API on localhost. Each request is
+/// bounded by the connect timeout and, as a whole, by the idle timeout, so a
+/// hung controller can't stall the daemon. Built with the timeouts of the
+/// first request.
+pub fn controller_client(timeout: &TimeoutConfig) -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let mut builder = reqwest::Client::builder();
+        if let Some(connect) = timeout.connect() {
+            builder = builder.connect_timeout(connect);
+        }
+        if let Some(idle) = timeout.idle() {
+            builder = builder.timeout(idle);
+        }
+        builder.build().expect("Failed to create HTTP client")
+    })
+}
+
+/// Follow at most `MAX_REDIRECTS` hops and stop early when a mirror
+/// redirects back to a URL already visited.
+fn redirect_policy() -> Policy {
+    Policy::custom(|attempt| {
+        if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error(format!("more than {} redirects", MAX_REDIRECTS))
+        } else if attempt.previous().contains(attempt.url()) {
+            let msg = format!("redirect loop at {}", attempt.url());
+            attempt.error(msg)
+        } else {
+            log_debug!("Following redirect to {}", attempt.url());
+            attempt.follow()
+        }
+    })
+}
+
+/// Retry and backoff settings for crash's own downloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Delay before the first retry; doubled for each further retry.
+    pub initial_delay_ms: u64,
+    /// Ceiling for the backoff delay.
+    pub max_delay_ms: u64,
+    /// Retries after the first attempt.
+    pub max_retries: u32,
+    /// Wall-clock budget after which no further retry is started (0 =
+    /// unlimited). A running attempt is never cut off by it; stalls are left
+    /// to the idle timeout.
+    pub max_total_secs: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 1000,
+            max_delay_ms: 30000,
+            max_retries: 3,
+            max_total_secs: 300,
+        }
+    }
+}
+
+impl RetryConfig {
+    fn max_total(&self) -> Option<Duration> {
+        (self.max_total_secs > 0).then(|| Duration::from_secs(self.max_total_secs))
+    }
+}
+
+impl std::fmt::Display for RetryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "max retries: {}, initial delay: {}ms, max delay: {}ms, max total: {}",
+            self.max_retries,
+            self.initial_delay_ms,
+            self.max_delay_ms,
+            match self.max_total_secs {
+                0 => "unlimited".to_string(),
+                secs => format!("{}s", secs),
+            }
+        )
+    }
+}
+
+/// Connection timeouts for crash's own downloads, separate from the retry
+/// budget in `RetryConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    /// Limit for establishing the connection (0 = none).
+    pub connect_secs: u64,
+    /// Limit for the wait for the response and for each chunk of the body;
+    /// restarts whenever data arrives (0 = none).
+    pub idle_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: 10,
+            idle_secs: 30,
+        }
+    }
+}
+
+impl TimeoutConfig {
+    fn connect(&self) -> Option<Duration> {
+        (self.connect_secs > 0).then(|| Duration::from_secs(self.connect_secs))
+    }
+
+    fn idle(&self) -> Option<Duration> {
+        (self.idle_secs > 0).then(|| Duration::from_secs(self.idle_secs))
+    }
+}
+
+impl std::fmt::Display for TimeoutConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = |secs: u64| match secs {
+            0 => "none".to_string(),
+            secs => format!("{}s", secs),
+        };
+        write!(
+            f,
+            "connect: {}, idle: {}",
+            secs(self.connect_secs),
+            secs(self.idle_secs)
+        )
+    }
+}
+
+/// Handle that aborts the downloads it was given to, from any task. Clones
+/// share the same state, so one `cancel` stops all of them; a cancelled
+/// download fails with `CrashError::Download("cancelled")`.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadCancel(Arc<CancelState>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl DownloadCancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort the downloads using this handle, and any started with it later.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once `cancel` has been called.
+    async fn cancelled(&self) {
+        loop {
+            // Registered before the check, so a `cancel` in between still
+            // wakes it.
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Per-invocation download settings derived from `CrashConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptions {
+    /// Maximum number of downloads in flight at once (0 = unlimited).
+    pub max_concurrent: usize,
+    /// Bandwidth cap in bytes per second (0 = unlimited).
+    pub rate_limit: u64,
+    /// Retry and backoff settings.
+    pub retry: RetryConfig,
+    /// Connect and idle timeouts of each attempt.
+    pub timeout: TimeoutConfig,
+    /// Aborts the download when cancelled, e.g. from an embedding GUI.
+    pub cancel: Option<DownloadCancel>,
+}
+
+/// Global permit pool bounding concurrent downloads, one per configured
+/// limit so that a changed setting takes effect; `None` when downloads are
+/// unlimited.
+fn download_permits(max_concurrent: usize) -> Option<&'static Semaphore> {
+    static PERMITS: OnceLock<Mutex<BTreeMap<usize, &'static Semaphore>>> = OnceLock::new();
+    if max_concurrent == 0 {
+        return None;
+    }
+    let mut pools = PERMITS.get_or_init(Default::default).lock().ok()?;
+    // A process only ever sees a handful of settings, so leaking is fine.
+    Some(
+        pools
+            .entry(max_concurrent)
+            .or_insert_with(|| Box::leak(Box::new(Semaphore::new(max_concurrent)))),
+    )
+}
+
+/// How long to pause so that `bytes` received over `elapsed` stays within
+/// `rate_limit` bytes per second.
+fn throttle_delay(bytes: u64, elapsed: Duration, rate_limit: u64) -> Duration {
+    if rate_limit == 0 {
+        return Duration::ZERO;
+    }
+    let expected = Duration::from_secs_f64(bytes as f64 / rate_limit as f64);
+    expected.saturating_sub(elapsed)
+}
 
 /// Calculate delay for a given attempt using exponential backoff
-fn calculate_delay(attempt: u32) -> Duration {
+fn calculate_delay(retry: &RetryConfig, attempt: u32) -> Duration {
     if attempt == 0 {
         return Duration::from_millis(0);
     }
 
     // Exponential backoff: initial_delay * 2^(attempt-1)
-    let delay_ms = INITIAL_DELAY_MS * 2u64.pow(attempt - 1);
+    let delay_ms = retry
+        .initial_delay_ms
+        .saturating_mul(2u64.saturating_pow(attempt - 1));
 
     // Cap at maximum delay
-    let capped_delay = delay_ms.min(MAX_DELAY_MS);
+    let capped_delay = delay_ms.min(retry.max_delay_ms);
 
     Duration::from_millis(capped_delay)
 }
 
 /// Download text content from URL with retry logic
-pub async fn download_text(url: &str) -> Result<String> {
+pub async fn download_text(url: &str, options: &DownloadOptions) -> Result<String> {
     log_info!("Starting text download from {}", url);
 
-    let mut attempt = 0;
+    let _permit = acquire_permit(options).await?;
+
+    with_retry(url, &options.retry, options.cancel.as_ref(), || async {
+        decode_subscription_body(&download_attempt(url, options).await?)
+    })
+    .await
+}
+
+/// Download raw bytes (e.g. a release archive) from URL with retry logic
+pub async fn download_bytes(url: &str, options: &DownloadOptions) -> Result<Vec<u8>> {
+    log_info!("Starting download from {}", url);
+
+    let _permit = acquire_permit(options).await?;
+
+    with_retry(url, &options.retry, options.cancel.as_ref(), || {
+        download_attempt(url, options)
+    })
+    .await
+}
+
+/// Size of the body at `url` as announced by the server in answer to a
+/// HEAD request, or `None` when it can't be told.
+pub async fn content_length(url: &str, options: &DownloadOptions) -> Option<u64> {
+    let request = download_client(&options.timeout).head(url).send();
+    let response = match with_idle_timeout(options.timeout.idle(), request).await {
+        Ok(Ok(response)) if response.status().is_success() => response,
+        _ => return None,
+    };
+    // Read from the header: the body of a HEAD response is always empty.
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Download `url` into `dest`. The body is streamed to `<dest>.part`, which
+/// replaces `dest` once complete and is removed if the download fails or is
+/// cancelled, so `dest` is never left half-written.
+pub async fn download_file(url: &str, dest: &Path, options: &DownloadOptions) -> Result<()> {
+    download_file_checked(url, dest, options, None).await
+}
+
+/// `download_file`, also failing when the body doesn't hash to `sha256`
+/// (hex). A mismatching download is removed like a failed one.
+pub async fn download_file_checked(
+    url: &str,
+    dest: &Path,
+    options: &DownloadOptions,
+    sha256: Option<&str>,
+) -> Result<()> {
+    log_info!("Starting download from {} to {}", url, dest.display());
+
+    let _permit = acquire_permit(options).await?;
+
+    let part = &part_path(dest);
+    let result = with_retry(
+        url,
+        &options.retry,
+        options.cancel.as_ref(),
+        move || async move {
+            let mut file = std::fs::File::create(part)?;
+            let mut hasher = Sha256::new();
+            download_attempt_with(url, options, |chunk| {
+                hasher.update(chunk);
+                Ok(file.write_all(chunk)?)
+            })
+            .await?;
+            file.sync_all()?;
+            Ok(format!("{:x}", hasher.finalize()))
+        },
+    )
+    .await
+    .and_then(|actual| match sha256 {
+        Some(expected) => verify_sha256(url, expected, &actual),
+        None => Ok(()),
+    });
+
+    match result {
+        Ok(()) => Ok(std::fs::rename(part, dest)?),
+        Err(e) => {
+            if std::fs::remove_file(part).is_ok() {
+                log_debug!("Removed partial download {}", part.display());
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Lowercase hex SHA256 of `bytes`.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Fail unless the `actual` hex SHA256 of the download of `url` is
+/// `expected`. Catches error pages a mirror serves with a 200 status.
+pub fn verify_sha256(url: &str, expected: &str, actual: &str) -> Result<()> {
+    if expected.eq_ignore_ascii_case(actual) {
+        log_debug!("SHA256 of {} verified", url);
+        return Ok(());
+    }
+    Err(CrashError::Download(format!(
+        "SHA256 mismatch for {}: expected {}, got {}",
+        url, expected, actual
+    )))
+}
+
+/// Checksum from a `.sha256` sidecar in `sha256sum` format (`<hex>  <name>`)
+/// or just the hex digest; `None` if it doesn't start with one.
+pub fn parse_sha256_sidecar(text: &str) -> Option<String> {
+    let sum = text.split_whitespace().next()?;
+    (sum.len() == 64 && sum.chars().all(|c| c.is_ascii_hexdigit())).then(|| sum.to_lowercase())
+}
+
+/// Where `download_file` writes `dest` until the download is complete.
+fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+/// Wait for a download slot when concurrency is limited. The slot is held
+/// until the returned permit is dropped.
+pub async fn acquire_permit(
+    options: &DownloadOptions,
+) -> Result<Option<tokio::sync::SemaphorePermit<'static>>> {
+    match download_permits(options.max_concurrent) {
+        Some(permits) => permits
+            .acquire()
+            .await
+            .map(Some)
+            .map_err(|e| CrashError::Download(format!("Download queue closed: {}", e))),
+        None => Ok(None),
+    }
+}
+
+/// Await `fut` unless `cancel` is cancelled first, failing with `CANCELLED`.
+/// The future is dropped at its current await point, between two chunks of
+/// a download at the latest.
+async fn unless_cancelled<T>(
+    cancel: Option<&DownloadCancel>,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(cancel) = cancel else {
+        return fut.await;
+    };
+    tokio::select! {
+        result = fut => result,
+        _ = cancel.cancelled() => Err(CrashError::Download(CANCELLED.to_string())),
+    }
+}
+
+/// Run `attempt` until it succeeds, retries are exhausted, the next retry
+/// would start past the total time budget or `cancel` is cancelled.
+async fn with_retry<T, F, Fut>(
+    url: &str,
+    retry: &RetryConfig,
+    cancel: Option<&DownloadCancel>,
+    mut attempt: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let start = Instant::now();
+    let deadline = retry.max_total().map(|budget| start + budget);
     let mut last_error = None;
 
-    while attempt <= MAX_RETRIES {
-        if attempt > 0 {
-            let delay = calculate_delay(attempt);
-            log_warn!(
-                "Retry attempt {} after {:?} delay for {}",
-                attempt,
-                delay,
-                url
-            );
-            tokio::time::sleep(delay).await;
+    for n in 0..=retry.max_retries {
+        if n > 0 {
+            let delay = calculate_delay(retry, n);
+            if deadline.is_some_and(|deadline| Instant::now() + delay >= deadline) {
+                log_warn!(
+                    "Giving up on {} after {:?}: retry budget exhausted",
+                    url,
+                    start.elapsed()
+                );
+                break;
+            }
+            log_warn!("Retry attempt {} after {:?} delay for {}", n, delay, url);
+            unless_cancelled(cancel, async {
+                tokio::time::sleep(delay).await;
+                Ok(())
+            })
+            .await?;
+        }
+
+        let result = unless_cancelled(cancel, attempt()).await;
+        if result.is_err() && cancel.is_some_and(DownloadCancel::is_cancelled) {
+            log_info!("Download of {} cancelled", url);
+            return Err(CrashError::Download(CANCELLED.to_string()));
         }
 
-        match download_text_attempt(url).await {
-            Ok(text) => {
-                log_info!("Text download completed successfully: {}", url);
-                return Ok(text);
+        match result {
+            Ok(value) => {
+                log_info!("Download completed successfully: {}", url);
+                return Ok(value);
             }
             Err(e) => {
-                log_error!("Download attempt {} failed: {}", attempt + 1, e);
+                log_error!("Download attempt {} failed: {}", n + 1, e);
                 last_error = Some(e);
-                attempt += 1;
             }
         }
     }
@@ -73,15 +496,40 @@ pub async fn download_text(url: &str) -> Result<String> {
         .unwrap_or_else(|| CrashError::Download("Download failed after all retries".to_string())))
 }
 
-/// Single text download attempt
-async fn download_text_attempt(url: &str) -> Result<String> {
+/// Single download attempt, returning the raw response body
+async fn download_attempt(url: &str, options: &DownloadOptions) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    download_attempt_with(url, options, |chunk| {
+        body.extend_from_slice(chunk);
+        Ok(())
+    })
+    .await?;
+    Ok(body)
+}
+
+/// Single download attempt, handing each chunk of the body to `on_chunk`
+async fn download_attempt_with(
+    url: &str,
+    options: &DownloadOptions,
+    mut on_chunk: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<()> {
     log_debug!("Sending HTTP GET request to {}", url);
 
-    let response = new_client()
-        .get(url)
-        .send()
-        .await
-        .map_err(|e| CrashError::Download(format!("HTTP request failed: {}", e)))?;
+    let idle = options.timeout.idle();
+    let request = download_client(&options.timeout).get(url).send();
+    let mut response = with_idle_timeout(idle, request).await?.map_err(|e| {
+        if e.is_redirect() {
+            CrashError::Download(format!("Too many redirects for {}: {}", url, e))
+        } else if e.is_connect() && e.is_timeout() {
+            CrashError::Download(format!("Connection to {} timed out", url))
+        } else {
+            CrashError::Download(format!("HTTP request failed: {}", e))
+        }
+    })?;
+
+    if response.url().as_str() != url {
+        log_debug!("Resolved {} to {}", url, response.url());
+    }
 
     if !response.status().is_success() {
         return Err(CrashError::Download(format!(
@@ -90,11 +538,384 @@ async fn download_text_attempt(url: &str) -> Result<String> {
         )));
     }
 
-    log_debug!("Reading response body as text");
-    let text = response
-        .text()
+    log_debug!("Reading response body");
+    let expected = response.content_length();
+    let start = Instant::now();
+    let mut received = 0;
+    while let Some(chunk) = with_idle_timeout(idle, response.chunk())
+        .await?
+        .map_err(|e| CrashError::Download(format!("Failed to read response body: {}", e)))?
+    {
+        on_chunk(&chunk)?;
+        received += chunk.len() as u64;
+
+        let delay = throttle_delay(received, start.elapsed(), options.rate_limit);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    if let Some(expected) = expected
+        && received != expected
+    {
+        return Err(CrashError::Download(format!(
+            "Incomplete response body: got {} of {} bytes",
+            received, expected
+        )));
+    }
+    Ok(())
+}
+
+/// Why a download or install step failed, told apart by its message since
+/// `ei` reports errors as text only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Timeout,
+    Http,
+    Extraction,
+    Other,
+}
+
+impl FailureKind {
+    pub fn of(message: &str) -> Self {
+        // Every message names the URL, which says nothing about the failure.
+        let message = message
+            .split_whitespace()
+            .filter(|word| !word.contains("://"))
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+        if has(&["timed out", "timeout", "stalled"]) {
+            FailureKind::Timeout
+        } else if has(&[
+            "extract",
+            "archive",
+            "decompress",
+            "not found after installation",
+        ]) {
+            FailureKind::Extraction
+        } else if has(&[
+            "http",
+            "status",
+            "connect",
+            "redirect",
+            "dns",
+            "response body",
+        ]) {
+            FailureKind::Http
+        } else {
+            FailureKind::Other
+        }
+    }
+}
+
+impl std::fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FailureKind::Timeout => "download timed out",
+            FailureKind::Http => "download failed (HTTP/network error)",
+            FailureKind::Extraction => "extraction failed",
+            FailureKind::Other => "failed",
+        })
+    }
+}
+
+/// Await `fut`, failing if nothing arrives for `idle`.
+async fn with_idle_timeout<T>(idle: Option<Duration>, fut: impl Future<Output = T>) -> Result<T> {
+    match idle {
+        Some(idle) => tokio::time::timeout(idle, fut)
+            .await
+            .map_err(|_| CrashError::Download(format!("Download stalled: no data for {:?}", idle))),
+        None => Ok(fut.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_failures() {
+        assert_eq!(
+            FailureKind::of("Download stalled: no data for 30s"),
+            FailureKind::Timeout
+        );
+        assert_eq!(
+            FailureKind::of("HTTP request failed with status: 404 Not Found"),
+            FailureKind::Http
+        );
+        assert_eq!(
+            FailureKind::of("ei: failed (fallback: Failed to extract tar: unexpected EOF)"),
+            FailureKind::Extraction
+        );
+        assert_eq!(
+            FailureKind::of(
+                "Failed to install UI from https://github.com/a/b/ui.tar.gz: Failed to extract tar"
+            ),
+            FailureKind::Extraction
+        );
+        assert_eq!(
+            FailureKind::of("Failed to write https://example.com/geoip.metadb: Permission denied"),
+            FailureKind::Other
+        );
+        assert_eq!(FailureKind::of("Permission denied"), FailureKind::Other);
+    }
+
+    #[test]
+    fn throttle_delay_enforces_rate() {
+        // 1000 bytes at 100 B/s should take 10s; 4s have passed.
+        assert_eq!(
+            throttle_delay(1000, Duration::from_secs(4), 100),
+            Duration::from_secs(6)
+        );
+        // Already slower than the limit: no pause.
+        assert_eq!(
+            throttle_delay(100, Duration::from_secs(4), 100),
+            Duration::ZERO
+        );
+        // Unlimited.
+        assert_eq!(throttle_delay(u64::MAX, Duration::ZERO, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let retry = RetryConfig::default();
+        assert_eq!(calculate_delay(&retry, 1), Duration::from_secs(1));
+        assert_eq!(calculate_delay(&retry, 3), Duration::from_secs(4));
+        assert_eq!(calculate_delay(&retry, 40), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn total_budget_aborts_before_retries_run_out() {
+        let retry = RetryConfig {
+            initial_delay_ms: 10,
+            max_delay_ms: 10,
+            max_retries: 1000,
+            max_total_secs: 1,
+        };
+        let mut attempts = 0;
+        let start = Instant::now();
+        let result: Result<()> = with_retry("test", &retry, None, || {
+            attempts += 1;
+            async { Err(CrashError::Download("boom".to_string())) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert!(attempts < 1000);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn progressing_attempt_outlives_the_budget() {
+        let retry = RetryConfig {
+            max_total_secs: 1,
+            ..Default::default()
+        };
+        let mut attempts = 0;
+        let result: Result<()> = with_retry("test", &retry, None, || {
+            attempts += 1;
+            async {
+                tokio::time::sleep(Duration::from_millis(1500)).await;
+                Err(CrashError::Download("boom".to_string()))
+            }
+        })
+        .await;
+
+        // The slow attempt ran to its end, but no retry started after it.
+        assert!(result.unwrap_err().to_string().contains("boom"));
+        assert_eq!(attempts, 1);
+    }
+
+    /// Serve canned responses on a local port, one connection per response.
+    fn serve(responses: Vec<String>) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 4096];
+                let _ = stream.read(&mut buf);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn follows_redirect_then_ok() {
+        let addr = serve(vec![
+            "HTTP/1.1 302 Found\r\nLocation: /final\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello".to_string(),
+        ]);
+        let text = download_text(&format!("{}/start", addr), &DownloadOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(text, "hello");
+    }
+
+    #[tokio::test]
+    async fn redirect_loop_is_reported() {
+        let redirect =
+            "HTTP/1.1 302 Found\r\nLocation: /a\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let addr = serve(vec![redirect.to_string()]);
+        let options = DownloadOptions {
+            retry: RetryConfig {
+                max_retries: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let err = download_text(&format!("{}/a", addr), &options)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Too many redirects"), "{}", err);
+    }
+
+    /// Serve one response, writing `chunks` with `pause` before each.
+    fn serve_slowly(chunks: Vec<&'static str>, pause: Duration) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf);
+            let len: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                len
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            for chunk in chunks {
+                std::thread::sleep(pause);
+                let _ = stream.write_all(chunk.as_bytes());
+                let _ = stream.flush();
+            }
+        });
+        addr
+    }
+
+    fn idle_options(idle_secs: u64) -> DownloadOptions {
+        DownloadOptions {
+            retry: RetryConfig {
+                max_retries: 0,
+                ..Default::default()
+            },
+            timeout: TimeoutConfig {
+                idle_secs,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_but_progressing_download_is_not_cut_off() {
+        // 1.6s in total, but never more than 0.4s without data.
+        let addr = serve_slowly(vec!["a", "b", "c", "d"], Duration::from_millis(400));
+        let text = download_text(&addr, &idle_options(1)).await.unwrap();
+        assert_eq!(text, "abcd");
+    }
+
+    #[tokio::test]
+    async fn stalled_download_is_cut_off() {
+        let addr = serve_slowly(vec!["a"], Duration::from_secs(10));
+        let start = Instant::now();
+        let err = download_text(&addr, &idle_options(1)).await.unwrap_err();
+        assert!(err.to_string().contains("stalled"), "{}", err);
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn cancelled_download_leaves_no_partial_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("geoip.metadb");
+        let addr = serve_slowly(vec!["a", "b", "c"], Duration::from_millis(300));
+        let cancel = DownloadCancel::new();
+        let options = DownloadOptions {
+            cancel: Some(cancel.clone()),
+            ..idle_options(5)
+        };
+
+        let download = download_file(&addr, &dest, &options);
+        let part = part_path(&dest);
+        let cancel_midway = async {
+            // Wait for the first chunk to land in the partial file.
+            while std::fs::metadata(&part).map_or(true, |m| m.len() == 0) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            cancel.cancel();
+        };
+        let (result, _) = tokio::join!(download, cancel_midway);
+
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CrashError::Download(CANCELLED.into()).to_string()
+        );
+        assert!(!part.exists());
+        assert!(!dest.exists());
+
+        // Later downloads with the same handle fail right away.
+        let err = download_text(&addr, &options).await.unwrap_err();
+        assert!(err.to_string().contains(CANCELLED), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn download_file_replaces_dest_when_complete() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("provider.yaml");
+        std::fs::write(&dest, "old").unwrap();
+        let addr = serve_slowly(vec!["new"], Duration::ZERO);
+        download_file(&addr, &dest, &idle_options(5)).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "new");
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch_removes_the_download() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("geoip.metadb");
+        let sidecar = format!("{}  geoip.metadb\n", sha256_hex(b"db"));
+        let expected = parse_sha256_sidecar(&sidecar).unwrap();
+
+        // A mirror's error page with a 200 status.
+        let addr = serve_slowly(vec!["<html>rate limited</html>"], Duration::ZERO);
+        let err = download_file_checked(&addr, &dest, &idle_options(5), Some(&expected))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("SHA256 mismatch"), "{}", err);
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+
+        let addr = serve_slowly(vec!["d", "b"], Duration::ZERO);
+        download_file_checked(
+            &addr,
+            &dest,
+            &idle_options(5),
+            Some(&expected.to_uppercase()),
+        )
         .await
-        .map_err(|e| CrashError::Download(format!("Failed to read response body: {}", e)))?;
+        .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"db");
 
-    Ok(text)
+        assert_eq!(parse_sha256_sidecar("<!DOCTYPE html>"), None);
+    }
+
+    #[test]
+    fn permit_pools_follow_the_configured_limit() {
+        assert!(download_permits(0).is_none());
+        assert_eq!(download_permits(2).unwrap().available_permits(), 2);
+        assert_eq!(download_permits(3).unwrap().available_permits(), 3);
+        assert!(std::ptr::eq(
+            download_permits(2).unwrap(),
+            download_permits(2).unwrap()
+        ));
+    }
 }
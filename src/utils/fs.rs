@@ -1,7 +1,11 @@
 // File system utilities
 
 use crate::error::{CrashError, Result};
-use std::path::Path;
+use guess_target::Target;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
 /// Ensures a directory exists, creating it if necessary
 pub fn ensure_dir(path: &Path) -> Result<()> {
@@ -18,6 +22,12 @@ pub fn ensure_dir(path: &Path) -> Result<()> {
 
 /// Writes content to a file atomically by writing to a temp file first
 pub fn atomic_write(path: &Path, content: &str) -> Result<()> {
+    atomic_write_bytes(path, content.as_bytes())
+}
+
+/// Binary counterpart of `atomic_write`: readers see either the old file or
+/// all of `content`, never a partial write.
+pub fn atomic_write_bytes(path: &Path, content: &[u8]) -> Result<()> {
     // Ensure parent directory exists
     if let Some(parent) = path.parent() {
         ensure_dir(parent)?;
@@ -52,7 +62,322 @@ pub fn atomic_write(path: &Path, content: &str) -> Result<()> {
     Ok(())
 }
 
+/// Writes `(name, content)` entries into a new deflate-compressed zip at `path`
+pub fn write_zip(path: &Path, entries: &[(String, String)]) -> Result<()> {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    if let Some(parent) = path.parent() {
+        ensure_dir(parent)?;
+    }
+
+    let zip_err = |e: zip::result::ZipError| {
+        CrashError::External(format!("Failed to write {}: {}", path.display(), e))
+    };
+    let file = std::fs::File::create(path)?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    for (name, content) in entries {
+        zip.start_file(name.as_str(), options).map_err(zip_err)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish().map_err(zip_err)?;
+
+    Ok(())
+}
+
+/// Checks whether files can be created in `dir` (creating it if missing),
+/// e.g. to detect read-only router overlays before persisting anything.
+pub fn is_writable(dir: &Path) -> bool {
+    if ensure_dir(dir).is_err() {
+        return false;
+    }
+
+    let probe = dir.join(".crash_write_test");
+    let ok = std::fs::write(&probe, b"").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    ok
+}
+
+/// Notices changes to a file by polling its modification time. A change
+/// is reported once the file has stayed unchanged for `debounce`, so an
+/// editor's several writes count as one.
+pub struct MtimeWatcher {
+    path: PathBuf,
+    debounce: Duration,
+    seen: Option<SystemTime>,
+    changed_at: Option<Instant>,
+}
+
+impl MtimeWatcher {
+    pub fn new(path: PathBuf, debounce: Duration) -> Self {
+        let seen = modified(&path);
+        Self {
+            path,
+            debounce,
+            seen,
+            changed_at: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file changed and has settled since the last change
+    /// reported.
+    pub fn poll(&mut self) -> bool {
+        let current = modified(&self.path);
+        if current != self.seen {
+            self.seen = current;
+            self.changed_at = Some(Instant::now());
+            return false;
+        }
+        match self.changed_at {
+            Some(at) if at.elapsed() >= self.debounce => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
 /// Checks if a file exists
 pub fn file_exists(path: &Path) -> bool {
     path.exists() && path.is_file()
 }
+
+/// CPU architecture of an executable or a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    Mips,
+    RiscV,
+}
+
+impl Arch {
+    /// Architecture `target` builds for, if known.
+    pub fn of_target(target: &Target) -> Option<Self> {
+        let name = format!("{:?}", target).to_lowercase();
+        [
+            ("x86_64", Arch::X86_64),
+            ("aarch64", Arch::Aarch64),
+            ("arm", Arch::Arm),
+            ("thumb", Arch::Arm),
+            ("i586", Arch::X86),
+            ("i686", Arch::X86),
+            ("mips", Arch::Mips),
+            ("riscv", Arch::RiscV),
+        ]
+        .into_iter()
+        .find_map(|(prefix, arch)| name.starts_with(prefix).then_some(arch))
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Arch::X86 => "x86",
+            Arch::X86_64 => "x86_64",
+            Arch::Arm => "arm",
+            Arch::Aarch64 => "aarch64",
+            Arch::Mips => "mips",
+            Arch::RiscV => "riscv",
+        })
+    }
+}
+
+/// Mach-O magic numbers as the first bytes of the file: 32- and 64-bit
+/// little-endian, and universal (fat) binaries.
+const MACHO_MAGICS: [[u8; 4]; 3] = [
+    [0xce, 0xfa, 0xed, 0xfe],
+    [0xcf, 0xfa, 0xed, 0xfe],
+    [0xca, 0xfe, 0xba, 0xbe],
+];
+
+/// Whether the file at `path` starts like an ELF, PE or Mach-O executable,
+/// rather than e.g. an error page a mirror served instead of the binary.
+pub fn is_executable_file(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    let read = std::fs::File::open(path).and_then(|mut file| file.read_exact(&mut magic));
+    read.is_ok()
+        && (magic == *b"\x7fELF" || magic.starts_with(b"MZ") || MACHO_MAGICS.contains(&magic))
+}
+
+/// Architecture of the executable at `path`, from the ELF `e_machine`, PE
+/// `Machine` or Mach-O `cputype` header field. `None` when it is none of
+/// them, a universal binary or unrecognized.
+pub fn detect_binary_arch(path: &Path) -> Option<Arch> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut header = [0u8; 64];
+    file.read_exact(&mut header).ok()?;
+
+    if header.starts_with(b"\x7fELF") {
+        let machine = match header[5] {
+            1 => u16::from_le_bytes([header[18], header[19]]),
+            2 => u16::from_be_bytes([header[18], header[19]]),
+            _ => return None,
+        };
+        return match machine {
+            3 => Some(Arch::X86),
+            62 => Some(Arch::X86_64),
+            40 => Some(Arch::Arm),
+            183 => Some(Arch::Aarch64),
+            8 => Some(Arch::Mips),
+            243 => Some(Arch::RiscV),
+            _ => None,
+        };
+    }
+
+    if header.starts_with(b"MZ") {
+        let offset = u32::from_le_bytes([header[60], header[61], header[62], header[63]]);
+        let mut pe = [0u8; 6];
+        file.seek(SeekFrom::Start(offset.into())).ok()?;
+        file.read_exact(&mut pe).ok()?;
+        if !pe.starts_with(b"PE\0\0") {
+            return None;
+        }
+        return match u16::from_le_bytes([pe[4], pe[5]]) {
+            0x014c => Some(Arch::X86),
+            0x8664 => Some(Arch::X86_64),
+            0x01c0 | 0x01c4 => Some(Arch::Arm),
+            0xaa64 => Some(Arch::Aarch64),
+            _ => None,
+        };
+    }
+
+    if MACHO_MAGICS[..2]
+        .iter()
+        .any(|magic| header.starts_with(magic))
+    {
+        return match u32::from_le_bytes([header[4], header[5], header[6], header[7]]) {
+            7 => Some(Arch::X86),
+            0x0100_0007 => Some(Arch::X86_64),
+            12 => Some(Arch::Arm),
+            0x0100_000c => Some(Arch::Aarch64),
+            _ => None,
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_write_bytes_keeps_the_old_file_until_done() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("geoip.metadb");
+        std::fs::write(&path, b"old").unwrap();
+
+        // A write that fails half way (here: the temp file can't be
+        // created) leaves the real file untouched.
+        std::fs::create_dir(path.with_extension("tmp")).unwrap();
+        assert!(atomic_write_bytes(&path, &[0, 1, 2]).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        std::fs::remove_dir(path.with_extension("tmp")).unwrap();
+
+        atomic_write_bytes(&path, &[0, 1, 2]).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), [0, 1, 2]);
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn detects_executables_and_their_architectures() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf[5] = 1;
+        elf[18..20].copy_from_slice(&183u16.to_le_bytes());
+        let path = tmp.path().join("elf");
+        std::fs::write(&path, &elf).unwrap();
+        assert_eq!(detect_binary_arch(&path), Some(Arch::Aarch64));
+
+        let mut pe = vec![0u8; 0x86];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[60..64].copy_from_slice(&0x80u32.to_le_bytes());
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+        let path = tmp.path().join("pe.exe");
+        std::fs::write(&path, &pe).unwrap();
+        assert_eq!(detect_binary_arch(&path), Some(Arch::X86_64));
+
+        assert!(is_executable_file(&path));
+
+        let mut macho = vec![0u8; 64];
+        macho[..4].copy_from_slice(&MACHO_MAGICS[1]);
+        macho[4..8].copy_from_slice(&0x0100_000cu32.to_le_bytes());
+        let path = tmp.path().join("macho");
+        std::fs::write(&path, &macho).unwrap();
+        assert_eq!(detect_binary_arch(&path), Some(Arch::Aarch64));
+        assert!(is_executable_file(&path));
+
+        let path = tmp.path().join("script");
+        std::fs::write(&path, "#!/bin/sh\n".repeat(10)).unwrap();
+        assert_eq!(detect_binary_arch(&path), None);
+        assert!(!is_executable_file(&path));
+
+        let path = tmp.path().join("mihomo");
+        std::fs::write(&path, "<html><body>429 Too Many Requests</body></html>").unwrap();
+        assert!(!is_executable_file(&path));
+
+        assert_eq!(
+            Arch::of_target(&Target::Aarch64UnknownLinuxMusl),
+            Some(Arch::Aarch64)
+        );
+        assert_eq!(
+            Arch::of_target(&Target::X86_64PcWindowsMsvc),
+            Some(Arch::X86_64)
+        );
+    }
+
+    #[test]
+    fn mtime_watcher_reports_a_settled_change_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.yaml");
+        std::fs::write(&path, "a").unwrap();
+        let mut watcher = MtimeWatcher::new(path.clone(), Duration::ZERO);
+        assert!(!watcher.poll());
+
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        // The first poll only sees the change start.
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        // Removing the file is a change too.
+        std::fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+    }
+
+    #[test]
+    fn is_writable_detects_unusable_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(is_writable(tmp.path()));
+        assert!(is_writable(&tmp.path().join("new/nested")));
+        assert!(!tmp.path().join(".crash_write_test").exists());
+
+        // A directory can't be created beneath a regular file.
+        let file = tmp.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(!is_writable(&file.join("sub")));
+    }
+}
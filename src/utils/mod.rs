@@ -1,27 +1,46 @@
 // Utility modules for shared functionality
+pub mod archive;
 pub mod command;
+pub mod decode;
 pub mod download;
 pub mod fs;
 pub mod monitor;
 pub mod path;
+pub mod pidfile;
 pub mod process;
 pub mod time;
-pub use fs::{atomic_write, ensure_dir, file_exists};
+pub use fs::{atomic_write, ensure_dir, file_exists, is_writable};
 use std::path::Path;
-pub use time::{current_timestamp, format_uptime};
+use std::sync::OnceLock;
+pub use time::{current_timestamp, format_age, format_hours, format_uptime, parse_hours};
 
 use crate::utils::command::execute;
 
+/// Name of the current user, resolved once per process.
+///
+/// Checks `USER` (unix), `USERNAME` (Windows) and `LOGNAME` before falling
+/// back to spawning `whoami`, since cron jobs often run without `USER`.
 pub fn get_user() -> String {
-    if let Ok(v) = std::env::var("USER") {
-        return v;
-    }
-
-    if let Ok(v) = execute("whoami", &[]) {
-        return v;
-    }
+    static USER: OnceLock<String> = OnceLock::new();
+    USER.get_or_init(|| {
+        user_from_env(|key| std::env::var(key).ok())
+            .or_else(|| {
+                execute("whoami", &[])
+                    .ok()
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+            })
+            .unwrap_or_else(|| "UNKNOWN".to_string())
+    })
+    .clone()
+}
 
-    "UNKNOWN".to_string()
+/// First non-empty user name among the env vars consulted by `get_user`.
+fn user_from_env(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    ["USER", "USERNAME", "LOGNAME"]
+        .into_iter()
+        .filter_map(var)
+        .find(|v| !v.trim().is_empty())
 }
 
 /// Compute the total size of a directory tree in bytes.
@@ -73,6 +92,66 @@ pub fn is_url(s: &str) -> bool {
     s.starts_with("http://") || s.starts_with("https://")
 }
 
+/// Turn a pasted subscription source into what `config url` stores: surrounding
+/// whitespace and quotes are dropped and an app deep link such as
+/// `clash://install-config?url=...` or `sub://...?url=...` is replaced by
+/// the URL it wraps. Anything that is then neither an http(s) URL nor an
+/// existing file is rejected.
+pub fn normalize_subscription_url(input: &str) -> Result<String, String> {
+    let source = input.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+    if source.is_empty() || is_url(source) || Path::new(source).exists() {
+        return Ok(source.to_string());
+    }
+
+    if let Ok(link) = reqwest::Url::parse(source)
+        && let Some((_, url)) = link.query_pairs().find(|(key, _)| key == "url")
+    {
+        let url = url.trim();
+        if is_url(url) {
+            return Ok(url.to_string());
+        }
+        return Err(format!(
+            "{} link wraps {:?}, which is not an http(s) URL",
+            link.scheme(),
+            url
+        ));
+    }
+
+    Err(format!(
+        "{:?} is neither an http(s) URL, a clash:// / sub:// link with a url= \
+         parameter, nor an existing file",
+        source
+    ))
+}
+
+/// Whether `name` can be used as an environment variable name: ASCII
+/// letters, digits and `_`, not starting with a digit.
+pub fn is_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse an environment variable name (clap value parser).
+pub fn parse_env_name(s: &str) -> Result<String, String> {
+    if is_env_name(s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "invalid environment variable name '{}' (letters, digits and _ only)",
+            s
+        ))
+    }
+}
+
+/// Parse a `KEY=VALUE` environment assignment (clap value parser).
+pub fn parse_env_pair(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", s))?;
+    Ok((parse_env_name(key)?, value.to_string()))
+}
+
 /// Probe `url` via the shared HTTP client with a 5s timeout.
 ///
 /// Used as a proxy health check: under TUN + `auto-route` (the mihomo
@@ -89,3 +168,135 @@ pub async fn check_connectivity(url: &str) -> bool {
         _ => false,
     }
 }
+
+/// GET `url` with the shared HTTP client and a per-request `timeout`,
+/// returning the response status and round-trip latency.
+pub async fn probe_url(
+    url: &str,
+    timeout: std::time::Duration,
+) -> crate::Result<(reqwest::StatusCode, std::time::Duration)> {
+    use crate::CrashError;
+
+    let start = std::time::Instant::now();
+    let response = crate::utils::download::new_client()
+        .get(url)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| CrashError::Download(format!("Request to {} failed: {}", url, e)))?;
+
+    Ok((response.status(), start.elapsed()))
+}
+
+/// Fetch `url` through the local HTTP proxy on `127.0.0.1:proxy_port` and
+/// return the response status and round-trip latency.
+///
+/// Unlike `check_connectivity`, traffic is explicitly sent to the core's
+/// proxy port, so this verifies the proxy end-to-end even without TUN.
+pub async fn ping_via_proxy(
+    url: &str,
+    proxy_port: u16,
+    timeout: std::time::Duration,
+) -> crate::Result<(reqwest::StatusCode, std::time::Duration)> {
+    use crate::CrashError;
+
+    let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{}", proxy_port))?;
+    let client = reqwest::Client::builder()
+        .proxy(proxy)
+        .timeout(timeout)
+        .build()?;
+
+    let start = std::time::Instant::now();
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| CrashError::Download(format!("Request via proxy failed: {}", e)))?;
+
+    Ok((response.status(), start.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: Vec<(String, String)> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+    }
+
+    #[test]
+    fn user_from_env_prefers_user_then_username_then_logname() {
+        assert_eq!(
+            user_from_env(env(&[("LOGNAME", "log"), ("USER", "root")])),
+            Some("root".to_string())
+        );
+        assert_eq!(
+            user_from_env(env(&[("USERNAME", "win"), ("LOGNAME", "log")])),
+            Some("win".to_string())
+        );
+        assert_eq!(
+            user_from_env(env(&[("USER", ""), ("LOGNAME", "log")])),
+            Some("log".to_string())
+        );
+        assert_eq!(user_from_env(env(&[])), None);
+    }
+
+    #[test]
+    fn normalizes_subscription_urls() {
+        let url = "https://example.com/sub?token=abc&flag=meta";
+        assert_eq!(normalize_subscription_url(url).as_deref(), Ok(url));
+        assert_eq!(
+            normalize_subscription_url(&format!("  \"{}\"\n", url)).as_deref(),
+            Ok(url)
+        );
+        assert_eq!(
+            normalize_subscription_url(&format!(" '{}' ", url)).as_deref(),
+            Ok(url)
+        );
+        assert_eq!(
+            normalize_subscription_url(
+                "clash://install-config?url=https%3A%2F%2Fexample.com%2Fsub%3Ftoken%3Dabc%26flag%3Dmeta&name=x"
+            )
+            .as_deref(),
+            Ok(url)
+        );
+        assert_eq!(
+            normalize_subscription_url("sub://import?url=https://example.com/s").as_deref(),
+            Ok("https://example.com/s")
+        );
+        assert_eq!(normalize_subscription_url("  ").as_deref(), Ok(""));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("config.yaml");
+        std::fs::write(&file, "").unwrap();
+        let path = file.to_string_lossy().to_string();
+        assert_eq!(normalize_subscription_url(&path), Ok(path.clone()));
+
+        assert!(normalize_subscription_url("clash://install-config?url=ftp://x").is_err());
+        assert!(normalize_subscription_url("clash://install-config?name=x").is_err());
+        assert!(normalize_subscription_url("example.com/sub").is_err());
+    }
+
+    #[test]
+    fn parses_env_pairs() {
+        assert_eq!(
+            parse_env_pair("HTTP_PROXY=http://127.0.0.1:8080?a=b"),
+            Ok((
+                "HTTP_PROXY".to_string(),
+                "http://127.0.0.1:8080?a=b".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_env_pair("DEBUG="),
+            Ok(("DEBUG".to_string(), String::new()))
+        );
+        assert!(parse_env_pair("DEBUG").is_err());
+        assert!(parse_env_pair("1KEY=x").is_err());
+        assert!(parse_env_pair("MY-KEY=x").is_err());
+        assert!(parse_env_pair("=x").is_err());
+    }
+}
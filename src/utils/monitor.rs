@@ -13,96 +13,73 @@ use std::time::Duration;
 /// Get the best LAN IP address, filtering out TUN/TAP virtual interfaces.
 ///
 /// On Linux, Mihomo/Clash TUN mode creates a virtual interface (commonly
-/// with IP in `198.18.0.0/15`). This function skips those and prefers
-/// physical/bridge interfaces like `eth0`, `br0`, `wlan0`, etc.
-#[cfg(unix)]
+/// with IP in `198.18.0.0/15`). Those are skipped so the dashboard URL in
+/// `status` points at an address the user can actually reach.
 fn get_lan_ip() -> Option<IpAddr> {
-    use std::ffi::CStr;
-
-    let mut ifaces: *mut libc::ifaddrs = std::ptr::null_mut();
-    if unsafe { libc::getifaddrs(&mut ifaces) } != 0 {
-        return local_ip_address::local_ip().ok();
-    }
-
-    let mut best_ip: Option<IpAddr> = None;
-    let mut fallback_ip: Option<IpAddr> = None;
-
-    let mut current = ifaces;
-    while !current.is_null() {
-        unsafe {
-            let ifa_addr = (*current).ifa_addr;
-            let ifa_name = (*current).ifa_name;
-
-            if ifa_addr.is_null() || ifa_name.is_null() {
-                current = (*current).ifa_next;
-                continue;
-            }
-
-            let family = (*ifa_addr).sa_family as libc::c_int;
-            if family != libc::AF_INET {
-                current = (*current).ifa_next;
-                continue;
-            }
-
-            let name = CStr::from_ptr(ifa_name).to_string_lossy();
-
-            // Parse IPv4 from sockaddr_in
-            let sockaddr = &*(ifa_addr as *const libc::sockaddr_in);
-            let octets = sockaddr.sin_addr.s_addr.to_ne_bytes();
-            let ip = IpAddr::from(octets);
-
-            // Skip loopback
-            if ip.is_loopback() {
-                current = (*current).ifa_next;
-                continue;
-            }
+    local_ip_address::list_afinet_netifas()
+        .ok()
+        .and_then(|ifaces| select_lan_ip(&ifaces))
+        .or_else(|| local_ip_address::local_ip().ok())
+}
 
-            // Skip TUN/TAP interface names
-            let name_lower = name.to_lowercase();
-            if name_lower.starts_with("tun")
-                || name_lower.starts_with("tap")
-                || name_lower.starts_with("utun")
-                || name_lower.starts_with("zt")
-            {
-                current = (*current).ifa_next;
-                continue;
-            }
+/// Pick the address users most likely reach the dashboard on from a list
+/// of `(interface name, address)` candidates.
+///
+/// Loopback, link-local and TUN addresses are never chosen. Among the rest,
+/// private IPv4 addresses (192.168/16, 10/8, 172.16/12) win over other IPv4
+/// addresses, which win over IPv6; ties go to physical/bridge interfaces
+/// and then to the first candidate listed.
+fn select_lan_ip(candidates: &[(String, IpAddr)]) -> Option<IpAddr> {
+    candidates
+        .iter()
+        .filter(|(name, ip)| !is_virtual_iface(name) && !is_unusable_ip(ip))
+        .min_by_key(|(name, ip)| {
+            let family = match ip {
+                IpAddr::V4(v4) if v4.is_private() => 0,
+                IpAddr::V4(_) => 1,
+                IpAddr::V6(_) => 2,
+            };
+            let iface = if is_physical_iface(name) { 0 } else { 1 };
+            (family, iface)
+        })
+        .map(|(_, ip)| *ip)
+}
 
-            // Skip IPs in the 198.18.0.0/15 range (used by Clash/Mihomo TUN)
-            if is_tun_ip_range(&ip) {
-                current = (*current).ifa_next;
-                continue;
-            }
+/// TUN/TAP and overlay interface names that never carry the LAN address.
+fn is_virtual_iface(name: &str) -> bool {
+    let name = name.to_lowercase();
+    [
+        "tun", "tap", "utun", "zt", "meta", "mihomo", "singbox", "wg",
+    ]
+    .iter()
+    .any(|prefix| name.starts_with(prefix))
+}
 
-            // Prefer physical/bridge interfaces
-            if name_lower.starts_with("eth")
-                || name_lower.starts_with("en")
-                || name_lower.starts_with("br")
-                || name_lower.starts_with("wlan")
-                || name_lower.starts_with("wl")
-                || name_lower.starts_with("bond")
-            {
-                best_ip = Some(ip);
-                break;
-            }
+/// Physical/bridge interface names, preferred when ranking candidates.
+fn is_physical_iface(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["eth", "en", "br", "wlan", "wl", "bond", "lan"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
 
-            if fallback_ip.is_none() {
-                fallback_ip = Some(ip);
-            }
-            current = (*current).ifa_next;
+/// Addresses that cannot be used to reach the dashboard from the LAN.
+fn is_unusable_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback() || v4.is_link_local() || v4.is_unspecified() || is_tun_ip_range(ip)
+        }
+        IpAddr::V6(v6) => {
+            v6.is_loopback()
+                || v6.is_unspecified()
+                // fe80::/10 link-local
+                || (v6.segments()[0] & 0xffc0) == 0xfe80
         }
     }
-
-    unsafe { libc::freeifaddrs(ifaces) };
-
-    best_ip
-        .or(fallback_ip)
-        .or_else(|| local_ip_address::local_ip().ok())
 }
 
 /// Check if an IP falls in the 198.18.0.0/15 range commonly used by
 /// Clash/Mihomo TUN virtual devices.
-#[cfg(unix)]
 fn is_tun_ip_range(ip: &IpAddr) -> bool {
     match ip {
         IpAddr::V4(v4) => {
@@ -113,10 +90,12 @@ fn is_tun_ip_range(ip: &IpAddr) -> bool {
     }
 }
 
-/// Get the best LAN IP (Windows: delegate to `local_ip_address`).
-#[cfg(windows)]
-fn get_lan_ip() -> Option<IpAddr> {
-    local_ip_address::local_ip().ok()
+/// Format `ip:port` for use in a URL, bracketing IPv6 addresses.
+fn format_host_port(ip: &IpAddr, port: &str) -> String {
+    match ip {
+        IpAddr::V4(v4) => format!("{}:{}", v4, port),
+        IpAddr::V6(v6) => format!("[{}]:{}", v6, port),
+    }
 }
 
 /// Get memory usage for a process by PID (Unix only)
@@ -203,11 +182,68 @@ async fn lookup_public_ip() -> String {
     }
 }
 
+/// Push the core version, pid and memory lines; returns whether the core
+/// is running. Only spawns the core (`-v`) and a process lookup, so it is
+/// cheap enough for frequent polling. `offline` skips spawning the core and
+/// shows the name only.
+fn push_core_lines(
+    config: &CrashConfig,
+    offline: bool,
+    lines: &mut Vec<(&'static str, String)>,
+) -> bool {
+    if offline {
+        lines.push((
+            "core",
+            format!("{} ({})", config.core.name(), config.core.github()),
+        ));
+    } else if let Ok(ver) = config.get_version() {
+        lines.push((
+            "core",
+            format!("{} {} ({})", config.core.name(), ver, config.core.github()),
+        ));
+    }
+
+    let Ok(pid) = get_pid(&config.core.exe_name()) else {
+        return false;
+    };
+
+    lines.push(("pid", pid.to_string()));
+    if let Ok(memory) = get_memory_usage(pid) {
+        lines.push(("memory", format_size(memory)));
+    }
+    true
+}
+
+/// Running state icon, uptime and max-runtime budget.
+fn status_text(config: &CrashConfig, is_running: bool) -> String {
+    let status_icon = if is_running { "✅" } else { "❌" };
+    // Running without a start time (started outside crash): the uptime
+    // is unknown, not "since 1970".
+    let uptime = match (is_running, config.start_time) {
+        (true, 0) => "unknown".to_string(),
+        (true, start_time) => format_uptime(start_time),
+        (false, _) => "0s".to_string(),
+    };
+
+    if config.max_runtime_hours > 0 {
+        format!(
+            "{} {} (max: {}h)",
+            status_icon, uptime, config.max_runtime_hours
+        )
+    } else {
+        format!("{} {}", status_icon, uptime)
+    }
+}
+
 /// Collect all status key/value pairs using blocking operations only:
 /// subprocess calls (`get_version`, `get_pid`, `tasklist`/`pidof`), a
 /// recursive directory-size walk, and `fs4` disk-space queries. Designed to
 /// be run on a `spawn_blocking` thread so the async runtime is not stalled.
-fn build_status_lines(config: &CrashConfig, ip_str: &str) -> Vec<(&'static str, String)> {
+fn build_status_lines(
+    config: &CrashConfig,
+    ip_str: Option<&str>,
+    tasks: Option<String>,
+) -> Vec<(&'static str, String)> {
     let mut lines: Vec<(&'static str, String)> = vec![(
         "version",
         format!(
@@ -218,29 +254,19 @@ fn build_status_lines(config: &CrashConfig, ip_str: &str) -> Vec<(&'static str,
         ),
     )];
 
-    let exe = config.core.exe_name();
-    let core_name = config.core.name();
-    if let Ok(ver) = config.get_version() {
-        lines.push((
-            "core",
-            format!("{} {} ({})", core_name, ver, config.core.github()),
-        ));
-    }
-
-    let mut is_running = false;
-    if let Ok(pid) = get_pid(&exe) {
-        lines.push(("pid", pid.to_string()));
-        is_running = true;
+    let is_running = push_core_lines(config, ip_str.is_none(), &mut lines);
 
-        if let Ok(memory) = get_memory_usage(pid) {
-            lines.push(("memory", format_size(memory)));
-        }
+    if let Some(ip_str) = ip_str {
+        lines.push(("ip", ip_str.to_string()));
     }
 
-    lines.push(("ip", ip_str.to_string()));
-
-    if let Some(ip) = get_lan_ip() {
-        let port = config.web.host.split(':').nth(1).unwrap_or("9090");
+    if let Some(ip) = config.web.display_ip.or_else(get_lan_ip) {
+        let port = config
+            .web
+            .host
+            .rsplit_once(':')
+            .map(|(_, port)| port)
+            .unwrap_or("9090");
         let ui_name = config.web.ui_name();
 
         let mut version_str = String::new();
@@ -250,28 +276,19 @@ fn build_status_lines(config: &CrashConfig, ip_str: &str) -> Vec<(&'static str,
 
         lines.push((
             "webui",
-            format!("{}{version_str} (http://{}:{}/ui)", ui_name, ip, port),
+            format!(
+                "{}{version_str} (http://{}/ui)",
+                ui_name,
+                format_host_port(&ip, port)
+            ),
         ));
     }
 
-    let status_icon = if is_running { "✅" } else { "❌" };
-    let uptime = if is_running && config.start_time > 0 {
-        format_uptime(config.start_time)
-    } else {
-        "0s".to_string()
-    };
-
-    let status_text = if config.max_runtime_hours > 0 {
-        format!(
-            "{} {} (max: {}h)",
-            status_icon, uptime, config.max_runtime_hours
-        )
-    } else {
-        format!("{} {}", status_icon, uptime)
-    };
-
-    lines.push(("status", status_text));
-    lines.push(("proxy", config.proxy.to_string()));
+    lines.push(("status", status_text(config, is_running)));
+    if let Some(tasks) = tasks {
+        lines.push(("tasks", tasks));
+    }
+    lines.push(("proxy", config.proxy().to_string()));
     let user_prefix = if is_admin::is_admin() { "#" } else { "$" };
     lines.push(("user", format!("{}{}", user_prefix, get_user())));
 
@@ -291,7 +308,7 @@ fn build_status_lines(config: &CrashConfig, ip_str: &str) -> Vec<(&'static str,
 }
 
 /// Render a list of `(key, value)` pairs as an aligned `key : value` block.
-fn render_lines(lines: &[(&str, String)]) -> String {
+pub fn render_lines(lines: &[(&str, String)]) -> String {
     let key_len = lines.iter().fold(0, |a, b| a.max(b.0.len()));
     lines
         .iter()
@@ -300,19 +317,99 @@ fn render_lines(lines: &[(&str, String)]) -> String {
         .join("\n")
 }
 
+/// Format only the core's version, pid, memory and uptime, skipping the
+/// public-IP lookup, LAN IP detection and directory-size walk.
+pub fn format_core_status(config: &CrashConfig, offline: bool) -> String {
+    let mut lines = Vec::new();
+    let is_running = push_core_lines(config, offline, &mut lines);
+    lines.push(("status", status_text(config, is_running)));
+    render_lines(&lines)
+}
+
 /// Format a comprehensive status string for the application.
 ///
 /// The public-IP lookup is network-bound and stays on the async runtime
 /// (with a timeout). Everything else is blocking work — subprocess calls,
 /// directory walks, disk-space queries — and is dispatched to a blocking
-/// thread pool so it cannot stall the runtime.
-pub async fn format_status(config: &CrashConfig) -> String {
-    let ip_str = lookup_public_ip().await;
+/// thread pool so it cannot stall the runtime. `offline` skips the lookup
+/// and the core version check, leaving local data only. `tasks` is shown
+/// as the scheduled-task line when given.
+pub async fn format_status(config: &CrashConfig, offline: bool, tasks: Option<String>) -> String {
+    let ip_str = if offline {
+        None
+    } else {
+        Some(lookup_public_ip().await)
+    };
 
     let config = config.clone();
-    let lines = tokio::task::spawn_blocking(move || build_status_lines(&config, &ip_str))
-        .await
-        .unwrap_or_else(|e| vec![("error", format!("status build failed: {}", e))]);
+    let lines =
+        tokio::task::spawn_blocking(move || build_status_lines(&config, ip_str.as_deref(), tasks))
+            .await
+            .unwrap_or_else(|e| vec![("error", format!("status build failed: {}", e))]);
 
     render_lines(&lines)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(list: &[(&str, &str)]) -> Vec<(String, IpAddr)> {
+        list.iter()
+            .map(|(name, ip)| (name.to_string(), ip.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn prefers_private_ipv4_over_public_and_ipv6() {
+        let list = candidates(&[
+            ("eth1", "2001:db8::1"),
+            ("ppp0", "203.0.113.7"),
+            ("br-lan", "192.168.1.1"),
+        ]);
+        assert_eq!(select_lan_ip(&list), Some("192.168.1.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn skips_tun_loopback_and_link_local() {
+        let list = candidates(&[
+            ("lo", "127.0.0.1"),
+            ("Meta", "198.18.0.1"),
+            ("utun3", "10.8.0.2"),
+            ("eth0", "169.254.3.4"),
+            ("eth0", "fe80::1"),
+            ("eth0", "10.0.0.5"),
+        ]);
+        assert_eq!(select_lan_ip(&list), Some("10.0.0.5".parse().unwrap()));
+    }
+
+    #[test]
+    fn prefers_physical_interface_on_tie() {
+        let list = candidates(&[("docker0", "172.17.0.1"), ("wlan0", "192.168.0.8")]);
+        assert_eq!(select_lan_ip(&list), Some("192.168.0.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn falls_back_to_ipv6_when_no_ipv4() {
+        let list = candidates(&[("lo", "::1"), ("eth0", "fd00::2")]);
+        assert_eq!(select_lan_ip(&list), Some("fd00::2".parse().unwrap()));
+        assert_eq!(select_lan_ip(&candidates(&[("lo", "127.0.0.1")])), None);
+    }
+
+    #[test]
+    fn formats_ipv6_host_with_brackets() {
+        let ip: IpAddr = "fd00::2".parse().unwrap();
+        assert_eq!(format_host_port(&ip, "9090"), "[fd00::2]:9090");
+    }
+
+    #[test]
+    fn status_text_handles_inconsistent_start_time() {
+        let mut config = CrashConfig::default();
+        // Running but started outside crash: no bogus uptime.
+        assert_eq!(status_text(&config, true), "✅ unknown");
+
+        // Stale timestamp of a dead core is never shown.
+        config.start_time = 1;
+        assert_eq!(status_text(&config, false), "❌ 0s");
+    }
+}
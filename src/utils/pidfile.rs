@@ -0,0 +1,67 @@
+// PID file for long-running crash processes (e.g. `crash daemon`)
+
+use crate::error::{CrashError, Result};
+use crate::log_warn;
+use crate::utils::fs::atomic_write;
+use crate::utils::process::pid_alive;
+use std::path::{Path, PathBuf};
+
+/// A PID file owned by the current process, removed again on drop.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current PID to `path`. Fails if the file names a process
+    /// that is still alive; a stale file is replaced.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        if let Some(pid) = read_pid(path) {
+            if pid_alive(pid) {
+                return Err(CrashError::Process(format!(
+                    "Already running with PID {} ({})",
+                    pid,
+                    path.display()
+                )));
+            }
+            log_warn!("Replacing stale PID file {} (PID {})", path.display(), pid);
+        }
+
+        atomic_write(path, &std::process::id().to_string())?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// PID stored in `path`, if the file exists and holds a number.
+pub fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acquire_replaces_stale_file_and_cleans_up() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("daemon.pid");
+        std::fs::write(&path, "not a pid").unwrap();
+
+        let pidfile = PidFile::acquire(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+
+        // Our own PID is alive, so a second acquire must fail.
+        assert!(PidFile::acquire(&path).is_err());
+
+        drop(pidfile);
+        assert!(!path.exists());
+    }
+}
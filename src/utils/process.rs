@@ -3,7 +3,7 @@
 use crate::error::{CrashError, Result};
 use crate::{log_debug, log_error, log_info};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
 
 /// Start a process with the given executable path and arguments
 pub fn start(exe_path: &Path, args: Vec<String>, envs: Vec<(&str, &str)>) -> Result<()> {
@@ -24,11 +24,28 @@ pub fn start(exe_path: &Path, args: Vec<String>, envs: Vec<(&str, &str)>) -> Res
 
     c.args(&args).envs(envs);
 
+    // Detach the core from crash's terminal so it survives the SSH session
+    // (and the SIGHUP sent to its process group) that started it.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: `setsid` is async-signal-safe and touches no parent state.
+        unsafe {
+            c.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
     #[cfg(target_os = "windows")]
     {
         use std::os::windows::process::CommandExt;
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        c.creation_flags(CREATE_NO_WINDOW);
+        const DETACHED_PROCESS: u32 = 0x00000008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        c.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
     }
 
     c.stdin(Stdio::null())
@@ -44,10 +61,40 @@ pub fn start(exe_path: &Path, args: Vec<String>, envs: Vec<(&str, &str)>) -> Res
             ))
         })?;
 
-    log_info!("Process started successfully: {}", exe_path.display());
+    log_info!(
+        "Process started successfully and detached from the terminal: {}",
+        exe_path.display()
+    );
     Ok(())
 }
 
+/// Start a process attached to crash's terminal, sharing its stdio, for
+/// `start --foreground`. The caller owns (and must reap) the child.
+pub fn start_attached(
+    exe_path: &Path,
+    args: Vec<String>,
+    envs: Vec<(&str, &str)>,
+) -> Result<Child> {
+    log_info!(
+        "Starting process in the foreground: {} with args: {:?}",
+        exe_path.display(),
+        args
+    );
+
+    Command::new(exe_path)
+        .args(&args)
+        .envs(envs)
+        .spawn()
+        .map_err(|e| {
+            log_error!("Failed to start process {}: {}", exe_path.display(), e);
+            CrashError::Process(format!(
+                "Failed to start process {}: {}",
+                exe_path.display(),
+                e
+            ))
+        })
+}
+
 /// Stop a process by name
 pub fn stop(exe_name: &str) -> Result<()> {
     log_info!("Stopping process: {}", exe_name);
@@ -104,9 +151,10 @@ pub fn kill_process(name_or_path: &str) -> Result<()> {
         .and_then(|n| n.to_str())
         .unwrap_or(name_or_path);
 
-    // Try pkill first
+    // Try pkill first. Match the exact process name so that stopping
+    // `mihomo` leaves named instances (`mihomo-<name>`) running.
     if Command::new("pkill")
-        .args(["-f", process_name])
+        .args(["-x", process_name])
         .output()
         .is_ok()
     {
@@ -174,3 +222,76 @@ pub fn kill_process(name_or_path: &str) -> Result<()> {
 pub fn is_running(name: &str) -> bool {
     get_pid(name).is_ok()
 }
+
+/// Whether a process with the given PID exists
+#[cfg(unix)]
+pub fn pid_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // EPERM: it exists but belongs to another user.
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn pid_alive(pid: u32) -> bool {
+    execute(
+        "tasklist",
+        &["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"],
+    )
+    .map(|output| output.contains(&format!("\"{}\"", pid)))
+    .unwrap_or(false)
+}
+
+/// Terminate a process by PID
+#[cfg(unix)]
+pub fn kill_pid(pid: u32) -> Result<()> {
+    let raw = libc::pid_t::try_from(pid)
+        .map_err(|_| CrashError::Process(format!("Invalid PID {}", pid)))?;
+    // SAFETY: plain syscall on a PID we validated above.
+    if unsafe { libc::kill(raw, libc::SIGTERM) } != 0 {
+        return Err(CrashError::Process(format!(
+            "Failed to kill PID {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn kill_pid(pid: u32) -> Result<()> {
+    execute("taskkill", &["/F", "/PID", &pid.to_string()])?;
+    Ok(())
+}
+
+/// PID of the process listening on TCP `port`, if it can be found
+#[cfg(unix)]
+pub fn listening_pid(port: u16) -> Option<u32> {
+    execute(
+        "lsof",
+        &["-nP", "-t", &format!("-iTCP:{}", port), "-sTCP:LISTEN"],
+    )
+    .ok()?
+    .split_whitespace()
+    .next()?
+    .parse()
+    .ok()
+}
+
+#[cfg(windows)]
+pub fn listening_pid(port: u16) -> Option<u32> {
+    // Proto  Local Address  Foreign Address  State  PID
+    let suffix = format!(":{}", port);
+    execute("netstat", &["-ano", "-p", "TCP"])
+        .ok()?
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|cols| cols.len() == 5 && cols[1].ends_with(&suffix) && cols[3] == "LISTENING")?[4]
+        .parse()
+        .ok()
+}
@@ -22,3 +22,143 @@ pub fn format_uptime(start_time: u64) -> String {
     let duration = Duration::from_secs(current - start_time);
     format_duration(duration).to_string()
 }
+
+/// How long ago `time` was, in its largest whole unit (`3d`, `5h`, `12m`).
+pub fn format_age(time: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs();
+    match secs {
+        86400.. => format!("{}d", secs / 86400),
+        3600.. => format!("{}h", secs / 3600),
+        60.. => format!("{}m", secs / 60),
+        _ => format!("{}s", secs),
+    }
+}
+
+/// Parse a duration in whole hours, either as a bare number of hours
+/// (`24`) or a human duration (`36h`, `1d12h`, `2days`).
+pub fn parse_hours(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    if let Ok(hours) = s.parse::<u64>() {
+        return Ok(hours);
+    }
+
+    let duration = humantime::parse_duration(s).map_err(|e| e.to_string())?;
+    let secs = duration.as_secs();
+    if secs % 3600 != 0 || duration.subsec_nanos() != 0 {
+        return Err(format!("{} is not a whole number of hours", s));
+    }
+    Ok(secs / 3600)
+}
+
+/// Format a number of hours as a human duration, e.g. `36` -> `1day 12h`.
+pub fn format_hours(hours: u64) -> String {
+    format_duration(Duration::from_secs(hours * 3600)).to_string()
+}
+
+/// Local wall clock as `(minute, hour, weekday)`, with Sunday = 0 like cron.
+#[cfg(unix)]
+pub fn local_clock() -> Option<(u32, u32, u32)> {
+    let now = libc::time_t::try_from(current_timestamp()).ok()?;
+    // SAFETY: `tm` is plain data, filled in by `localtime_r`.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return None;
+    }
+    Some((tm.tm_min as u32, tm.tm_hour as u32, tm.tm_wday as u32))
+}
+
+/// Whether a cron field (`*`, `*/n`, `n` or a comma list of those) matches
+/// `value`; `None` for syntax this doesn't handle, such as ranges.
+fn cron_field_matches(field: &str, value: u32) -> Option<bool> {
+    let mut matched = false;
+    for part in field.split(',') {
+        matched |= match part {
+            "*" => true,
+            _ => match part.strip_prefix("*/") {
+                Some(step) => value.is_multiple_of(step.parse::<u32>().ok().filter(|&n| n > 0)?),
+                None => part.parse::<u32>().ok()? == value,
+            },
+        };
+    }
+    Some(matched)
+}
+
+/// Minutes from `now` (`local_clock`) until the cron expression `expr` next
+/// fires. Only the minute, hour and weekday fields are evaluated; `None` if
+/// the day-of-month or month field is restricted or the syntax unsupported.
+pub fn cron_minutes_until(expr: &str, now: (u32, u32, u32)) -> Option<u64> {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let [minute, hour, "*", "*", weekday] = fields[..] else {
+        return None;
+    };
+
+    let (min, hr, day) = now;
+    let start = (day * 24 + hr) * 60 + min;
+    const WEEK: u32 = 7 * 24 * 60;
+    for ahead in 1..=WEEK {
+        let t = (start + ahead) % WEEK;
+        let (day, hr, min) = (t / (24 * 60), t / 60 % 24, t % 60);
+        let weekday_matches =
+            cron_field_matches(weekday, day)? || (day == 0 && cron_field_matches(weekday, 7)?);
+        if cron_field_matches(minute, min)? && cron_field_matches(hour, hr)? && weekday_matches {
+            return Some(ahead as u64);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_next_run() {
+        // Wednesday 02:55: the weekly run is 5 minutes away.
+        assert_eq!(cron_minutes_until("0 3 * * 3", (55, 2, 3)), Some(5));
+        // Wednesday 03:00 exactly: next week.
+        assert_eq!(
+            cron_minutes_until("0 3 * * 3", (0, 3, 3)),
+            Some(7 * 24 * 60)
+        );
+        // Saturday 23:59 to Sunday 00:00, with Sunday written as 7.
+        assert_eq!(cron_minutes_until("0 0 * * 7", (59, 23, 6)), Some(1));
+        assert_eq!(cron_minutes_until("*/10 * * * *", (3, 14, 1)), Some(7));
+        assert_eq!(cron_minutes_until("0 3 1 * *", (0, 0, 0)), None);
+        assert_eq!(cron_minutes_until("0 1-5 * * *", (0, 0, 0)), None);
+    }
+
+    #[test]
+    fn parse_hours_accepts_numbers_and_durations() {
+        assert_eq!(parse_hours("24"), Ok(24));
+        assert_eq!(parse_hours("0"), Ok(0));
+        assert_eq!(parse_hours("36h"), Ok(36));
+        assert_eq!(parse_hours("1d12h"), Ok(36));
+        assert_eq!(parse_hours("3days"), Ok(72));
+    }
+
+    #[test]
+    fn parse_hours_rejects_partial_hours_and_garbage() {
+        assert!(parse_hours("90m").is_err());
+        assert!(parse_hours("soon").is_err());
+    }
+
+    #[test]
+    fn format_age_uses_the_largest_unit() {
+        let ago = |secs| SystemTime::now() - Duration::from_secs(secs);
+        assert_eq!(format_age(ago(40 * 86400 + 5000)), "40d");
+        assert_eq!(format_age(ago(7200)), "2h");
+        assert_eq!(format_age(ago(90)), "1m");
+        assert_eq!(
+            format_age(SystemTime::now() + Duration::from_secs(60)),
+            "0s"
+        );
+    }
+
+    #[test]
+    fn format_hours_is_human_readable() {
+        assert_eq!(format_hours(36), "1day 12h");
+    }
+}